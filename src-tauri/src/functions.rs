@@ -7,7 +7,7 @@ use crate::timer::TimerState;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Manager, WindowEvent};
 use winrt_notification::{Duration as winrtDuration, Sound, Toast};
 
@@ -90,6 +90,25 @@ pub fn read_data(pathname: &str) -> Result<String, std::io::Error> {
     fs::read_to_string(path)
 }
 
+// function to save binary data (like images)
+pub fn save_bytes(pathname: &str, data: &[u8]) -> Result<(), std::io::Error> {
+    let path = generate_data_path(pathname); // generate path
+
+    // Create the directory if it doesn't exist
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(path, data)
+}
+
+// function to read binary data from a file
+pub fn read_bytes(pathname: &str) -> Result<Vec<u8>, std::io::Error> {
+    let path = generate_data_path(pathname); // generate path
+
+    fs::read(path)
+}
+
 // function to read contents of a directory
 pub fn read_dir(pathname: &str) -> Result<fs::ReadDir, std::io::Error> {
     let path = generate_data_path(pathname); // generate path
@@ -104,6 +123,13 @@ pub fn delete_file(pathname: &str) -> Result<(), std::io::Error> {
     fs::remove_file(path)
 }
 
+// function to delete a directory with all its contents
+pub fn delete_dir(pathname: &str) -> Result<(), std::io::Error> {
+    let path = generate_data_path(pathname); // generate path
+
+    fs::remove_dir_all(path)
+}
+
 // function to toast a notification
 pub fn notify(title: String, description: String, sound: Option<Sound>, duration: winrtDuration) {
     // use powershell id for sending notification on dev mode and app package id in build mode
@@ -132,3 +158,15 @@ pub fn duration_to_hms(duration: Duration) -> String {
 
     format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
 }
+
+// function to convert a system time to milliseconds since UNIX_EPOCH
+pub fn system_time_to_ms(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+// function to get current time in milliseconds since UNIX_EPOCH
+pub fn current_time_ms() -> u128 {
+    system_time_to_ms(SystemTime::now())
+}
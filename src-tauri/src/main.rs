@@ -19,6 +19,9 @@ pub fn main() {
         state.timers = timers;
     }
 
+    let index_state = reader::index::init_state(); // initiating search index state
+    reader::index::update_in_background(&index_state); // loading the index and indexing books changed since the last run
+
     // creating tauri app instance
     let mut app = tauri::Builder::default();
 
@@ -49,18 +52,69 @@ pub fn main() {
             reader::open_file_dialog,
             reader::load_installed_fonts,
             reader::e_pub_data_get,
-            reader::e_pub_highlight_save,
+            reader::highlights::e_pub_highlight_save,
+            reader::highlights::e_pub_highlights_list,
+            reader::highlights::e_pub_highlight_add,
+            reader::highlights::e_pub_highlight_update,
+            reader::highlights::e_pub_highlight_delete,
+            reader::highlights::pdf_data_get,
+            reader::highlights::pdf_highlights_list,
+            reader::highlights::pdf_highlight_add,
+            reader::highlights::pdf_highlight_update,
+            reader::highlights::pdf_highlight_delete,
+            reader::bookmarks::bookmarks_list,
+            reader::bookmarks::bookmark_add,
+            reader::bookmarks::bookmark_rename,
+            reader::bookmarks::bookmark_delete,
+            reader::export::highlights_export,
+            reader::annotations::annotations_import,
+            reader::annotations::koreader_annotations_export,
+            reader::citation::library_book_citations_get,
+            reader::citation::library_book_citation_set,
+            reader::dictionary::dictionaries_list,
+            reader::dictionary::dictionary_lookup,
+            reader::excerpt::e_pub_chapter_text,
+            reader::excerpt::pdf_pages_text,
+            reader::search::e_pub_search,
+            reader::validate::e_pub_validate,
+            reader::index::library_index_update,
+            reader::index::library_search,
+            reader::pdf_info::pdf_details_get,
+            reader::pdf_text::pdf_text_get,
+            reader::toc::e_pub_toc_get,
+            reader::comic::comic_pages_list,
+            reader::library::library_books_list,
+            reader::library::library_book_open,
+            reader::library::library_book_remove,
+            reader::library::library_book_trust_set,
+            reader::library::library_book_relocate,
+            reader::library::library_books_relocate_in,
+            reader::library::library_shelves_list,
+            reader::library::library_shelf_create,
+            reader::library::library_shelf_rename,
+            reader::library::library_shelf_delete,
+            reader::library::library_shelf_book_add,
+            reader::library::library_shelf_book_remove,
+            reader::position::reading_position_save,
+            reader::position::reading_position_get,
+            reader::stats::reading_stats_get,
+            reader::stats::reading_estimate_get,
+            reader::cover::book_cover_generate,
+            reader::cover::book_cover_save,
             buddy_chat::ask_buddy,
             buddy_chat::get_chat_data,
             buddy_chat::get_chats_list,
             buddy_chat::delete_chat
         ])
+        .register_uri_scheme_protocol("cover", reader::cover::cover_protocol)
+        .register_uri_scheme_protocol("book", reader::resources::book_protocol)
         .on_window_event({
             move |window, event| {
                 on_window_event(window, event, &state_clone);
             }
         })
         .manage(state)
+        .manage(index_state)
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
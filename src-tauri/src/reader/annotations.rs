@@ -0,0 +1,1441 @@
+/*
+    reader module:
+    annotations.rs imports highlights and notes made in other readers (kindle clippings, koreader sidecars
+    and calibre highlight exports) into library books, and exports highlights to koreader sidecars
+*/
+
+// importing crates and modules
+use crate::functions::current_time_ms;
+use crate::reader::cache::open_book;
+use crate::reader::document::decode_text;
+use crate::reader::epub::read_entry_string;
+use crate::reader::export::export_book;
+use crate::reader::highlights::{
+    highlights_key, load_highlights, new_highlight_id, save_highlights, validate_color, Highlight,
+    HighlightRange, Rect,
+};
+use crate::reader::library::{Book, BookFormat, Library};
+use crate::reader::lua::{parse_lua, write_lua, LuaValue};
+use crate::reader::pdf::{load_document, page_size};
+use crate::reader::pdf_text::{extract_pages, PageText};
+use crate::reader::position::cfi_spine_index;
+use crate::reader::search::spine_cfi;
+use crate::reader::stats::civil_date;
+use crate::reader::text::DocumentText;
+use crate::reader::xml::{decode_entities, tokenize, Token};
+use crate::reader::ReaderError;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// line separating the entries of kindle clippings
+const KINDLE_SEPARATOR: &str = "==========";
+
+// month names of kindle clippings, only devices set to english are understood
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+// highlight colors of koreader, other colors are exported as the closest of them
+const KOREADER_COLORS: [&str; 9] = [
+    "red", "orange", "yellow", "green", "olive", "cyan", "blue", "purple", "gray",
+];
+
+// color of imported highlights whose color is unknown
+const DEFAULT_COLOR: &str = "yellow";
+
+// AnnotationSource enum: readers annotations can be imported from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AnnotationSource {
+    Kindle,   // "My Clippings.txt" of kindle devices
+    Koreader, // metadata.<ext>.lua in the <book>.sdr directory next to the book
+    Calibre,  // highlights exported by the calibre viewer (json)
+}
+
+// implementations for AnnotationSource enum
+impl AnnotationSource {
+    // detects the source from the file name (or the .sdr directory of koreader)
+    fn from_path(path: &str) -> Option<Self> {
+        let name = path.trim_end_matches(['/', '\\']).to_ascii_lowercase();
+        if name.ends_with(".sdr") || name.ends_with(".lua") {
+            Some(Self::Koreader)
+        } else if name.ends_with(".json") || name.ends_with(".calibre_highlights") {
+            Some(Self::Calibre)
+        } else if name.ends_with(".txt") {
+            Some(Self::Kindle)
+        } else {
+            None
+        }
+    }
+}
+
+// SourceBook struct: the book annotations belong to, as described by the other reader
+#[derive(Clone, Debug, Default)]
+struct SourceBook {
+    title: Option<String>,
+    authors: Vec<String>,
+    identifiers: Vec<String>,
+    file_stem: Option<String>, // name of the book file without extension
+}
+
+// SourceAnnotation struct: a highlight or note read from another reader
+#[derive(Clone, Debug, Default)]
+struct SourceAnnotation {
+    text: String, // highlighted text, empty for notes not attached to a highlight
+    note: Option<String>,
+    color: Option<String>,
+    created_at: Option<u128>,
+    spine_index: Option<usize>, // chapter the other reader placed the highlight in
+    page: Option<u32>,          // page of pdf highlights
+}
+
+// SourceGroup struct: annotations of one book
+#[derive(Clone, Debug, Default)]
+struct SourceGroup {
+    book: SourceBook,
+    annotations: Vec<SourceAnnotation>,
+}
+
+// ImportedBook struct: how many annotations were added to a library book
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImportedBook {
+    pub book_id: String,
+    pub title: String,
+    pub imported: usize,
+    pub duplicates: usize, // already highlighted ranges
+    pub skipped: usize,    // annotations whose text was not found in the book
+}
+
+// ImportFailure struct: a library book whose annotations could not be imported
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ImportFailure {
+    pub book_id: String,
+    pub title: String,
+    pub error: String,
+}
+
+// ImportReport struct
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ImportReport {
+    pub books: Vec<ImportedBook>,
+    pub unmatched: Vec<String>, // titles of books which are not in the library
+    pub failed: Vec<ImportFailure>, // books which could not be read or saved
+}
+
+// KoreaderExport struct: where the sidecar was written and how many highlights it got
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KoreaderExport {
+    pub path: String,
+    pub exported: usize,
+    pub skipped: usize, // highlights whose position could not be converted
+}
+
+// BookText enum: text of a library book used to find imported highlights
+enum BookText {
+    Epub(Vec<(usize, String, DocumentText)>), // spine index, idref and text of every spine document
+    Pdf(Vec<PageText>),
+    None,
+}
+
+// implementations for BookText enum
+impl BookText {
+    // reads the text of a book, comics have none
+    fn load(book: &Book) -> Result<Self, ReaderError> {
+        match book.format {
+            BookFormat::Comic => Ok(Self::None),
+            BookFormat::Pdf => Ok(Self::Pdf(extract_pages(&book.path, None, None)?)),
+            _ => {
+                let (mut archive, package) = open_book(&book.path)?;
+                let mut chapters = Vec::new();
+                for (index, spine_item) in package.spine.iter().enumerate() {
+                    let Some(item) = package.item_by_id(&spine_item.idref) else {
+                        continue;
+                    };
+                    if let Ok(data) = read_entry_string(&mut archive, &item.href) {
+                        chapters.push((index, spine_item.idref.clone(), DocumentText::new(&data)));
+                    }
+                }
+                Ok(Self::Epub(chapters))
+            }
+        }
+    }
+
+    // finds the range of an annotation, looking at the chapter or page it was made in first
+    fn locate(&self, pattern: &Regex, annotation: &SourceAnnotation) -> Option<HighlightRange> {
+        match self {
+            Self::Epub(chapters) => {
+                let hinted = chapters
+                    .iter()
+                    .filter(|(index, _, _)| Some(*index) == annotation.spine_index);
+                let others = chapters
+                    .iter()
+                    .filter(|(index, _, _)| Some(*index) != annotation.spine_index);
+
+                hinted.chain(others).find_map(|(index, idref, document)| {
+                    let found = pattern.find(&document.text)?;
+                    let range = document.cfi_range(found.start(), found.end())?;
+                    Some(HighlightRange::Epub {
+                        cfi_range: spine_cfi(*index, idref, &range),
+                    })
+                })
+            }
+            Self::Pdf(pages) => {
+                let hinted = pages
+                    .iter()
+                    .filter(|page| Some(page.page) == annotation.page);
+                let others = pages
+                    .iter()
+                    .filter(|page| Some(page.page) != annotation.page);
+
+                hinted.chain(others).find_map(|page| {
+                    let found = pattern.find(&page.text)?;
+                    let rects = pdf_rects(page, found.start(), found.end());
+                    (!rects.is_empty()).then_some(HighlightRange::Pdf {
+                        page: page.page,
+                        rects,
+                    })
+                })
+            }
+            Self::None => None,
+        }
+    }
+}
+
+// function to get the rectangles of text[start..end] of a pdf page, one per line, relative to the page size
+fn pdf_rects(page: &PageText, start: usize, end: usize) -> Vec<Rect> {
+    if page.width <= 0.0 || page.height <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut rects = Vec::new();
+    let mut cursor = 0;
+    for line in &page.lines {
+        // lines appear in the page text in order, separated by new lines
+        let Some(offset) = page.text[cursor..].find(&line.text) else {
+            continue;
+        };
+        let line_start = cursor + offset;
+        let line_end = line_start + line.text.len();
+        cursor = line_end;
+        if line.text.is_empty() || line_end <= start || line_start >= end {
+            continue;
+        }
+
+        // the part of the line covered by the match, assuming characters of equal width
+        let chars = line.text.chars().count() as f64;
+        let covered = |index: usize| {
+            line.text[..index.clamp(line_start, line_end) - line_start]
+                .chars()
+                .count() as f64
+                / chars
+        };
+        let (from, to) = (covered(start), covered(end));
+        let clamp = |value: f64| value.clamp(0.0, 1.0);
+
+        let x = clamp((line.x + line.width * from) / page.width);
+        let y = clamp(line.y / page.height);
+        rects.push(Rect {
+            x,
+            y,
+            width: clamp((line.x + line.width * to) / page.width) - x,
+            height: clamp((line.y + line.font_size) / page.height) - y,
+        });
+    }
+    rects
+}
+
+// function to build a regex finding the text of an annotation in the book
+// whitespace may differ and readers replace quotes and dashes with typographic ones
+fn text_pattern(text: &str) -> Option<Regex> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .map(|c| match c {
+                    '\'' | '‘' | '’' => "['‘’]".to_string(),
+                    '"' | '“' | '”' => "[\"“”]".to_string(),
+                    '-' | '–' | '—' => "[-–—]".to_string(),
+                    _ => regex::escape(&c.to_string()),
+                })
+                .collect()
+        })
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    RegexBuilder::new(&words.join(r"\s+"))
+        .size_limit(1 << 24)
+        .build()
+        .ok()
+}
+
+// function to convert a date to days since 1970-01-01
+// uses the days from civil algorithm for the proleptic gregorian calendar
+fn days_from_civil(year: i128, month: i128, day: i128) -> i128 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// regex matching iso dates with an optional time and zone, e.g. 2024-01-31 10:20:30 or 2024-01-31T10:20:30.123Z
+fn iso_date_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"(\d{4})-(\d{1,2})-(\d{1,2})(?:[T ](\d{1,2}):(\d{2})(?::(\d{2}))?(?:\.\d+)?\s*(Z|[+-]\d{2}:?\d{2})?)?",
+        )
+        .unwrap()
+    })
+}
+
+// regex matching a time of day written out, e.g. 10:01:23 or 11:02 PM
+fn clock_time_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(\d{1,2}):(\d{2})(?::(\d{2}))?(?:\s*([AaPp])\.?[Mm]\.?)?").unwrap()
+    })
+}
+
+// regex matching the page of a kindle clipping
+fn kindle_page_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\bpage\s+(\d+)").unwrap())
+}
+
+// regex matching the location range of a kindle clipping
+fn kindle_location_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\blocation\s+(\d+)(?:\s*-\s*(\d+))?").unwrap())
+}
+
+// function to read a zone written after an iso time as minutes from utc, e.g. Z, +01:00 or -0530
+fn zone_offset(zone: &str) -> Option<i128> {
+    if zone == "Z" {
+        return Some(0);
+    }
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let digits: String = zone.chars().filter(char::is_ascii_digit).collect();
+    let hours: i128 = digits.get(..2)?.parse().ok()?;
+    let minutes: i128 = digits.get(2..)?.parse().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+// function to read a date written by another reader as ms since 1970
+// times without a zone are local times, taken at the given offset from utc (e.g. 60 for utc+1)
+// e.g. 2024-01-31 10:20:30, 2024-01-31T10:20:30.123Z, Monday, 3 April 2023 10:01:23 or Friday, April 7, 2023 11:02:03 PM
+fn parse_date(text: &str, utc_offset_minutes: i32) -> Option<u128> {
+    let iso = iso_date_regex();
+    let time = clock_time_regex();
+    let number = |value: Option<regex::Match>| -> i128 {
+        value
+            .and_then(|value| value.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+
+    let (year, month, day, mut hour, minute, second, pm) = if let Some(caps) = iso.captures(text) {
+        let (hour, minute, second) = (
+            number(caps.get(4)),
+            number(caps.get(5)),
+            number(caps.get(6)),
+        );
+        (
+            number(caps.get(1)),
+            number(caps.get(2)),
+            number(caps.get(3)),
+            hour,
+            minute,
+            second,
+            None,
+        )
+    } else {
+        let lower = text.to_lowercase();
+        let month = lower
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|word| word.len() >= 3)
+            .find_map(|word| MONTHS.iter().position(|month| month.starts_with(word)))?;
+
+        let (date, hour, minute, second, pm) = match time.captures(&lower) {
+            Some(caps) => (
+                lower.replace(&caps[0], " "),
+                number(caps.get(1)),
+                number(caps.get(2)),
+                number(caps.get(3)),
+                caps.get(4).map(|half| half.as_str() == "p"),
+            ),
+            None => (lower.clone(), 0, 0, 0, None),
+        };
+        let numbers: Vec<&str> = date
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .collect();
+        let year = numbers.iter().find(|part| part.len() == 4)?.parse().ok()?;
+        let day = numbers.iter().find(|part| part.len() <= 2)?.parse().ok()?;
+        (year, month as i128 + 1, day, hour, minute, second, pm)
+    };
+
+    // iso times may end with their zone
+    let zone = iso
+        .captures(text)
+        .and_then(|caps| caps.get(7))
+        .and_then(|zone| zone_offset(zone.as_str()));
+
+    // 12 AM is midnight and 12 PM is noon
+    match pm {
+        Some(true) if hour < 12 => hour += 12,
+        Some(false) if hour == 12 => hour = 0,
+        _ => {}
+    }
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let offset = zone.unwrap_or(utc_offset_minutes as i128);
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset * 60;
+    u128::try_from(seconds * 1000).ok()
+}
+
+// function to write a time as the local date and time koreader uses, e.g. 2024-01-31 10:20:30
+// the local time is taken at the given offset from utc (e.g. 60 for utc+1)
+fn koreader_datetime(time_ms: u128, utc_offset_minutes: i32) -> String {
+    let seconds = (time_ms / 1000) as i128 + utc_offset_minutes as i128 * 60;
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        civil_date(seconds.div_euclid(86_400)),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// function to split the title line of a clipping into the title and the authors
+// e.g. "Dune (Herbert, Frank)" -> ("Dune", ["Frank Herbert"])
+fn split_title_line(line: &str) -> (String, Vec<String>) {
+    let line = line.trim();
+    if !line.ends_with(')') {
+        return (line.to_string(), Vec::new());
+    }
+
+    // the authors are in the last parentheses, titles may have parentheses of their own
+    let mut depth = 0;
+    let mut open = None;
+    for (index, c) in line.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                depth -= 1;
+                if depth == 0 {
+                    open = Some(index);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(open) = open.filter(|&open| open > 0) else {
+        return (line.to_string(), Vec::new());
+    };
+
+    let authors = line[open + 1..line.len() - 1]
+        .split(';')
+        .map(|author| match author.split_once(',') {
+            Some((last, first)) if !first.contains(',') => {
+                format!("{} {}", first.trim(), last.trim())
+            }
+            _ => author.trim().to_string(),
+        })
+        .filter(|author| !author.is_empty())
+        .collect();
+    (line[..open].trim().to_string(), authors)
+}
+
+// KindleEntry struct: a highlight or note of kindle clippings
+#[derive(Clone)]
+struct KindleEntry {
+    group: usize, // index of the book in the parsed groups
+    is_note: bool,
+    location: Option<(u32, u32)>, // first and last kindle location
+    annotation: SourceAnnotation,
+}
+
+// implementations for KindleEntry struct
+impl KindleEntry {
+    // checks if the entry is the same book and overlaps the location range of another one
+    fn overlaps(&self, other: &KindleEntry) -> bool {
+        self.group == other.group
+            && matches!((self.location, other.location), (Some((start, end)), Some((other_start, other_end)))
+                if start <= other_end && other_start <= end)
+    }
+}
+
+// function to read kindle clippings, bookmarks are left out and notes are attached to their highlight
+fn parse_kindle(data: &str, utc_offset_minutes: i32) -> Vec<SourceGroup> {
+    let page_regex = kindle_page_regex();
+    let location_regex = kindle_location_regex();
+
+    let mut entries: Vec<KindleEntry> = Vec::new();
+    let mut groups: Vec<SourceGroup> = Vec::new();
+
+    for entry in data.split(KINDLE_SEPARATOR) {
+        let mut lines = entry
+            .lines()
+            .map(|line| line.trim_matches(|c: char| c == '\u{feff}' || c.is_whitespace()))
+            .skip_while(|line| line.is_empty());
+        let (Some(title_line), Some(info)) = (lines.next(), lines.next()) else {
+            continue;
+        };
+        let content = lines.collect::<Vec<&str>>().join("\n").trim().to_string();
+
+        let kind = info.to_lowercase();
+        if content.is_empty() || kind.contains("bookmark") {
+            continue;
+        }
+        let is_note = kind.contains("note");
+
+        let (title, authors) = split_title_line(title_line);
+        let group = match groups
+            .iter()
+            .position(|group| group.book.title.as_deref() == Some(title.as_str()))
+        {
+            Some(group) => group,
+            None => {
+                groups.push(SourceGroup {
+                    book: SourceBook {
+                        title: Some(title),
+                        authors,
+                        ..Default::default()
+                    },
+                    annotations: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+
+        let location = location_regex.captures(info).and_then(|caps| {
+            let start: u32 = caps[1].parse().ok()?;
+            let end = caps
+                .get(2)
+                .and_then(|end| end.as_str().parse().ok())
+                .unwrap_or(start);
+            Some((start, end))
+        });
+        let (text, note) = if is_note {
+            (String::new(), Some(content))
+        } else {
+            (content, None)
+        };
+        entries.push(KindleEntry {
+            group,
+            is_note,
+            location,
+            annotation: SourceAnnotation {
+                text,
+                note,
+                created_at: info
+                    .rsplit('|')
+                    .next()
+                    .filter(|added| added.to_lowercase().contains("added"))
+                    .and_then(|added| parse_date(added, utc_offset_minutes)),
+                page: page_regex
+                    .captures(info)
+                    .and_then(|caps| caps[1].parse().ok()),
+                ..Default::default()
+            },
+        });
+    }
+
+    // kindle keeps the old version when a highlight is extended, only the longest one is imported
+    let superseded = |entry: &KindleEntry| {
+        entries.iter().any(|other| {
+            !other.is_note
+                && entry.overlaps(other)
+                && other.annotation.text.len() > entry.annotation.text.len()
+                && other
+                    .annotation
+                    .text
+                    .contains(entry.annotation.text.as_str())
+        })
+    };
+    let mut highlights: Vec<KindleEntry> = entries
+        .iter()
+        .filter(|entry| !entry.is_note && !superseded(entry))
+        .cloned()
+        .collect();
+
+    // a note is placed at the last location of the highlight it belongs to
+    for note in entries.iter().filter(|entry| entry.is_note) {
+        let highlight = highlights
+            .iter_mut()
+            .rev()
+            .find(|highlight| highlight.annotation.note.is_none() && highlight.overlaps(note));
+        match highlight {
+            Some(highlight) => highlight.annotation.note = note.annotation.note.clone(),
+            None => highlights.push(note.clone()), // skipped on import, it has no text to find
+        }
+    }
+
+    for highlight in highlights {
+        groups[highlight.group]
+            .annotations
+            .push(highlight.annotation);
+    }
+    groups
+}
+
+// function to get the spine index from the DocFragment step of a koreader xpointer
+// e.g. /body/DocFragment[12]/body/p[3]/text().0 -> 11
+fn fragment_index(xpointer: &str) -> Option<usize> {
+    let rest = xpointer.split("DocFragment[").nth(1)?;
+    let index: usize = rest.split(']').next()?.parse().ok()?;
+    index.checked_sub(1)
+}
+
+// function to read the chapter or page hint from a koreader position (xpointer for epubs, table for pdfs)
+fn koreader_hint(annotation: &mut SourceAnnotation, position: Option<&LuaValue>) {
+    match position {
+        Some(LuaValue::String(xpointer)) => annotation.spine_index = fragment_index(xpointer),
+        Some(LuaValue::Number(page)) => annotation.page = Some(*page as u32),
+        Some(position @ LuaValue::Table(_)) => {
+            annotation.page = position
+                .field("page")
+                .and_then(LuaValue::as_number)
+                .map(|page| page as u32)
+        }
+        _ => {}
+    }
+}
+
+// function to read a koreader metadata file, both the annotations list of current versions
+// and the highlight and bookmarks lists of versions before 2024 are read
+fn parse_koreader(
+    table: &LuaValue,
+    file_stem: Option<String>,
+    utc_offset_minutes: i32,
+) -> SourceGroup {
+    let text = |value: &LuaValue, key: &str| {
+        value
+            .field(key)
+            .and_then(LuaValue::as_str)
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
+    };
+    let lines = |value: Option<String>| -> Vec<String> {
+        value
+            .map(|value| {
+                value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let props = table.field("doc_props").cloned().unwrap_or(LuaValue::Nil);
+    let book = SourceBook {
+        title: text(&props, "title"),
+        authors: lines(text(&props, "authors")),
+        identifiers: lines(text(&props, "identifiers")),
+        file_stem: text(table, "doc_path")
+            .and_then(|path| {
+                Path::new(&path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            })
+            .or(file_stem),
+    };
+
+    let mut annotations = Vec::new();
+    if let Some(list) = table.field("annotations") {
+        // bookmarks are annotations without a range
+        for entry in list
+            .values()
+            .into_iter()
+            .filter(|entry| entry.field("pos0").is_some())
+        {
+            let mut annotation = SourceAnnotation {
+                text: text(entry, "text").unwrap_or_default(),
+                note: text(entry, "note"),
+                color: text(entry, "color"),
+                created_at: text(entry, "datetime")
+                    .and_then(|date| parse_date(&date, utc_offset_minutes)),
+                ..Default::default()
+            };
+            koreader_hint(&mut annotation, entry.field("page").or(entry.field("pos0")));
+            annotations.push(annotation);
+        }
+    } else if let Some(pages) = table.field("highlight") {
+        let bookmarks = table
+            .field("bookmarks")
+            .map(LuaValue::values)
+            .unwrap_or_default();
+        for entry in pages.values().into_iter().flat_map(LuaValue::values) {
+            let datetime = text(entry, "datetime");
+            // notes were kept in the bookmark of the highlight, whose text defaults to the page and date
+            let note = bookmarks
+                .iter()
+                .find(|bookmark| {
+                    bookmark.field("highlighted").is_some_and(LuaValue::as_bool)
+                        && text(bookmark, "datetime") == datetime
+                })
+                .and_then(|bookmark| text(bookmark, "text"))
+                .filter(|note| {
+                    datetime
+                        .as_ref()
+                        .is_none_or(|datetime| !note.ends_with(datetime.as_str()))
+                });
+
+            let mut annotation = SourceAnnotation {
+                text: text(entry, "text").unwrap_or_default(),
+                note,
+                color: text(entry, "color"),
+                created_at: datetime.and_then(|date| parse_date(&date, utc_offset_minutes)),
+                ..Default::default()
+            };
+            koreader_hint(&mut annotation, entry.field("pos0"));
+            annotations.push(annotation);
+        }
+    }
+
+    SourceGroup { book, annotations }
+}
+
+// function to read highlights exported by the calibre viewer, the export has no book details
+fn parse_calibre(
+    value: &serde_json::Value,
+    file_stem: Option<String>,
+    utc_offset_minutes: i32,
+) -> SourceGroup {
+    let list = value
+        .get("highlights")
+        .or_else(|| value.get("annotations"))
+        .unwrap_or(value)
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    let annotations = list
+        .iter()
+        .filter(|entry| {
+            entry.get("type").and_then(|kind| kind.as_str()) == Some("highlight")
+                && !entry
+                    .get("removed")
+                    .and_then(|removed| removed.as_bool())
+                    .unwrap_or(false)
+        })
+        .map(|entry| {
+            let text = |key: &str| {
+                entry
+                    .get(key)
+                    .and_then(|value| value.as_str())
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty())
+                    .map(str::to_string)
+            };
+            let style = entry.get("style");
+            SourceAnnotation {
+                text: text("highlighted_text").unwrap_or_default(),
+                note: text("notes"),
+                color: style
+                    .filter(|style| {
+                        style.get("kind").and_then(|kind| kind.as_str()) == Some("color")
+                    })
+                    .and_then(|style| style.get("which"))
+                    .and_then(|which| which.as_str())
+                    .map(str::to_string),
+                created_at: text("timestamp")
+                    .and_then(|date| parse_date(&date, utc_offset_minutes)),
+                spine_index: entry
+                    .get("spine_index")
+                    .and_then(|index| index.as_u64())
+                    .map(|index| index as usize),
+                page: None,
+            }
+        })
+        .collect();
+
+    SourceGroup {
+        book: SourceBook {
+            file_stem,
+            ..Default::default()
+        },
+        annotations,
+    }
+}
+
+// function to get the name of a file or directory without extension
+fn file_stem(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+}
+
+// function to find the metadata file inside a koreader .sdr directory
+fn koreader_metadata_file(dir: &Path) -> Result<PathBuf, ReaderError> {
+    std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("metadata.") && name.ends_with(".lua")
+        })
+        .ok_or_else(|| {
+            ReaderError::NotFound(format!("{} has no koreader metadata file", dir.display()))
+        })
+}
+
+// function to read the annotations of a file exported by another reader
+fn read_source(
+    path: &str,
+    source: AnnotationSource,
+    utc_offset_minutes: i32,
+) -> Result<Vec<SourceGroup>, ReaderError> {
+    let path = Path::new(path);
+    match source {
+        AnnotationSource::Kindle => {
+            let data = decode_text(&std::fs::read(path)?, false);
+            Ok(parse_kindle(&data, utc_offset_minutes))
+        }
+        AnnotationSource::Koreader => {
+            let (file, dir) = if path.is_dir() {
+                (koreader_metadata_file(path)?, path)
+            } else {
+                (path.to_path_buf(), path.parent().unwrap_or(path))
+            };
+            // the sidecar directory is named after the book file, e.g. Dune.sdr for Dune.epub
+            let book_stem = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .and_then(|name| name.strip_suffix(".sdr").map(str::to_string));
+
+            let table = parse_lua(&std::fs::read_to_string(&file)?)?;
+            Ok(vec![parse_koreader(&table, book_stem, utc_offset_minutes)])
+        }
+        AnnotationSource::Calibre => {
+            let value: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+            Ok(vec![parse_calibre(
+                &value,
+                file_stem(path),
+                utc_offset_minutes,
+            )])
+        }
+    }
+}
+
+// function to normalize a title or name for comparing, only lowercase letters and digits are kept
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// function to normalize a book identifier, schemes like urn:isbn: are dropped
+fn normalize_identifier(identifier: &str) -> String {
+    normalize(identifier.rsplit(':').next().unwrap_or(identifier))
+}
+
+// function to find the library book annotations belong to, by identifier, title (and authors) or file name
+fn find_book<'a>(library: &'a Library, source: &SourceBook) -> Option<&'a Book> {
+    let identifiers: Vec<String> = source
+        .identifiers
+        .iter()
+        .map(|identifier| normalize_identifier(identifier))
+        .filter(|identifier| !identifier.is_empty())
+        .collect();
+    let by_identifier = library.books.iter().find(|book| {
+        [Some(&book.id), book.metadata.identifier.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|identifier| identifiers.contains(&normalize_identifier(identifier)))
+    });
+    if by_identifier.is_some() {
+        return by_identifier;
+    }
+
+    // titles may be written with or without their subtitle
+    if let Some(title) = source.title.as_deref() {
+        let main_title = |title: &str| normalize(title.split(':').next().unwrap_or(title));
+        let (full, main) = (normalize(title), main_title(title));
+        let candidates: Vec<&Book> = library
+            .books
+            .iter()
+            .filter(|book| {
+                let book_title = normalize(&book.metadata.title);
+                !book_title.is_empty()
+                    && (book_title == full || main_title(&book.metadata.title) == main)
+            })
+            .collect();
+
+        let authors: Vec<String> = source
+            .authors
+            .iter()
+            .map(|author| normalize(author))
+            .collect();
+        let by_author = candidates.iter().find(|book| {
+            book.metadata
+                .authors
+                .iter()
+                .any(|author| authors.contains(&normalize(author)))
+        });
+        if let Some(book) = by_author.or(candidates.first()) {
+            return Some(book);
+        }
+    }
+
+    let stem = normalize(source.file_stem.as_deref()?);
+    library.books.iter().find(|book| {
+        !stem.is_empty()
+            && file_stem(Path::new(&book.path)).is_some_and(|name| normalize(&name) == stem)
+    })
+}
+
+// function to add the annotations of another reader to the highlights of a library book
+fn import_annotations(
+    book: &Book,
+    annotations: Vec<SourceAnnotation>,
+) -> Result<ImportedBook, ReaderError> {
+    let key = highlights_key(book);
+    let mut highlights = load_highlights(key)?;
+    let text = BookText::load(book)?;
+    let mut imported = ImportedBook {
+        book_id: book.id.clone(),
+        title: book.metadata.title.clone(),
+        imported: 0,
+        duplicates: 0,
+        skipped: 0,
+    };
+
+    for annotation in annotations {
+        let range =
+            text_pattern(&annotation.text).and_then(|pattern| text.locate(&pattern, &annotation));
+        let Some(range) = range else {
+            imported.skipped += 1;
+            continue;
+        };
+        if highlights.iter().any(|highlight| highlight.range == range) {
+            imported.duplicates += 1;
+            continue;
+        }
+
+        let now = current_time_ms();
+        let highlight = Highlight {
+            id: new_highlight_id(highlights.iter()),
+            range,
+            text: annotation.text,
+            color: annotation
+                .color
+                .filter(|color| validate_color(color).is_ok())
+                .unwrap_or_else(|| DEFAULT_COLOR.to_string()),
+            note: annotation.note,
+            tags: Vec::new(),
+            created_at: annotation.created_at.unwrap_or(now),
+            updated_at: now,
+        };
+        if highlight.validate().is_err() {
+            imported.skipped += 1;
+            continue;
+        }
+        highlights.push(highlight);
+        imported.imported += 1;
+    }
+
+    if imported.imported > 0 {
+        save_highlights(key, &highlights)?;
+    }
+    Ok(imported)
+}
+
+// CfiLocation struct: a location inside a spine document, e.g. /4/2/1:10
+struct CfiLocation {
+    steps: Vec<usize>,
+    offset: Option<usize>,
+}
+
+// function to read a cfi location without assertions
+fn parse_location(path: &str) -> Option<CfiLocation> {
+    let (steps, offset) = match path.split_once(':') {
+        Some((steps, offset)) => {
+            let digits: String = offset.chars().take_while(char::is_ascii_digit).collect();
+            (steps, Some(digits.parse().ok()?))
+        }
+        None => (path, None),
+    };
+    let steps = steps
+        .split('/')
+        .filter(|step| !step.is_empty())
+        .map(|step| step.parse().ok())
+        .collect::<Option<Vec<usize>>>()?;
+    (!steps.is_empty()).then_some(CfiLocation { steps, offset })
+}
+
+// function to split an epub cfi range into the start and end locations inside its spine document
+// e.g. epubcfi(/6/4[c1]!/4/2[p1],/1:0,/3:5) -> (/4/2/1:0, /4/2/3:5)
+fn cfi_locations(cfi: &str) -> Option<(CfiLocation, CfiLocation)> {
+    let inner = cfi.strip_prefix("epubcfi(")?.strip_suffix(')')?;
+
+    // assertions like [id] may contain escaped characters, they are not needed here
+    let mut path = String::new();
+    let (mut in_assertion, mut escaped) = (false, false);
+    for c in inner.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '^' => escaped = true,
+            '[' => in_assertion = true,
+            ']' => in_assertion = false,
+            _ if !in_assertion => path.push(c),
+            _ => {}
+        }
+    }
+
+    let (_, document_path) = path.split_once('!')?;
+    let parts: Vec<&str> = document_path.split(',').collect();
+    match parts.as_slice() {
+        [common, start, end] => Some((
+            parse_location(&format!("{}{}", common, start))?,
+            parse_location(&format!("{}{}", common, end))?,
+        )),
+        [location] => Some((parse_location(location)?, parse_location(location)?)),
+        _ => None,
+    }
+}
+
+// function to convert a cfi location to the xpointer koreader (crengine) uses for the same place
+// e.g. /4/2/1:10 in the 3rd spine document -> /body/DocFragment[3]/body/p/text().10
+fn xpointer(data: &str, fragment: usize, location: &CfiLocation) -> Option<String> {
+    let mut tokens = tokenize(data).into_iter().skip_while(
+        |token| !matches!(token, Token::Tag(tag) if tag.name == "html" && tag.is_open()),
+    );
+    tokens.next(); // the html element is the document fragment
+
+    let mut xpointer = format!("/body/DocFragment[{}]", fragment);
+    for (index, &step) in location.steps.iter().enumerate() {
+        let last = index + 1 == location.steps.len();
+        let mut depth = 0;
+        let mut elements = 0;
+        let mut names: Vec<(String, usize)> = Vec::new();
+        let mut texts = 0;
+        let mut in_text = false;
+
+        loop {
+            match tokens.next()? {
+                Token::Tag(tag) if depth == 0 => {
+                    if tag.is_close() {
+                        return None; // the element has fewer children than the cfi says
+                    }
+                    in_text = false;
+                    elements += 1;
+                    let count = match names.iter_mut().find(|(name, _)| *name == tag.name) {
+                        Some((_, count)) => {
+                            *count += 1;
+                            *count
+                        }
+                        None => {
+                            names.push((tag.name.clone(), 1));
+                            1
+                        }
+                    };
+
+                    if step == elements * 2 {
+                        xpointer.push('/');
+                        xpointer.push_str(&tag.name);
+                        if count > 1 {
+                            xpointer.push_str(&format!("[{}]", count));
+                        }
+                        if !tag.is_open() && !last {
+                            return None;
+                        }
+                        break;
+                    }
+                    if tag.is_open() {
+                        depth += 1;
+                    }
+                }
+                Token::Tag(tag) => {
+                    if tag.is_open() {
+                        depth += 1;
+                    } else if tag.is_close() {
+                        depth -= 1;
+                    }
+                }
+                Token::Text(raw) if depth == 0 => {
+                    // crengine drops text nodes made of whitespace only
+                    let text = decode_entities(raw);
+                    if !in_text && !text.trim().is_empty() {
+                        texts += 1;
+                        in_text = true;
+                    }
+                    if step == elements * 2 + 1 && in_text {
+                        // cfi offsets count utf-16 units, crengine offsets count characters
+                        let units = location.offset.unwrap_or(0);
+                        let (mut counted, mut chars) = (0, 0);
+                        for c in text.chars() {
+                            if counted >= units {
+                                break;
+                            }
+                            counted += c.len_utf16();
+                            chars += 1;
+                        }
+                        let node = if texts > 1 {
+                            format!("text()[{}]", texts)
+                        } else {
+                            "text()".to_string()
+                        };
+                        return Some(format!("{}/{}.{}", xpointer, node, chars));
+                    }
+                }
+                Token::Text(_) => {}
+            }
+        }
+    }
+    Some(xpointer)
+}
+
+// function to get the koreader color closest to a highlight color
+fn koreader_color(color: &str) -> &'static str {
+    match color {
+        "magenta" | "pink" => "purple",
+        _ => KOREADER_COLORS
+            .iter()
+            .find(|name| **name == color)
+            .copied()
+            .unwrap_or(DEFAULT_COLOR),
+    }
+}
+
+// function to build a koreader annotation from a highlight and its koreader position
+fn koreader_annotation(
+    highlight: &Highlight,
+    chapter: Option<String>,
+    page: LuaValue,
+    (pos0, pos1): (LuaValue, LuaValue),
+    utc_offset_minutes: i32,
+) -> LuaValue {
+    LuaValue::table(vec![
+        (
+            "chapter",
+            chapter.map(LuaValue::String).unwrap_or(LuaValue::Nil),
+        ),
+        (
+            "color",
+            LuaValue::String(koreader_color(&highlight.color).to_string()),
+        ),
+        (
+            "datetime",
+            LuaValue::String(koreader_datetime(highlight.created_at, utc_offset_minutes)),
+        ),
+        (
+            "datetime_updated",
+            LuaValue::String(koreader_datetime(highlight.updated_at, utc_offset_minutes)),
+        ),
+        ("drawer", LuaValue::String("lighten".to_string())),
+        (
+            "note",
+            highlight
+                .note
+                .clone()
+                .map(LuaValue::String)
+                .unwrap_or(LuaValue::Nil),
+        ),
+        ("page", page),
+        ("pos0", pos0),
+        ("pos1", pos1),
+        ("text", LuaValue::String(highlight.text.clone())),
+    ])
+}
+
+// function to get the reading order position of a koreader annotation, used to sort merged lists
+fn koreader_order(annotation: &LuaValue) -> usize {
+    match annotation.field("page") {
+        Some(LuaValue::String(xpointer)) => fragment_index(xpointer).unwrap_or(usize::MAX),
+        Some(LuaValue::Number(page)) => *page as usize,
+        _ => usize::MAX,
+    }
+}
+
+// function to check if two koreader annotations highlight the same text in the same chapter or page
+fn same_text(a: &LuaValue, b: &LuaValue) -> bool {
+    let words = |annotation: &LuaValue| {
+        annotation
+            .field("text")
+            .and_then(LuaValue::as_str)
+            .map(|text| {
+                text.split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<String>>()
+            })
+    };
+    words(a).is_some() && words(a) == words(b) && koreader_order(a) == koreader_order(b)
+}
+
+// function to convert the highlights of a library book to koreader annotations
+// returns the annotations and the number of highlights which could not be converted
+fn koreader_annotations(
+    book: &Book,
+    utc_offset_minutes: i32,
+) -> Result<(Vec<LuaValue>, usize), ReaderError> {
+    let exported = export_book(book)?;
+    let mut annotations = Vec::new();
+    let mut skipped = 0;
+
+    match book.format {
+        BookFormat::Pdf => {
+            let document = load_document(&book.path)?;
+            let pages = document.get_pages();
+            for item in exported.highlights {
+                let HighlightRange::Pdf { page, rects } = &item.highlight.range else {
+                    continue;
+                };
+                let (Some(page_id), Some(first), Some(last)) =
+                    (pages.get(page), rects.first(), rects.last())
+                else {
+                    skipped += 1;
+                    continue;
+                };
+
+                // koreader positions are in points from the top left corner of the page
+                let (width, height) = page_size(&document, *page_id);
+                let point = |x: f64, y: f64| {
+                    LuaValue::table(vec![
+                        ("page", LuaValue::Number(*page as f64)),
+                        ("rotation", LuaValue::Number(0.0)),
+                        ("x", LuaValue::Number(x * width)),
+                        ("y", LuaValue::Number(y * height)),
+                        ("zoom", LuaValue::Number(1.0)),
+                    ])
+                };
+                let positions = (
+                    point(first.x, first.y),
+                    point(last.x + last.width, last.y + last.height),
+                );
+                let page = LuaValue::Number(*page as f64);
+                annotations.push(koreader_annotation(
+                    &item.highlight,
+                    item.context,
+                    page,
+                    positions,
+                    utc_offset_minutes,
+                ));
+            }
+        }
+        BookFormat::Epub => {
+            let (mut archive, package) = open_book(&book.path)?;
+            for item in exported.highlights {
+                let HighlightRange::Epub { cfi_range } = &item.highlight.range else {
+                    continue;
+                };
+                let positions = cfi_spine_index(cfi_range)
+                    .zip(cfi_locations(cfi_range))
+                    .and_then(|(index, (start, end))| {
+                        let spine_item = package.spine.get(index)?;
+                        let href = &package.item_by_id(&spine_item.idref)?.href;
+                        let data = read_entry_string(&mut archive, href).ok()?;
+                        Some((
+                            xpointer(&data, index + 1, &start)?,
+                            xpointer(&data, index + 1, &end)?,
+                        ))
+                    });
+                let Some((pos0, pos1)) = positions else {
+                    skipped += 1;
+                    continue;
+                };
+
+                let page = LuaValue::String(pos0.clone());
+                let positions = (LuaValue::String(pos0), LuaValue::String(pos1));
+                annotations.push(koreader_annotation(
+                    &item.highlight,
+                    item.context,
+                    page,
+                    positions,
+                    utc_offset_minutes,
+                ));
+            }
+        }
+        _ => {
+            return Err(ReaderError::ValidationError(
+                "koreader sidecars can only be written for epub and pdf books".to_string(),
+            ))
+        }
+    }
+
+    Ok((annotations, skipped))
+}
+
+// annotations_import command: imports highlights and notes from a kindle, koreader or calibre file when invoked
+// annotations are added to the given book, or to the library books they are matched to by identifier or title
+// times without a zone are in the local time of the ui, given as offset from utc (e.g. 60 for utc+1)
+#[tauri::command]
+pub fn annotations_import(
+    path: &str,
+    source: Option<AnnotationSource>,
+    book_id: Option<String>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<ImportReport, ReaderError> {
+    let source = source
+        .or_else(|| AnnotationSource::from_path(path))
+        .ok_or_else(|| {
+            ReaderError::ValidationError(format!(
+                "{} is not a kindle, koreader or calibre annotations file",
+                path
+            ))
+        })?;
+    let groups = read_source(path, source, utc_offset_minutes.unwrap_or(0))?;
+
+    let library = Library::load()?;
+    let target = match &book_id {
+        Some(id) => Some(
+            library
+                .book(id)
+                .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", id)))?,
+        ),
+        None => None,
+    };
+
+    let mut report = ImportReport::default();
+    for group in groups {
+        let Some(book) = target.or_else(|| find_book(&library, &group.book)) else {
+            let name = group
+                .book
+                .title
+                .or(group.book.file_stem)
+                .unwrap_or_else(|| "Unknown book".to_string());
+            report.unmatched.push(name);
+            continue;
+        };
+
+        let imported = match import_annotations(book, group.annotations) {
+            Ok(imported) => imported,
+            Err(e) => {
+                report.failed.push(ImportFailure {
+                    book_id: book.id.clone(),
+                    title: book.metadata.title.clone(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        match report
+            .books
+            .iter_mut()
+            .find(|existing| existing.book_id == imported.book_id)
+        {
+            Some(existing) => {
+                existing.imported += imported.imported;
+                existing.duplicates += imported.duplicates;
+                existing.skipped += imported.skipped;
+            }
+            None => report.books.push(imported),
+        }
+    }
+
+    Ok(report)
+}
+
+// koreader_annotations_export command: writes the highlights of a book to the koreader sidecar next to it when invoked
+// an existing sidecar keeps its settings and annotations (a copy is kept as .old, like koreader does)
+// koreader writes local times, taken in the local time of the ui, given as offset from utc (e.g. 60 for utc+1)
+#[tauri::command]
+pub fn koreader_annotations_export(
+    book_id: &str,
+    utc_offset_minutes: Option<i32>,
+) -> Result<KoreaderExport, ReaderError> {
+    let library = Library::load()?;
+    let book = library
+        .book(book_id)
+        .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", book_id)))?;
+    let (annotations, skipped) = koreader_annotations(book, utc_offset_minutes.unwrap_or(0))?;
+
+    // koreader keeps book settings in <book>.sdr/metadata.<extension>.lua
+    let book_path = Path::new(&book.path);
+    let (Some(dir), Some(stem), Some(extension)) = (
+        book_path.parent(),
+        file_stem(book_path),
+        book_path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase()),
+    ) else {
+        return Err(ReaderError::InvalidData(format!(
+            "{} is not a book file path",
+            book.path
+        )));
+    };
+    let sidecar_dir = dir.join(format!("{}.sdr", stem));
+    let sidecar = sidecar_dir.join(format!("metadata.{}.lua", extension));
+
+    let mut table = match std::fs::read_to_string(&sidecar) {
+        Ok(data) => parse_lua(&data)?, // an unreadable sidecar is not replaced
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => LuaValue::Table(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    // highlights exported before, or imported from this sidecar, are not added again
+    let mut merged: Vec<LuaValue> = table
+        .field("annotations")
+        .map(|list| list.values().into_iter().cloned().collect())
+        .unwrap_or_default();
+    let mut exported = 0;
+    for annotation in annotations {
+        let exists = merged.iter().any(|existing| {
+            existing.field("pos0") == annotation.field("pos0") || same_text(existing, &annotation)
+        });
+        if !exists {
+            merged.push(annotation);
+            exported += 1;
+        }
+    }
+    merged.sort_by_key(koreader_order);
+
+    table.set("annotations", LuaValue::array(merged));
+    table.set("annotations_externally_modified", LuaValue::Bool(true));
+    if table.field("doc_props").is_none() {
+        let metadata = &book.metadata;
+        table.set(
+            "doc_props",
+            LuaValue::table(vec![
+                ("title", LuaValue::String(metadata.title.clone())),
+                ("authors", LuaValue::String(metadata.authors.join("\n"))),
+                (
+                    "language",
+                    metadata
+                        .language
+                        .clone()
+                        .map(LuaValue::String)
+                        .unwrap_or(LuaValue::Nil),
+                ),
+            ]),
+        );
+    }
+    if table.field("doc_path").is_none() {
+        table.set("doc_path", LuaValue::String(book.path.clone()));
+    }
+
+    std::fs::create_dir_all(&sidecar_dir)?;
+    if sidecar.exists() {
+        std::fs::copy(&sidecar, sidecar.with_extension("lua.old"))?;
+    }
+    std::fs::write(&sidecar, write_lua(&table))?;
+
+    Ok(KoreaderExport {
+        path: sidecar.to_string_lossy().to_string(),
+        exported,
+        skipped,
+    })
+}
@@ -0,0 +1,282 @@
+/*
+    reader module:
+    bookmarks.rs stores named bookmarks of library books, separate from highlights
+*/
+
+// importing crates and modules
+use crate::functions::{current_time_ms, delete_file};
+use crate::reader::cache::open_book;
+use crate::reader::epub::read_entry_string;
+use crate::reader::library::{is_book_id, Book, BookFormat, Library};
+use crate::reader::pdf_text::extract_pages;
+use crate::reader::position::{cfi_spine_index, validate_locator, Locator};
+use crate::reader::text::DocumentText;
+use crate::reader::toc::{read_toc, spine_chapters};
+use crate::reader::{read_json, save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+
+// limits used while validating bookmarks
+const MAX_LABEL_LENGTH: usize = 200;
+
+// number of characters of text captured at the bookmarked location
+const SNIPPET_LENGTH: usize = 200;
+
+// Bookmark struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bookmark {
+    pub id: String,
+    pub locator: Locator,
+    pub label: String,
+    pub snippet: Option<String>, // text at the location, captured when the bookmark is added
+    pub created_at: u128,
+}
+
+// function to generate path of the bookmarks file of a book
+// ids come from the webview, so they are checked before they are used in a path
+fn bookmarks_path(book_id: &str) -> Result<String, ReaderError> {
+    if !is_book_id(book_id) {
+        return Err(ReaderError::ValidationError(format!(
+            "{} is not a book id",
+            book_id
+        )));
+    }
+    Ok(format!("bookmarks/{}.json", book_id))
+}
+
+// function to load bookmarks of a book
+pub fn load_bookmarks(book_id: &str) -> Result<Vec<Bookmark>, ReaderError> {
+    read_json(&bookmarks_path(book_id)?)
+}
+
+// function to save bookmarks of a book
+fn save_bookmarks(book_id: &str, bookmarks: &[Bookmark]) -> Result<(), ReaderError> {
+    save_json(&bookmarks_path(book_id)?, bookmarks)
+}
+
+// function to move bookmarks of a book to its new id
+pub fn move_bookmarks(old_id: &str, new_id: &str) -> Result<(), ReaderError> {
+    let bookmarks = load_bookmarks(old_id)?;
+    if !bookmarks.is_empty() {
+        save_bookmarks(new_id, &bookmarks)?;
+        delete_file(&bookmarks_path(old_id)?)?;
+    }
+    Ok(())
+}
+
+// function to delete the bookmarks of a book removed from the library
+pub fn remove_bookmarks(book_id: &str) -> Result<(), ReaderError> {
+    match delete_file(&bookmarks_path(book_id)?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// function to validate and clean a label
+fn validate_label(label: &str) -> Result<String, ReaderError> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(ReaderError::ValidationError(
+            "bookmark label is empty".to_string(),
+        ));
+    }
+    if label.chars().count() > MAX_LABEL_LENGTH {
+        return Err(ReaderError::ValidationError(format!(
+            "bookmark label is longer than {} characters",
+            MAX_LABEL_LENGTH
+        )));
+    }
+    Ok(label.to_string())
+}
+
+// function to shorten text to a snippet, cutting at a space
+fn shorten(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= SNIPPET_LENGTH {
+        return Some(text);
+    }
+
+    let end = text
+        .char_indices()
+        .nth(SNIPPET_LENGTH)
+        .map(|(index, _)| index)
+        .unwrap_or(text.len());
+    let cut = text[..end].rfind(' ').unwrap_or(end);
+    Some(format!("{}…", &text[..cut]))
+}
+
+// function to split the content part of a cfi into a path and a character offset
+// e.g. epubcfi(/6/4[c1]!/4/2[p1]/1:10) -> (/4/2/1, 10), ranges use their start
+fn cfi_content_path(cfi: &str) -> Option<(String, usize)> {
+    let inner = cfi.strip_prefix("epubcfi(")?.strip_suffix(')')?;
+    let (_, content) = inner.split_once('!')?;
+
+    // a range is written as parent,start,end
+    let mut parts = content.splitn(3, ',');
+    let location = format!("{}{}", parts.next()?, parts.next().unwrap_or_default());
+
+    // remove assertions in brackets, ^ escapes the next character
+    let mut path = String::new();
+    let mut depth = 0;
+    let mut chars = location.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '^' => {
+                chars.next();
+            }
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ if depth == 0 => path.push(c),
+            _ => {}
+        }
+    }
+
+    match path.split_once(':') {
+        Some((path, offset)) => {
+            let offset: String = offset.chars().take_while(|c| c.is_ascii_digit()).collect();
+            Some((path.to_string(), offset.parse().unwrap_or(0)))
+        }
+        None => Some((path, 0)),
+    }
+}
+
+// function to capture the text at an epub location and the title of its chapter
+fn e_pub_location_text(
+    path: &str,
+    cfi: &str,
+) -> Result<(Option<String>, Option<String>), ReaderError> {
+    let index = cfi_spine_index(cfi)
+        .ok_or_else(|| ReaderError::InvalidData(format!("{} has no spine step", cfi)))?;
+
+    let (mut archive, package) = open_book(path)?;
+    let chapter = read_toc(&mut archive, &package)
+        .ok()
+        .and_then(|toc| spine_chapters(&package, &toc).get(index).cloned().flatten());
+
+    let item = package
+        .spine
+        .get(index)
+        .and_then(|spine_item| package.item_by_id(&spine_item.idref))
+        .ok_or_else(|| ReaderError::NotFound(format!("spine item {} in {}", index, path)))?;
+    let document = DocumentText::new(&read_entry_string(&mut archive, &item.href)?);
+
+    let text = cfi_content_path(cfi)
+        .and_then(|(content_path, offset)| document.text_index(&content_path, offset))
+        .and_then(|start| shorten(&document.text[start..]));
+    Ok((text, chapter))
+}
+
+// function to capture the text and a default label for a location in a book
+// capturing is best effort, a bookmark is still added if the file can't be read
+fn capture(book: &Book, locator: &Locator) -> (Option<String>, String) {
+    match locator {
+        Locator::Epub { cfi, .. } => match e_pub_location_text(&book.path, cfi) {
+            Ok((text, chapter)) => (text, chapter.unwrap_or_else(|| "Bookmark".to_string())),
+            Err(_) => (None, "Bookmark".to_string()),
+        },
+        Locator::Pdf { page, .. } => {
+            let text = extract_pages(&book.path, Some(*page), Some(*page))
+                .ok()
+                .and_then(|pages| pages.into_iter().next())
+                .and_then(|page| shorten(&page.text));
+            (text, format!("Page {}", page))
+        }
+        Locator::Comic { page, .. } => (None, format!("Page {}", page)),
+    }
+}
+
+// function to generate an id not used by the given bookmarks
+fn new_bookmark_id(existing: &[Bookmark]) -> String {
+    let mut id = current_time_ms();
+    while existing
+        .iter()
+        .any(|bookmark| bookmark.id == id.to_string())
+    {
+        id += 1;
+    }
+    id.to_string()
+}
+
+// bookmarks_list command: returns all bookmarks of a book when invoked
+#[tauri::command]
+pub fn bookmarks_list(book_id: &str) -> Result<Vec<Bookmark>, ReaderError> {
+    load_bookmarks(book_id)
+}
+
+// bookmark_add command: adds a bookmark at a location of a library book and returns it when invoked
+// label and snippet are generated from the book if the ui does not send them
+#[tauri::command]
+pub fn bookmark_add(
+    book_id: &str,
+    locator: Locator,
+    label: Option<String>,
+    snippet: Option<String>,
+) -> Result<Bookmark, ReaderError> {
+    let library = Library::load()?;
+    let book = library
+        .book(book_id)
+        .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", book_id)))?;
+
+    // documents are read as epubs, so they have epub locations
+    let matches_format = match locator {
+        Locator::Epub { .. } => !matches!(book.format, BookFormat::Pdf | BookFormat::Comic),
+        Locator::Pdf { .. } => book.format == BookFormat::Pdf,
+        Locator::Comic { .. } => book.format == BookFormat::Comic,
+    };
+    if !matches_format {
+        return Err(ReaderError::ValidationError(
+            "location does not match the book format".to_string(),
+        ));
+    }
+    validate_locator(&locator, None)?;
+
+    let (captured, default_label) = capture(book, &locator);
+    let label = match label.filter(|label| !label.trim().is_empty()) {
+        Some(label) => validate_label(&label)?,
+        None => default_label,
+    };
+
+    let mut bookmarks = load_bookmarks(book_id)?;
+    let bookmark = Bookmark {
+        id: new_bookmark_id(&bookmarks),
+        locator,
+        label,
+        snippet: snippet.and_then(|snippet| shorten(&snippet)).or(captured),
+        created_at: current_time_ms(),
+    };
+
+    bookmarks.push(bookmark.clone());
+    save_bookmarks(book_id, &bookmarks)?;
+    Ok(bookmark)
+}
+
+// bookmark_rename command: changes the label of a bookmark when invoked
+#[tauri::command]
+pub fn bookmark_rename(book_id: &str, id: &str, label: &str) -> Result<Bookmark, ReaderError> {
+    let label = validate_label(label)?;
+    let mut bookmarks = load_bookmarks(book_id)?;
+    let bookmark = bookmarks
+        .iter_mut()
+        .find(|bookmark| bookmark.id == id)
+        .ok_or_else(|| ReaderError::NotFound(format!("bookmark {} not found", id)))?;
+
+    bookmark.label = label;
+    let renamed = bookmark.clone();
+    save_bookmarks(book_id, &bookmarks)?;
+    Ok(renamed)
+}
+
+// bookmark_delete command: deletes a bookmark of a book when invoked
+#[tauri::command]
+pub fn bookmark_delete(book_id: &str, id: &str) -> Result<(), ReaderError> {
+    let mut bookmarks = load_bookmarks(book_id)?;
+    let count = bookmarks.len();
+
+    bookmarks.retain(|bookmark| bookmark.id != id);
+    if bookmarks.len() == count {
+        return Err(ReaderError::NotFound(format!("bookmark {} not found", id)));
+    }
+    save_bookmarks(book_id, &bookmarks)
+}
@@ -0,0 +1,210 @@
+/*
+    reader module:
+    cache.rs keeps recently used epubs open, so the zip central directory and the package are parsed once per book
+    text documents are converted once per version of the file
+*/
+
+// importing crates and modules
+use crate::reader::document::document_epub;
+use crate::reader::epub::{read_package, Package};
+use crate::reader::library::BookFormat;
+use crate::reader::ReaderError;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+use zip::ZipArchive;
+
+// number of books kept open
+const CACHE_SIZE: usize = 8;
+
+// number of idle archives kept per book, more are opened while a book is read from several threads
+const ARCHIVES_PER_BOOK: usize = 2;
+
+// Fingerprint struct: identifies the version of a file, a changed file is opened again
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Fingerprint {
+    modified: SystemTime,
+    size: u64,
+}
+
+// implementations for Fingerprint struct
+impl Fingerprint {
+    // reads the fingerprint of the file at path
+    fn of(path: &str) -> Result<Self, ReaderError> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified()?,
+            size: metadata.len(),
+        })
+    }
+}
+
+// ArchiveSource enum: an epub file on disk, or a text document packed as an epub in memory
+pub enum ArchiveSource {
+    File(File),
+    Memory(Cursor<Vec<u8>>),
+}
+
+// implementations for ArchiveSource enum
+impl Read for ArchiveSource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for ArchiveSource {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+// CacheEntry struct: an opened book
+struct CacheEntry {
+    path: String,
+    fingerprint: Fingerprint,
+    archives: Vec<ZipArchive<ArchiveSource>>, // archives not in use at the moment
+    package: Option<Arc<Package>>,            // parsed when first needed
+}
+
+// function to lock the cache, entries are ordered from least to most recently used
+fn cache() -> MutexGuard<'static, Vec<CacheEntry>> {
+    static CACHE: OnceLock<Mutex<Vec<CacheEntry>>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// function to find the entry of a book, dropping it if the file changed since it was opened
+fn find_entry<'a>(
+    entries: &'a mut Vec<CacheEntry>,
+    path: &str,
+    fingerprint: Fingerprint,
+) -> Option<&'a mut CacheEntry> {
+    let index = entries.iter().position(|entry| entry.path == path)?;
+    let entry = entries.remove(index);
+    if entry.fingerprint != fingerprint {
+        return None;
+    }
+
+    // most recently used entries are kept at the end
+    entries.push(entry);
+    entries.last_mut()
+}
+
+// function to open the archive of a book, documents are converted to an epub first
+fn open_source(path: &str) -> Result<ZipArchive<ArchiveSource>, ReaderError> {
+    let source = match BookFormat::from_path(path) {
+        Some(format) if format.is_document() => {
+            ArchiveSource::Memory(Cursor::new(document_epub(path, format)?))
+        }
+        _ => ArchiveSource::File(File::open(path)?),
+    };
+    Ok(ZipArchive::new(source)?)
+}
+
+// CachedArchive struct: an archive borrowed from the cache, returned to it when dropped
+pub struct CachedArchive {
+    path: String,
+    fingerprint: Fingerprint,
+    archive: Option<ZipArchive<ArchiveSource>>, // always Some until dropped
+}
+
+// implementations for CachedArchive struct
+impl Deref for CachedArchive {
+    type Target = ZipArchive<ArchiveSource>;
+
+    fn deref(&self) -> &Self::Target {
+        self.archive
+            .as_ref()
+            .expect("archive is taken only on drop")
+    }
+}
+
+impl DerefMut for CachedArchive {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.archive
+            .as_mut()
+            .expect("archive is taken only on drop")
+    }
+}
+
+impl Drop for CachedArchive {
+    fn drop(&mut self) {
+        let Some(archive) = self.archive.take() else {
+            return;
+        };
+        let mut entries = cache();
+        // archives of evicted or changed books are closed
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.path == self.path && entry.fingerprint == self.fingerprint);
+        if let Some(entry) = entry {
+            if entry.archives.len() < ARCHIVES_PER_BOOK {
+                entry.archives.push(archive);
+            }
+        }
+    }
+}
+
+// function to open an epub through the cache, the archive is only parsed again if the file changed
+pub fn open_cached_archive(path: &str) -> Result<CachedArchive, ReaderError> {
+    let fingerprint = Fingerprint::of(path)?;
+    let cached = find_entry(&mut cache(), path, fingerprint).and_then(|entry| entry.archives.pop());
+
+    let archive = match cached {
+        Some(archive) => archive,
+        None => {
+            // opened without holding the lock, reading the central directory of a large book takes time
+            let archive = open_source(path)?;
+            let mut entries = cache();
+            if find_entry(&mut entries, path, fingerprint).is_none() {
+                entries.push(CacheEntry {
+                    path: path.to_string(),
+                    fingerprint,
+                    archives: Vec::new(),
+                    package: None,
+                });
+                if entries.len() > CACHE_SIZE {
+                    entries.remove(0);
+                }
+            }
+            archive
+        }
+    };
+
+    Ok(CachedArchive {
+        path: path.to_string(),
+        fingerprint,
+        archive: Some(archive),
+    })
+}
+
+// function to open an epub through the cache together with its parsed package
+pub fn open_book(path: &str) -> Result<(CachedArchive, Arc<Package>), ReaderError> {
+    let mut archive = open_cached_archive(path)?;
+    let cached =
+        find_entry(&mut cache(), path, archive.fingerprint).and_then(|entry| entry.package.clone());
+    if let Some(package) = cached {
+        return Ok((archive, package));
+    }
+
+    let package = Arc::new(read_package(&mut archive)?);
+    if let Some(entry) = find_entry(&mut cache(), path, archive.fingerprint) {
+        entry.package = Some(package.clone());
+    }
+    Ok((archive, package))
+}
+
+// function to close a cached book, needed before the file is replaced or deleted
+pub fn evict(path: &str) {
+    cache().retain(|entry| entry.path != path);
+}
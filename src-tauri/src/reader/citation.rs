@@ -0,0 +1,824 @@
+/*
+    reader module:
+    citation.rs builds apa, mla, chicago and bibtex citations of library books from their metadata,
+    corrected by the user where needed, optionally pointing to the page or chapter of a quoted highlight
+*/
+
+// importing crates and modules
+use crate::reader::export::export_book;
+use crate::reader::highlights::HighlightRange;
+use crate::reader::library::{Book, Library};
+use crate::reader::pdf::load_document;
+use crate::reader::pdf_info::page_labels;
+use crate::reader::xml::{collapse_whitespace, escape_xml};
+use crate::reader::ReaderError;
+use serde::{Deserialize, Serialize};
+
+// limits used while validating corrected fields
+const MAX_FIELD_LENGTH: usize = 1000;
+const MAX_AUTHORS: usize = 100;
+
+// lowercase words which belong to the family name, e.g. Ludwig van Beethoven
+const NAME_PARTICLES: [&str; 12] = [
+    "da", "de", "del", "della", "der", "di", "du", "la", "le", "van", "von", "zu",
+];
+
+// CitationStyle enum: supported citation styles
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CitationStyle {
+    Apa,     // apa 7th edition
+    Mla,     // mla 9th edition
+    Chicago, // chicago 17th edition, notes and bibliography
+    Bibtex,
+}
+
+// styles in the order citations are returned
+const STYLES: [CitationStyle; 4] = [
+    CitationStyle::Apa,
+    CitationStyle::Mla,
+    CitationStyle::Chicago,
+    CitationStyle::Bibtex,
+];
+
+// CitationFields struct: metadata corrected by the user, missing fields are taken from the book metadata
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CitationFields {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub authors: Option<Vec<String>>, // "Given Family" or "Family, Given", an empty list removes the authors
+    pub edition: Option<String>,      // e.g. 2 or Revised
+    pub publisher: Option<String>,
+    pub place: Option<String>, // city of publication, used by chicago and bibtex
+    pub year: Option<String>,
+    pub isbn: Option<String>,
+    pub doi: Option<String>,
+    pub url: Option<String>,
+}
+
+// CitationSource struct: the fields citations are built from, corrections applied
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CitationSource {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub edition: Option<String>,
+    pub publisher: Option<String>,
+    pub place: Option<String>,
+    pub year: Option<String>,
+    pub isbn: Option<String>,
+    pub doi: Option<String>,
+    pub url: Option<String>,
+}
+
+// Quote struct: a highlight being quoted and where it is in the book
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Quote {
+    pub text: String,
+    pub page: Option<String>, // printed page label of pdfs, page number if the pdf has no labels
+    pub chapter: Option<String>, // chapter of epubs and documents, which have no pages
+}
+
+// Citation struct: a citation in one style
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Citation {
+    pub style: CitationStyle,
+    pub reference: String, // reference list or bibliography entry, the entry itself for bibtex
+    pub reference_html: String, // reference with the title in italics
+    pub in_text: String,   // parenthetical citation, a note for chicago and \cite for bibtex
+    pub quotation: Option<String>, // quoted highlight followed by its citation
+}
+
+// Citations struct: citations of a book in every style
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Citations {
+    pub source: CitationSource,
+    pub quote: Option<Quote>,
+    pub citations: Vec<Citation>,
+}
+
+// Person struct: a name split into given and family names
+#[derive(Clone, Debug)]
+struct Person {
+    given: Option<String>,
+    family: String,
+}
+
+// implementations for Person struct
+impl Person {
+    // splits a name written as "Given Family" or "Family, Given"
+    fn parse(name: &str) -> Option<Self> {
+        if let Some((family, given)) = name.split_once(',') {
+            return Some(Self {
+                given: Some(given.trim().to_string()).filter(|given| !given.is_empty()),
+                family: family.trim().to_string(),
+            })
+            .filter(|person| !person.family.is_empty());
+        }
+
+        let words: Vec<&str> = name.split_whitespace().collect();
+        let (last, rest) = words.split_last()?;
+        // particles before the last name are part of it
+        let family_start = rest
+            .iter()
+            .position(|word| NAME_PARTICLES.contains(word))
+            .filter(|&position| position > 0)
+            .unwrap_or(rest.len());
+
+        Some(Self {
+            given: Some(rest[..family_start].join(" ")).filter(|given| !given.is_empty()),
+            family: rest[family_start..]
+                .iter()
+                .chain(std::iter::once(last))
+                .copied()
+                .collect::<Vec<&str>>()
+                .join(" "),
+        })
+    }
+
+    // returns the initials of the given names, e.g. Kevin J. -> K. J., Jean-Paul -> J.-P.
+    fn initials(&self) -> Option<String> {
+        let given = self.given.as_ref()?;
+        let initials = given
+            .split_whitespace()
+            .map(|name| {
+                name.split('-')
+                    .filter_map(|part| part.chars().next())
+                    .map(|initial| format!("{}.", initial.to_uppercase()))
+                    .collect::<Vec<String>>()
+                    .join("-")
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        Some(initials)
+    }
+
+    // Given Family
+    fn full(&self) -> String {
+        match &self.given {
+            Some(given) => format!("{} {}", given, self.family),
+            None => self.family.clone(),
+        }
+    }
+
+    // Family, Given
+    fn inverted(&self) -> String {
+        match &self.given {
+            Some(given) => format!("{}, {}", self.family, given),
+            None => self.family.clone(),
+        }
+    }
+
+    // Family, G.
+    fn apa(&self) -> String {
+        match self.initials() {
+            Some(initials) => format!("{}, {}", self.family, initials),
+            None => self.family.clone(),
+        }
+    }
+}
+
+// Text struct: citation text with italic parts, written as plain text and html at once
+#[derive(Default)]
+struct Text {
+    plain: String,
+    html: String,
+}
+
+// implementations for Text struct
+impl Text {
+    fn push(&mut self, text: &str) {
+        self.plain.push_str(text);
+        self.html.push_str(&escape_xml(text));
+    }
+
+    fn italic(&mut self, text: &str) {
+        self.plain.push_str(text);
+        self.html.push_str(&format!("<i>{}</i>", escape_xml(text)));
+    }
+
+    fn append(&mut self, other: Text) {
+        self.plain.push_str(&other.plain);
+        self.html.push_str(&other.html);
+    }
+}
+
+// function to trim a field, empty fields are None
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| collapse_whitespace(&value))
+        .filter(|value| !value.is_empty())
+}
+
+// function to check the length of a corrected field
+fn check_length(name: &str, value: &Option<String>) -> Result<(), ReaderError> {
+    if value
+        .as_ref()
+        .is_some_and(|value| value.chars().count() > MAX_FIELD_LENGTH)
+    {
+        return Err(ReaderError::ValidationError(format!(
+            "{} is longer than {} characters",
+            name, MAX_FIELD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+// function to get the doi from a doi, doi: link or doi.org url
+fn doi(value: &str) -> Option<String> {
+    let lower = value.to_ascii_lowercase();
+    let start = lower.find("10.")?;
+    let prefix = lower[..start].trim_end_matches('/');
+    let known = prefix.is_empty()
+        || prefix.ends_with("doi:")
+        || prefix.ends_with("doi.org")
+        || prefix.ends_with("dx.doi.org");
+    (known && value[start..].contains('/')).then(|| value[start..].trim().to_string())
+}
+
+// function to get an isbn (10 or 13 digits, hyphens and spaces removed) from an identifier
+fn isbn(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix("urn:isbn:")
+        .or_else(|| value.strip_prefix("isbn:"))
+        .or_else(|| value.strip_prefix("ISBN:"))
+        .or_else(|| value.strip_prefix("ISBN"))
+        .unwrap_or(value)
+        .trim();
+    // counted in chars, a byte length would let multibyte characters through
+    let digits: Vec<char> = value.chars().filter(|c| !matches!(c, '-' | ' ')).collect();
+
+    let valid = match digits.len() {
+        10 => {
+            digits[..9].iter().all(char::is_ascii_digit)
+                && matches!(digits[9], '0'..='9' | 'X' | 'x')
+        }
+        13 => digits.iter().all(char::is_ascii_digit),
+        _ => false,
+    };
+    valid.then(|| digits.iter().collect::<String>().to_uppercase())
+}
+
+// implementations for CitationFields struct
+impl CitationFields {
+    // trims the fields and checks them, empty fields are removed
+    fn validate(self) -> Result<Self, ReaderError> {
+        let fields = Self {
+            title: clean(self.title),
+            subtitle: clean(self.subtitle),
+            authors: self.authors.map(|authors| {
+                authors
+                    .into_iter()
+                    .filter_map(|author| clean(Some(author)))
+                    .collect()
+            }),
+            edition: clean(self.edition),
+            publisher: clean(self.publisher),
+            place: clean(self.place),
+            year: clean(self.year),
+            isbn: clean(self.isbn),
+            doi: clean(self.doi),
+            url: clean(self.url),
+        };
+
+        for (name, value) in [
+            ("title", &fields.title),
+            ("subtitle", &fields.subtitle),
+            ("edition", &fields.edition),
+            ("publisher", &fields.publisher),
+            ("place", &fields.place),
+            ("url", &fields.url),
+        ] {
+            check_length(name, value)?;
+        }
+        if let Some(authors) = &fields.authors {
+            if authors.len() > MAX_AUTHORS {
+                return Err(ReaderError::ValidationError(format!(
+                    "a book can have at most {} authors",
+                    MAX_AUTHORS
+                )));
+            }
+            for author in authors {
+                check_length("author", &Some(author.clone()))?;
+            }
+        }
+
+        if let Some(year) = &fields.year {
+            if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+                return Err(ReaderError::ValidationError(format!(
+                    "{} is not a year",
+                    year
+                )));
+            }
+        }
+        let isbn = match &fields.isbn {
+            Some(value) => Some(isbn(value).ok_or_else(|| {
+                ReaderError::ValidationError(format!("{} is not an isbn", value))
+            })?),
+            None => None,
+        };
+        let doi =
+            match &fields.doi {
+                Some(value) => Some(doi(value).ok_or_else(|| {
+                    ReaderError::ValidationError(format!("{} is not a doi", value))
+                })?),
+                None => None,
+            };
+        if let Some(url) = &fields.url {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return Err(ReaderError::ValidationError(format!(
+                    "{} is not a web address",
+                    url
+                )));
+            }
+        }
+
+        Ok(Self {
+            isbn,
+            doi,
+            ..fields
+        })
+    }
+}
+
+// implementations for CitationSource struct
+impl CitationSource {
+    // applies the corrections of a book to its metadata
+    pub fn resolve(book: &Book) -> Self {
+        let fields = &book.citation;
+        let metadata = &book.metadata;
+        let identifier = metadata.identifier.as_deref();
+
+        Self {
+            title: fields
+                .title
+                .clone()
+                .unwrap_or_else(|| metadata.title.clone()),
+            subtitle: fields.subtitle.clone(),
+            authors: fields
+                .authors
+                .clone()
+                .unwrap_or_else(|| metadata.authors.clone()),
+            edition: fields.edition.clone(),
+            publisher: fields
+                .publisher
+                .clone()
+                .or_else(|| metadata.publisher.clone()),
+            place: fields.place.clone(),
+            // dates are stored as written, the year is the first four digit number
+            year: fields.year.clone().or_else(|| {
+                let date = metadata.date.as_deref()?;
+                date.split(|c: char| !c.is_ascii_digit())
+                    .find(|part| part.len() == 4)
+                    .map(str::to_string)
+            }),
+            isbn: fields.isbn.clone().or_else(|| identifier.and_then(isbn)),
+            doi: fields.doi.clone().or_else(|| identifier.and_then(doi)),
+            url: fields.url.clone(),
+        }
+    }
+
+    // title followed by the subtitle
+    fn full_title(&self) -> String {
+        match &self.subtitle {
+            Some(subtitle) => format!("{}: {}", self.title, subtitle),
+            None => self.title.clone(),
+        }
+    }
+
+    fn people(&self) -> Vec<Person> {
+        self.authors
+            .iter()
+            .filter_map(|author| Person::parse(author))
+            .collect()
+    }
+
+    // doi as a link, or the url
+    fn link(&self) -> Option<String> {
+        self.doi
+            .as_ref()
+            .map(|doi| format!("https://doi.org/{}", doi))
+            .or_else(|| self.url.clone())
+    }
+}
+
+// function to write the ordinal of a number, e.g. 2 -> 2nd
+fn ordinal(number: u32) -> String {
+    let suffix = match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", number, suffix)
+}
+
+// function to write an edition, e.g. 2 -> 2nd ed., Revised -> Revised ed.
+fn edition(edition: &str) -> String {
+    let edition = match edition.parse::<u32>() {
+        Ok(number) => ordinal(number),
+        Err(_) => edition.to_string(),
+    };
+    if edition.to_lowercase().contains("ed") && edition.ends_with('.') {
+        edition
+    } else {
+        format!("{} ed.", edition)
+    }
+}
+
+// function to end a sentence with a period, unless it already ends with a punctuation mark
+fn sentence(text: &str) -> String {
+    if text.ends_with(['.', '?', '!']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+// function to join names with commas and a conjunction before the last one
+fn join_names(names: &[String], conjunction: &str, serial_comma: bool) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [first, second] => format!("{} {} {}", first, conjunction, second),
+        [rest @ .., last] => format!(
+            "{}{} {} {}",
+            rest.join(", "),
+            if serial_comma { "," } else { "" },
+            conjunction,
+            last
+        ),
+    }
+}
+
+// function to build an apa reference and in-text citation
+fn apa(source: &CitationSource, quote: Option<&Quote>) -> (Text, String) {
+    let people = source.people();
+    let year = source.year.clone().unwrap_or_else(|| "n.d.".to_string());
+    let mut text = Text::default();
+
+    let mut title_part = Text::default();
+    title_part.italic(&source.full_title());
+    if let Some(value) = &source.edition {
+        title_part.push(&format!(" ({})", edition(value)));
+    }
+
+    if people.is_empty() {
+        // works without authors start with the title
+        text.append(title_part);
+        text.push(&format!(". ({}).", year));
+    } else {
+        let names: Vec<String> = people.iter().map(Person::apa).collect();
+        let authors = if names.len() > 20 {
+            format!(
+                "{}, . . . {}",
+                names[..19].join(", "),
+                names[names.len() - 1]
+            )
+        } else {
+            join_names(&names, "&", true)
+        };
+        text.push(&format!("{} ({}). ", authors, year));
+        text.append(title_part);
+        text.push(".");
+    }
+    if let Some(publisher) = &source.publisher {
+        text.push(&format!(" {}", sentence(publisher)));
+    }
+    if let Some(link) = source.link() {
+        text.push(&format!(" {}", link));
+    }
+
+    let names = match people.as_slice() {
+        [] => source.title.clone(),
+        [person] => person.family.clone(),
+        [first, second] => format!("{} & {}", first.family, second.family),
+        [first, ..] => format!("{} et al.", first.family),
+    };
+    let location = match quote {
+        Some(Quote {
+            page: Some(page), ..
+        }) => format!(", p. {}", page),
+        Some(Quote {
+            chapter: Some(chapter),
+            ..
+        }) => format!(", {} section", chapter),
+        _ => String::new(),
+    };
+    (text, format!("({}, {}{})", names, year, location))
+}
+
+// function to build an mla works cited entry and in-text citation
+fn mla(source: &CitationSource, quote: Option<&Quote>) -> (Text, String) {
+    let people = source.people();
+    let mut text = Text::default();
+
+    let authors = match people.as_slice() {
+        [] => None,
+        [person] => Some(person.inverted()),
+        [first, second] => Some(format!("{}, and {}", first.inverted(), second.full())),
+        [first, ..] => Some(format!("{}, et al", first.inverted())),
+    };
+    if let Some(authors) = authors {
+        text.push(&format!("{} ", sentence(&authors)));
+    }
+    text.italic(&source.full_title());
+    text.push(if source.full_title().ends_with(['?', '!']) {
+        ""
+    } else {
+        "."
+    });
+
+    // publication facts are a list ending with a period
+    let facts: Vec<String> = [
+        source.edition.as_deref().map(edition),
+        source.publisher.clone(),
+        source.year.clone(),
+        source.link(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !facts.is_empty() {
+        text.push(&format!(" {}", sentence(&facts.join(", "))));
+    }
+
+    let names = match people.as_slice() {
+        [] => format!("\u{201c}{}\u{201d}", source.title),
+        [person] => person.family.clone(),
+        [first, second] => format!("{} and {}", first.family, second.family),
+        [first, ..] => format!("{} et al.", first.family),
+    };
+    let location = match quote {
+        Some(Quote {
+            page: Some(page), ..
+        }) => format!(" {}", page),
+        Some(Quote {
+            chapter: Some(chapter),
+            ..
+        }) => format!(", \u{201c}{}\u{201d}", chapter),
+        _ => String::new(),
+    };
+    (text, format!("({}{})", names, location))
+}
+
+// function to build a chicago bibliography entry and note
+fn chicago(source: &CitationSource, quote: Option<&Quote>) -> (Text, String) {
+    let people = source.people();
+    let mut text = Text::default();
+
+    // the first author is inverted in the bibliography, more than ten are shortened to seven
+    let mut names: Vec<String> = people.iter().map(Person::full).collect();
+    if let Some(first) = people.first() {
+        names[0] = first.inverted();
+    }
+    let authors = if names.len() > 10 {
+        format!("{}, et al", names[..7].join(", "))
+    } else {
+        join_names(&names, "and", true)
+    };
+    if !authors.is_empty() {
+        text.push(&format!("{} ", sentence(&authors)));
+    }
+    text.italic(&source.full_title());
+    text.push(".");
+    if let Some(value) = &source.edition {
+        text.push(&format!(" {}", edition(value)));
+    }
+
+    let year = source.year.clone().unwrap_or_else(|| "n.d.".to_string());
+    let facts = match (&source.place, &source.publisher) {
+        (Some(place), Some(publisher)) => format!("{}: {}, {}", place, publisher, year),
+        (None, Some(publisher)) => format!("{}, {}", publisher, year),
+        (Some(place), None) => format!("{}, {}", place, year),
+        (None, None) => year,
+    };
+    text.push(&format!(" {}", sentence(&facts)));
+    if let Some(link) = source.link() {
+        text.push(&format!(" {}.", link));
+    }
+
+    let names: Vec<String> = people.iter().map(Person::full).collect();
+    let note_authors = match names.as_slice() {
+        [] => String::new(),
+        [_, _, _, _, ..] => format!("{} et al., ", names[0]),
+        names => format!("{}, ", join_names(names, "and", true)),
+    };
+    let location = match quote {
+        Some(Quote {
+            page: Some(page), ..
+        }) => format!(", {}", page),
+        Some(Quote {
+            chapter: Some(chapter),
+            ..
+        }) => format!(", chap. \u{201c}{}\u{201d}", chapter),
+        _ => String::new(),
+    };
+    let note = format!(
+        "{}{} ({}){}.",
+        note_authors,
+        source.full_title(),
+        facts,
+        location
+    );
+    (text, note)
+}
+
+// function to escape characters with special meaning in bibtex
+fn escape_bibtex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// function to build the citation key of a bibtex entry, e.g. herbert1965dune
+fn bibtex_key(source: &CitationSource) -> String {
+    let word = |text: &str| -> String {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .filter(char::is_ascii_alphanumeric)
+                    .collect::<String>()
+                    .to_lowercase()
+            })
+            .find(|word| word.len() > 3 || word.chars().all(|c| c.is_ascii_digit()))
+            .or_else(|| text.split_whitespace().next().map(str::to_lowercase))
+            .unwrap_or_default()
+    };
+
+    let people = source.people();
+    let author = people
+        .first()
+        .map(|person| word(&person.family))
+        .unwrap_or_default();
+    let key = format!(
+        "{}{}{}",
+        author,
+        source.year.as_deref().unwrap_or_default(),
+        word(&source.title)
+    );
+    if key.is_empty() {
+        "book".to_string()
+    } else {
+        key
+    }
+}
+
+// function to build a bibtex entry and cite command
+fn bibtex(source: &CitationSource, quote: Option<&Quote>) -> (Text, String) {
+    let key = bibtex_key(source);
+    let authors: Vec<String> = source.people().iter().map(Person::inverted).collect();
+
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    if !authors.is_empty() {
+        fields.push(("author", authors.join(" and ")));
+    }
+    fields.push(("title", source.full_title()));
+    let optional = [
+        ("edition", source.edition.clone()),
+        ("publisher", source.publisher.clone()),
+        ("address", source.place.clone()),
+        ("year", source.year.clone()),
+        ("isbn", source.isbn.clone()),
+        ("doi", source.doi.clone()),
+        ("url", source.url.clone()),
+        ("pages", quote.and_then(|quote| quote.page.clone())),
+        ("chapter", quote.and_then(|quote| quote.chapter.clone())),
+    ];
+    fields.extend(
+        optional
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| (name, value))),
+    );
+
+    let mut text = Text::default();
+    text.push(&format!("@book{{{},\n", key));
+    for (name, value) in fields {
+        // urls and dois are verbatim, escaping would break them
+        let value = if matches!(name, "url" | "doi") {
+            value
+        } else {
+            escape_bibtex(&value)
+        };
+        text.push(&format!("  {} = {{{}}},\n", name, value));
+    }
+    text.push("}");
+
+    let cite = match quote {
+        Some(Quote {
+            page: Some(page), ..
+        }) => format!("\\cite[p.~{}]{{{}}}", escape_bibtex(page), key),
+        _ => format!("\\cite{{{}}}", key),
+    };
+    (text, cite)
+}
+
+// function to build the citation of a book in a style
+pub fn build_citation(
+    source: &CitationSource,
+    style: CitationStyle,
+    quote: Option<&Quote>,
+) -> Citation {
+    let (reference, in_text) = match style {
+        CitationStyle::Apa => apa(source, quote),
+        CitationStyle::Mla => mla(source, quote),
+        CitationStyle::Chicago => chicago(source, quote),
+        CitationStyle::Bibtex => bibtex(source, quote),
+    };
+
+    // chicago cites quotations in a note, bibtex with latex quotes
+    let quotation = quote.map(|quote| match style {
+        CitationStyle::Chicago => format!("\u{201c}{}\u{201d}\n{}", quote.text, in_text),
+        CitationStyle::Bibtex => format!("``{}'' {}", escape_bibtex(&quote.text), in_text),
+        _ => format!("\u{201c}{}\u{201d} {}", quote.text, in_text),
+    });
+
+    Citation {
+        style,
+        reference: reference.plain,
+        reference_html: reference.html,
+        in_text,
+        quotation,
+    }
+}
+
+// function to find a highlight of a book and where it is, pdf pages use their printed labels
+fn find_quote(book: &Book, highlight_id: &str) -> Result<Quote, ReaderError> {
+    let exported = export_book(book)?
+        .highlights
+        .into_iter()
+        .find(|exported| exported.highlight.id == highlight_id)
+        .ok_or_else(|| ReaderError::NotFound(format!("highlight {} not found", highlight_id)))?;
+
+    let (page, chapter) = match exported.highlight.range {
+        HighlightRange::Pdf { page, .. } => {
+            let label = load_document(&book.path).ok().and_then(|document| {
+                let labels = page_labels(&document, document.get_pages().len() as u32);
+                labels.get(page as usize - 1).cloned().flatten()
+            });
+            (Some(label.unwrap_or_else(|| page.to_string())), None)
+        }
+        HighlightRange::Epub { .. } => (None, exported.context),
+    };
+
+    Ok(Quote {
+        text: collapse_whitespace(&exported.highlight.text),
+        page,
+        chapter,
+    })
+}
+
+// library_book_citations_get command: returns the citations of a library book in every style when invoked
+// citations point to the page or chapter of the highlight if one is given
+#[tauri::command]
+pub fn library_book_citations_get(
+    id: &str,
+    highlight_id: Option<String>,
+) -> Result<Citations, ReaderError> {
+    let library = Library::load()?;
+    let book = library
+        .book(id)
+        .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", id)))?;
+
+    let source = CitationSource::resolve(book);
+    let quote = match highlight_id {
+        Some(highlight_id) => Some(find_quote(book, &highlight_id)?),
+        None => None,
+    };
+    let citations = STYLES
+        .iter()
+        .map(|style| build_citation(&source, *style, quote.as_ref()))
+        .collect();
+
+    Ok(Citations {
+        source,
+        quote,
+        citations,
+    })
+}
+
+// library_book_citation_set command: saves the metadata corrections used for citations of a library book when invoked
+// fields which are left empty are taken from the book metadata again
+#[tauri::command]
+pub fn library_book_citation_set(id: &str, fields: CitationFields) -> Result<Book, ReaderError> {
+    let fields = fields.validate()?;
+
+    let mut library = Library::load()?;
+    let book = library.book_mut(id)?;
+    book.citation = fields;
+    let book = book.clone();
+    library.save()?;
+    Ok(book)
+}
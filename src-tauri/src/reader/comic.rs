@@ -0,0 +1,257 @@
+/*
+    reader module:
+    comic.rs reads comic book archives (cbz), the images of the archive are the pages of the book in natural order
+*/
+
+// importing crates and modules
+use crate::functions::{read_bytes, save_bytes};
+use crate::reader::cache::open_cached_archive;
+use crate::reader::epub::{read_entry_bytes, read_entry_string};
+use crate::reader::library::BookMetadata;
+use crate::reader::xml::{collapse_whitespace, decode_entities, tokenize, Token};
+use crate::reader::ReaderError;
+use image::codecs::jpeg::JpegEncoder;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::Read;
+
+// extensions of archive entries read as pages
+const PAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "gif", "webp", "bmp"];
+
+// maximum width and height of page previews (aspect ratio is preserved)
+const PREVIEW_BOUNDS: (u32, u32) = (240, 360);
+
+// metadata file written by comic taggers at the root of the archive
+const COMIC_INFO_FILE: &str = "ComicInfo.xml";
+
+// ComicPage struct: an image of a comic archive
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ComicPage {
+    pub page: u32,    // 1 based page number
+    pub name: String, // path of the image inside the archive
+    pub size: u64,
+}
+
+// function to compare names the way people count, e.g. page2.jpg comes before page10.jpg
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+
+    while let (Some(&a_next), Some(&b_next)) = (a_chars.peek(), b_chars.peek()) {
+        if a_next.is_ascii_digit() && b_next.is_ascii_digit() {
+            let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                digits
+            };
+            let (a_number, b_number) = (take_number(&mut a_chars), take_number(&mut b_chars));
+            let (a_trimmed, b_trimmed) = (
+                a_number.trim_start_matches('0'),
+                b_number.trim_start_matches('0'),
+            );
+            // numbers without leading zeros compare by length first, then digit by digit
+            let order = a_trimmed
+                .len()
+                .cmp(&b_trimmed.len())
+                .then_with(|| a_trimmed.cmp(b_trimmed));
+            if order != Ordering::Equal {
+                return order;
+            }
+        } else {
+            let order = a_next.to_lowercase().cmp(b_next.to_lowercase());
+            if order != Ordering::Equal {
+                return order;
+            }
+            a_chars.next();
+            b_chars.next();
+        }
+    }
+
+    // names equal apart from case or leading zeros keep a stable order
+    a_chars.count().cmp(&b_chars.count()).then_with(|| a.cmp(b))
+}
+
+// function to check if an archive entry is a page, hidden files and macOS resource forks are skipped
+fn is_page(name: &str) -> bool {
+    if name.ends_with('/') || name.split('/').any(|part| part == "__MACOSX") {
+        return false;
+    }
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    if file_name.starts_with('.') {
+        return false;
+    }
+
+    file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .is_some_and(|extension| PAGE_EXTENSIONS.contains(&extension.as_str()))
+}
+
+// function to check that a comic archive can be read, cbr files are only supported if they are zips
+fn check_archive(path: &str) -> Result<(), ReaderError> {
+    let mut magic = [0; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    if read == 4 && magic == *b"Rar!" {
+        return Err(ReaderError::InvalidData(format!(
+            "{} is a rar archive, only zip comic archives (cbz) are supported",
+            path
+        )));
+    }
+    Ok(())
+}
+
+// function to list the pages of a comic archive in reading order
+pub fn list_pages(path: &str) -> Result<Vec<ComicPage>, ReaderError> {
+    check_archive(path)?;
+    let mut archive = open_cached_archive(path)?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        if is_page(entry.name()) {
+            entries.push((entry.name().to_string(), entry.size()));
+        }
+    }
+    entries.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, (name, size))| ComicPage {
+            page: index as u32 + 1,
+            name,
+            size,
+        })
+        .collect())
+}
+
+// function to find a page of a comic archive by its number
+pub fn find_page(path: &str, page: u32) -> Result<ComicPage, ReaderError> {
+    let pages = list_pages(path)?;
+    page.checked_sub(1)
+        .and_then(|index| pages.into_iter().nth(index as usize))
+        .ok_or_else(|| ReaderError::NotFound(format!("page {} not found in {}", page, path)))
+}
+
+// function to read the image of a page
+pub fn read_page(path: &str, page: &ComicPage) -> Result<Vec<u8>, ReaderError> {
+    let mut archive = open_cached_archive(path)?;
+    read_entry_bytes(&mut archive, &page.name)
+}
+
+// function to get the image of the first page, used as cover
+pub fn comic_cover(path: &str) -> Result<Option<Vec<u8>>, ReaderError> {
+    match list_pages(path)?.first() {
+        Some(page) => Ok(Some(read_page(path, page)?)),
+        None => Ok(None),
+    }
+}
+
+// function to generate path of a cached page preview, previews are kept next to the cover thumbnails
+fn preview_path(book_id: &str, page: u32) -> String {
+    format!("covers/{}/pages/{}.jpg", book_id, page)
+}
+
+// function to get a downscaled page from cache, generating it on first request
+// pages in formats the image crate can't decode are returned as they are
+pub fn page_preview(
+    book_id: &str,
+    path: &str,
+    page: u32,
+) -> Result<(Vec<u8>, String), ReaderError> {
+    if let Ok(preview) = read_bytes(&preview_path(book_id, page)) {
+        return Ok((preview, "preview.jpg".to_string()));
+    }
+
+    let page = find_page(path, page)?;
+    let data = read_page(path, &page)?;
+    let Ok(image) = image::load_from_memory(&data) else {
+        return Ok((data, page.name));
+    };
+
+    let (width, height) = PREVIEW_BOUNDS;
+    let preview = image.thumbnail(width, height).to_rgb8(); // jpeg has no alpha channel
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 80).encode_image(&preview)?;
+    save_bytes(&preview_path(book_id, page.page), &jpeg)?;
+    Ok((jpeg, "preview.jpg".to_string()))
+}
+
+// function to read the metadata of ComicInfo.xml, None if the archive has no readable metadata
+pub fn read_comic_info(path: &str) -> Option<BookMetadata> {
+    let mut archive = open_cached_archive(path).ok()?;
+    let data = read_entry_string(&mut archive, COMIC_INFO_FILE).ok()?;
+
+    let mut fields: Vec<(String, String)> = Vec::new(); // element name and text
+    let mut element: Option<(String, String)> = None;
+    for token in tokenize(&data) {
+        match token {
+            Token::Tag(tag) if tag.is_open() => element = Some((tag.name.clone(), String::new())),
+            Token::Tag(tag) if tag.is_close() => {
+                if let Some((name, text)) = element.take().filter(|(name, _)| *name == tag.name) {
+                    let text = collapse_whitespace(&text);
+                    if !text.is_empty() {
+                        fields.push((name, text));
+                    }
+                }
+            }
+            Token::Text(raw) => {
+                if let Some((_, text)) = element.as_mut() {
+                    text.push_str(&decode_entities(raw));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, text)| text.clone())
+    };
+    // people and genres are comma separated lists, a writer who also drew the pages is listed once
+    let list = |names: &[&str]| {
+        let mut values: Vec<String> = Vec::new();
+        for value in names.iter().filter_map(|name| field(name)) {
+            for part in value.split(',').map(str::trim) {
+                if !part.is_empty() && !values.iter().any(|existing| existing == part) {
+                    values.push(part.to_string());
+                }
+            }
+        }
+        values
+    };
+
+    // issues of a series are named after the series and number if they have no title of their own
+    let title = match (field("title"), field("series"), field("number")) {
+        (Some(title), _, _) => title,
+        (None, Some(series), Some(number)) => format!("{} #{}", series, number),
+        (None, Some(series), None) => series,
+        (None, None, _) => return None,
+    };
+    let date = field("year").map(|year| match (field("month"), field("day")) {
+        (Some(month), Some(day)) => format!("{}-{:0>2}-{:0>2}", year, month, day),
+        (Some(month), None) => format!("{}-{:0>2}", year, month),
+        _ => year,
+    });
+
+    Some(BookMetadata {
+        title,
+        authors: list(&["writer", "penciller", "artist"]),
+        language: field("languageiso"),
+        publisher: field("publisher"),
+        identifier: field("gtin"),
+        description: field("summary"),
+        date,
+        subjects: list(&["genre", "tags"]),
+    })
+}
+
+// comic_pages_list command: returns the pages of a comic archive in reading order when invoked
+#[tauri::command]
+pub fn comic_pages_list(path: &str) -> Result<Vec<ComicPage>, ReaderError> {
+    list_pages(path)
+}
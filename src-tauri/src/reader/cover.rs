@@ -0,0 +1,219 @@
+/*
+    reader module:
+    cover.rs extracts cover images of books and caches their thumbnails in the app data directory
+*/
+
+// importing crates and modules
+use crate::functions::{delete_dir, delete_file, read_bytes, save_bytes};
+use crate::reader::cache::open_book;
+use crate::reader::comic::comic_cover;
+use crate::reader::epub::{read_entry_bytes, read_entry_string};
+use crate::reader::library::{is_book_id, Book, BookFormat, Library};
+use crate::reader::pdf::first_page_image;
+use crate::reader::xml::{parent_dir, resolve_path, tokenize, Token};
+use crate::reader::ReaderError;
+use image::codecs::jpeg::JpegEncoder;
+use serde::{Deserialize, Serialize};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Runtime, UriSchemeContext};
+
+// thumbnail sizes generated for every cover
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ThumbnailSize {
+    Small,
+    Medium,
+    Large,
+}
+
+// implementations for ThumbnailSize enum
+impl ThumbnailSize {
+    const ALL: [ThumbnailSize; 3] = [Self::Small, Self::Medium, Self::Large];
+
+    // maximum width and height of the thumbnail (aspect ratio is preserved)
+    fn bounds(self) -> (u32, u32) {
+        match self {
+            Self::Small => (120, 180),
+            Self::Medium => (240, 360),
+            Self::Large => (480, 720),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.name() == name)
+    }
+}
+
+// function to generate path of a cached thumbnail
+fn thumbnail_path(book_id: &str, size: ThumbnailSize) -> String {
+    format!("covers/{}/{}.jpg", book_id, size.name())
+}
+
+// function to generate path of the marker saved for books without a cover
+fn no_cover_path(book_id: &str) -> String {
+    format!("covers/{}/none", book_id)
+}
+
+// function to extract the original cover image of a book
+pub fn extract_cover(book: &Book) -> Result<Option<Vec<u8>>, ReaderError> {
+    match book.format {
+        BookFormat::Epub => e_pub_cover(&book.path),
+        BookFormat::Pdf => first_page_image(&book.path),
+        BookFormat::Comic => comic_cover(&book.path),
+        BookFormat::Text
+        | BookFormat::Markdown
+        | BookFormat::Html
+        | BookFormat::Docx
+        | BookFormat::Odt => Ok(None),
+    }
+}
+
+// function to extract cover of an epub from manifest, or the first image of the first spine item
+fn e_pub_cover(path: &str) -> Result<Option<Vec<u8>>, ReaderError> {
+    let (mut archive, package) = open_book(path)?;
+
+    if let Some(item) = package.cover_item() {
+        return Ok(Some(read_entry_bytes(&mut archive, &item.href)?));
+    }
+
+    let Some(first) = package.spine_items().first().map(|item| item.href.clone()) else {
+        return Ok(None);
+    };
+    let data = read_entry_string(&mut archive, &first)?;
+
+    // <img src="..."> or svg <image xlink:href="...">
+    let image_href = tokenize(&data).into_iter().find_map(|token| match token {
+        Token::Tag(tag) if tag.name == "img" => tag.attr("src"),
+        Token::Tag(tag) if tag.name == "image" => tag.attr("xlink:href").or(tag.attr("href")),
+        _ => None,
+    });
+
+    match image_href {
+        Some(href) => {
+            let image_path = resolve_path(parent_dir(&first), &href);
+            Ok(Some(read_entry_bytes(&mut archive, &image_path)?))
+        }
+        None => Ok(None),
+    }
+}
+
+// function to resize a cover image to all thumbnail sizes and save them in cache
+fn save_thumbnails(book_id: &str, data: &[u8]) -> Result<(), ReaderError> {
+    let cover = image::load_from_memory(data)?;
+
+    for size in ThumbnailSize::ALL {
+        let (width, height) = size.bounds();
+        let thumbnail = cover.thumbnail(width, height).to_rgb8(); // jpeg has no alpha channel
+
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 85).encode_image(&thumbnail)?;
+        save_bytes(&thumbnail_path(book_id, size), &jpeg)?;
+    }
+
+    let _ = delete_file(&no_cover_path(book_id));
+    Ok(())
+}
+
+// function to find the library book of an id sent by the webview
+// the id is checked before it is used in a path, so it can't point outside the cover cache
+fn library_book(book_id: &str) -> Result<Book, ReaderError> {
+    if !is_book_id(book_id) {
+        return Err(ReaderError::ValidationError(format!(
+            "{} is not a book id",
+            book_id
+        )));
+    }
+
+    Library::load()?
+        .book(book_id)
+        .cloned()
+        .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", book_id)))
+}
+
+// function to get a cover thumbnail from cache, generating the thumbnails on first request
+pub fn cover_thumbnail(book_id: &str, size: ThumbnailSize) -> Result<Option<Vec<u8>>, ReaderError> {
+    let book = library_book(book_id)?;
+    if let Ok(thumbnail) = read_bytes(&thumbnail_path(book_id, size)) {
+        return Ok(Some(thumbnail));
+    }
+    if read_bytes(&no_cover_path(book_id)).is_ok() {
+        return Ok(None);
+    }
+
+    match extract_cover(&book)? {
+        Some(cover) => {
+            save_thumbnails(book_id, &cover)?;
+            Ok(Some(read_bytes(&thumbnail_path(book_id, size))?))
+        }
+        None => {
+            // remember that there is no cover so the book is not read again on every request
+            save_bytes(&no_cover_path(book_id), &[])?;
+            Ok(None)
+        }
+    }
+}
+
+// function to delete cached thumbnails of a book
+pub fn clear_cover_cache(book_id: &str) {
+    if is_book_id(book_id) {
+        let _ = delete_dir(&format!("covers/{}", book_id));
+    }
+}
+
+// book_cover_generate command: generates thumbnails if needed and returns whether the book has a cover when invoked
+#[tauri::command]
+pub fn book_cover_generate(book_id: &str) -> Result<bool, ReaderError> {
+    Ok(cover_thumbnail(book_id, ThumbnailSize::Small)?.is_some())
+}
+
+// book_cover_save command: saves an image rendered by the ui (e.g. first page of a pdf) as cover when invoked
+#[tauri::command]
+pub fn book_cover_save(book_id: &str, data: Vec<u8>) -> Result<(), ReaderError> {
+    library_book(book_id)?;
+    clear_cover_cache(book_id);
+    save_thumbnails(book_id, &data)
+}
+
+// cover_protocol: handles cover://localhost/{book_id}/{size} requests from the webview
+pub fn cover_protocol<R: Runtime>(
+    _ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let path = request.uri().path().trim_matches('/');
+    let (book_id, size) = path.split_once('/').unwrap_or((path, "medium"));
+
+    let Some(size) = ThumbnailSize::from_name(size) else {
+        return error_response(StatusCode::BAD_REQUEST, "unknown thumbnail size");
+    };
+
+    match cover_thumbnail(book_id, size) {
+        Ok(Some(thumbnail)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "image/jpeg")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(thumbnail)
+            .unwrap_or_default(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "book has no cover"),
+        Err(ReaderError::ValidationError(message)) => {
+            error_response(StatusCode::BAD_REQUEST, &message)
+        }
+        Err(ReaderError::NotFound(message)) => error_response(StatusCode::NOT_FOUND, &message),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+// function to build a plain text error response for custom protocols
+pub fn error_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(message.as_bytes().to_vec())
+        .unwrap_or_default()
+}
@@ -0,0 +1,827 @@
+/*
+    reader module:
+    dictionary.rs looks up selected words in stardict dictionaries (.ifo, .idx, .dict or .dict.dz)
+    placed by the user in the dictionaries folder of the app data directory, without going online
+*/
+
+// importing crates and modules
+use crate::functions::read_dir;
+use crate::reader::text::html_to_text;
+use crate::reader::ReaderError;
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use flate2::{Decompress, FlushDecompress};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
+
+// directory of the dictionaries inside the app data directory
+// dictionaries are placed directly in it, or in a folder of their own
+const DICTIONARY_DIR: &str = "dictionaries";
+
+// first line of every stardict .ifo file
+const IFO_MAGIC: &str = "StarDict's dict ifo file";
+
+// number of entries returned when no limit is given, and the most a lookup may ask for
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+// longest selection looked up, anything longer is not a word or phrase
+const MAX_WORD_LENGTH: usize = 100;
+
+// definitions larger than this are treated as a corrupt index
+const MAX_DEFINITION_SIZE: u32 = 4 * 1024 * 1024;
+
+// number of prefix matches considered per dictionary before the shortest ones are picked
+const PREFIX_CANDIDATES: usize = 200;
+
+// how a dictionary word matched the looked up word
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum MatchKind {
+    Exact,  // same word, ignoring case
+    Prefix, // the dictionary word starts with the looked up word
+    Fuzzy,  // the dictionary word is one or two edits away, used when nothing else matches
+}
+
+// DictionaryInfo struct: a dictionary found in the dictionaries folder
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DictionaryInfo {
+    pub id: String, // path of the .ifo file relative to the dictionaries folder
+    pub name: String,
+    pub word_count: usize,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub error: Option<String>, // why the dictionary could not be loaded, it is not used for lookups
+}
+
+// DictionaryEntry struct: a definition found for a looked up word
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DictionaryEntry {
+    pub dictionary_id: String,
+    pub dictionary: String, // name of the dictionary
+    pub word: String,       // headword as written in the dictionary
+    pub definition: String, // plain text, markup of html, pango and xdxf definitions is removed
+    pub match_kind: MatchKind,
+}
+
+// Ifo struct: the fields of an .ifo file used for reading the dictionary
+struct Ifo {
+    name: String,
+    word_count: usize,
+    author: Option<String>,
+    description: Option<String>,
+    offset_bits: u32, // width of definition offsets in the index, 32 or 64
+    same_type_sequence: Option<String>, // field types of every definition, when they are all alike
+}
+
+// DictionaryFiles struct: the files a dictionary is made of
+struct DictionaryFiles {
+    ifo: PathBuf,
+    idx: PathBuf,         // .idx or .idx.gz
+    dict: PathBuf,        // .dict or .dict.dz
+    syn: Option<PathBuf>, // synonyms pointing to index words
+}
+
+// implementations for DictionaryFiles struct
+impl DictionaryFiles {
+    // finds the files next to an .ifo file, the index and definitions may be compressed
+    fn find(ifo: &Path) -> Result<Self, ReaderError> {
+        let base = ifo.with_extension("");
+        let find = |extensions: &[&str]| {
+            extensions
+                .iter()
+                .map(|extension| with_suffix(&base, extension))
+                .find(|path| path.is_file())
+        };
+
+        Ok(Self {
+            ifo: ifo.to_path_buf(),
+            idx: find(&[".idx", ".idx.gz"])
+                .ok_or_else(|| ReaderError::NotFound("index file (.idx) not found".to_string()))?,
+            dict: find(&[".dict", ".dict.dz"]).ok_or_else(|| {
+                ReaderError::NotFound("definitions file (.dict) not found".to_string())
+            })?,
+            syn: find(&[".syn"]),
+        })
+    }
+
+    // modification time and size of every file, a changed dictionary is loaded again
+    fn stamp(&self) -> Vec<(SystemTime, u64)> {
+        [
+            Some(&self.ifo),
+            Some(&self.idx),
+            Some(&self.dict),
+            self.syn.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| {
+            (
+                metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                metadata.len(),
+            )
+        })
+        .collect()
+    }
+}
+
+// Headword struct: a word of the index and where its definition is stored
+struct Headword {
+    word: String,
+    offset: u64,
+    size: u32,
+}
+
+// function to read the 16 bit little endian numbers of the dictzip RA subfield
+// the subfield holds a version, the chunk length, the chunk count and the compressed size of each chunk
+fn chunk_table(extra: &[u8]) -> Option<(u64, Vec<usize>)> {
+    let number = |data: &[u8], at: usize| -> Option<usize> {
+        Some(u16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]) as usize)
+    };
+
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let length = number(rest, 2)?;
+        let data = rest.get(4..4 + length)?;
+        if &rest[..2] == b"RA" {
+            let chunk_length = number(data, 2)?;
+            let count = number(data, 4)?;
+            let sizes: Vec<usize> = (0..count).filter_map(|i| number(data, 6 + i * 2)).collect();
+            return (chunk_length > 0 && sizes.len() == count)
+                .then_some((chunk_length as u64, sizes));
+        }
+        rest = &rest[4 + length..];
+    }
+
+    None
+}
+
+// Dictzip struct: a gzip file compressed in chunks which can be read without decompressing all of it
+struct Dictzip {
+    path: PathBuf,
+    chunk_length: u64,         // uncompressed length of every chunk
+    chunks: Vec<(u64, usize)>, // file offset and compressed size of every chunk
+}
+
+// implementations for Dictzip struct
+impl Dictzip {
+    // reads the chunk table from the gzip header, files without one are plain gzip files
+    fn open(path: &Path) -> Result<Option<Self>, ReaderError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if header[..3] != [0x1f, 0x8b, 8] {
+            return Err(ReaderError::InvalidData(
+                "definitions file is not a gzip file".to_string(),
+            ));
+        }
+
+        let flags = header[3];
+        let mut position = header.len() as u64;
+        let mut table = None;
+        if flags & 0x04 != 0 {
+            // extra field, the random access table of dictzip is the RA subfield
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            let mut extra = vec![0; u16::from_le_bytes(length) as usize];
+            reader.read_exact(&mut extra)?;
+            position += 2 + extra.len() as u64;
+            table = chunk_table(&extra);
+        }
+        for flag in [0x08, 0x10] {
+            // file name and comment, both zero terminated
+            if flags & flag != 0 {
+                let mut text = Vec::new();
+                position += reader.read_until(0, &mut text)? as u64;
+            }
+        }
+        if flags & 0x02 != 0 {
+            position += 2; // header crc
+        }
+
+        let Some((chunk_length, sizes)) = table else {
+            return Ok(None);
+        };
+        let chunks = sizes
+            .into_iter()
+            .map(|size| {
+                let chunk = (position, size);
+                position += size as u64;
+                chunk
+            })
+            .collect();
+
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            chunk_length,
+            chunks,
+        }))
+    }
+
+    // reads size bytes at an uncompressed offset, only the chunks holding them are decompressed
+    fn read(&self, offset: u64, size: usize) -> Result<Vec<u8>, ReaderError> {
+        let outside =
+            || ReaderError::InvalidData("definition is outside the definitions file".to_string());
+        let definition_end = offset.checked_add(size as u64).ok_or_else(outside)?;
+        let first = (offset / self.chunk_length) as usize;
+        let last = (definition_end.saturating_sub(1) / self.chunk_length) as usize;
+        let chunks = self
+            .chunks
+            .get(first..=last.max(first))
+            .ok_or_else(outside)?;
+
+        let start = chunks[0].0;
+        let end = chunks
+            .last()
+            .map(|(offset, size)| offset + *size as u64)
+            .unwrap_or(start);
+        let mut compressed = vec![0; (end - start) as usize];
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut compressed)?;
+
+        // chunks end with a full flush, so each one inflates on its own
+        let mut data = Vec::with_capacity(chunks.len() * self.chunk_length as usize);
+        for (chunk_offset, chunk_size) in chunks {
+            let input = &compressed[(chunk_offset - start) as usize..][..*chunk_size];
+            let mut output = Vec::with_capacity(self.chunk_length as usize);
+            Decompress::new(false)
+                .decompress_vec(input, &mut output, FlushDecompress::Sync)
+                .map_err(|e| ReaderError::InvalidData(format!("broken dictzip chunk: {}", e)))?;
+            data.extend_from_slice(&output);
+        }
+
+        let skip = (offset - first as u64 * self.chunk_length) as usize;
+        skip.checked_add(size)
+            .and_then(|end| data.get(skip..end))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(outside)
+    }
+}
+
+// DictData enum: where definitions are read from
+enum DictData {
+    File(PathBuf),    // uncompressed .dict file
+    Dictzip(Dictzip), // .dict.dz with a chunk table
+    Memory(Vec<u8>),  // .dict.dz without a chunk table, decompressed when loaded
+}
+
+// implementations for DictData enum
+impl DictData {
+    // opens the definitions file of a dictionary
+    fn open(path: &Path) -> Result<Self, ReaderError> {
+        if !has_suffix(path, ".dz") {
+            return Ok(Self::File(path.to_path_buf()));
+        }
+
+        match Dictzip::open(path)? {
+            Some(dictzip) => Ok(Self::Dictzip(dictzip)),
+            None => {
+                let mut data = Vec::new();
+                MultiGzDecoder::new(File::open(path)?).read_to_end(&mut data)?;
+                Ok(Self::Memory(data))
+            }
+        }
+    }
+
+    // reads the definition stored at offset
+    fn read(&self, offset: u64, size: u32) -> Result<Vec<u8>, ReaderError> {
+        if size > MAX_DEFINITION_SIZE {
+            return Err(ReaderError::InvalidData(
+                "definition is too large".to_string(),
+            ));
+        }
+
+        let size = size as usize;
+        match self {
+            Self::File(path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                let mut data = vec![0; size];
+                file.read_exact(&mut data)?;
+                Ok(data)
+            }
+            Self::Dictzip(dictzip) => dictzip.read(offset, size),
+            Self::Memory(data) => usize::try_from(offset)
+                .ok()
+                .and_then(|offset| Some(offset..offset.checked_add(size)?))
+                .and_then(|range| data.get(range))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| {
+                    ReaderError::InvalidData(
+                        "definition is outside the definitions file".to_string(),
+                    )
+                }),
+        }
+    }
+}
+
+// Dictionary struct: a loaded stardict dictionary
+struct Dictionary {
+    id: String,
+    ifo: Ifo,
+    headwords: Vec<Headword>,
+    keys: Vec<(String, u32)>, // lowercase index words and synonyms with their headword, sorted
+    data: DictData,
+}
+
+// implementations for Dictionary struct
+impl Dictionary {
+    // loads the index and synonyms of a dictionary, definitions are read when looked up
+    fn load(id: String, files: &DictionaryFiles) -> Result<Self, ReaderError> {
+        let ifo = parse_ifo(&std::fs::read_to_string(&files.ifo)?)?;
+
+        let mut index = Vec::new();
+        if has_suffix(&files.idx, ".gz") {
+            GzDecoder::new(File::open(&files.idx)?).read_to_end(&mut index)?;
+        } else {
+            index = std::fs::read(&files.idx)?;
+        }
+        let headwords = parse_index(&index, ifo.offset_bits)?;
+
+        let mut keys: Vec<(String, u32)> = headwords
+            .iter()
+            .enumerate()
+            .map(|(index, headword)| (fold(&headword.word), index as u32))
+            .collect();
+        if let Some(syn) = &files.syn {
+            keys.extend(parse_synonyms(&std::fs::read(syn)?, headwords.len())?);
+        }
+        keys.sort_unstable();
+        keys.dedup();
+
+        Ok(Self {
+            id,
+            data: DictData::open(&files.dict)?,
+            ifo,
+            headwords,
+            keys,
+        })
+    }
+
+    // information shown in the list of dictionaries
+    fn info(&self) -> DictionaryInfo {
+        DictionaryInfo {
+            id: self.id.clone(),
+            name: self.ifo.name.clone(),
+            word_count: self.ifo.word_count,
+            author: self.ifo.author.clone(),
+            description: self.ifo.description.clone(),
+            error: None,
+        }
+    }
+
+    // headwords of the keys equal to a folded word
+    fn exact(&self, key: &str) -> Vec<u32> {
+        let start = self.keys.partition_point(|(k, _)| k.as_str() < key);
+        self.keys[start..]
+            .iter()
+            .take_while(|(k, _)| k == key)
+            .map(|(_, headword)| *headword)
+            .collect()
+    }
+
+    // headwords of the keys starting with a folded word, shortest keys first
+    fn prefixed(&self, key: &str) -> Vec<u32> {
+        let start = self.keys.partition_point(|(k, _)| k.as_str() < key);
+        let mut candidates: Vec<&(String, u32)> = self.keys[start..]
+            .iter()
+            .take_while(|(k, _)| k.starts_with(key))
+            .filter(|(k, _)| k != key)
+            .take(PREFIX_CANDIDATES)
+            .collect();
+        candidates.sort_by_key(|(k, _)| k.chars().count());
+        candidates
+            .into_iter()
+            .map(|(_, headword)| *headword)
+            .collect()
+    }
+
+    // headwords of the keys at most max_distance edits away from a folded word, closest first
+    fn fuzzy(&self, key: &str, max_distance: usize) -> Vec<u32> {
+        let key: Vec<char> = key.chars().collect();
+        let mut matches: Vec<(usize, u32)> = self
+            .keys
+            .iter()
+            .filter_map(|(k, headword)| {
+                let distance = edit_distance(&key, k, max_distance)?;
+                (distance > 0).then_some((distance, *headword))
+            })
+            .collect();
+        matches.sort_by_key(|(distance, _)| *distance);
+        matches.into_iter().map(|(_, headword)| headword).collect()
+    }
+
+    // reads the definition of a headword as plain text
+    fn definition(&self, headword: &Headword) -> Result<String, ReaderError> {
+        let data = self.data.read(headword.offset, headword.size)?;
+        Ok(definition_text(
+            &data,
+            self.ifo.same_type_sequence.as_deref(),
+        ))
+    }
+}
+
+// CachedDictionary struct: a dictionary loaded earlier, or why it could not be loaded
+#[derive(Clone)]
+struct CachedDictionary {
+    id: String,
+    stamp: Vec<(SystemTime, u64)>,
+    dictionary: Result<Arc<Dictionary>, String>,
+}
+
+// function to get path with a suffix added to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// function to check the end of a file name, ignoring case
+fn has_suffix(path: &Path, suffix: &str) -> bool {
+    path.to_string_lossy().to_lowercase().ends_with(suffix)
+}
+
+// function to parse the key=value lines of an .ifo file
+fn parse_ifo(data: &str) -> Result<Ifo, ReaderError> {
+    let mut lines = data.trim_start_matches('\u{feff}').lines();
+    if lines.next().map(str::trim) != Some(IFO_MAGIC) {
+        return Err(ReaderError::InvalidData(
+            "not a stardict .ifo file".to_string(),
+        ));
+    }
+
+    let fields: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, value)| *key == name && !value.is_empty())
+            .map(|(_, value)| value.to_string())
+    };
+
+    let name = field("bookname")
+        .ok_or_else(|| ReaderError::InvalidData(".ifo file has no bookname".to_string()))?;
+    let word_count = field("wordcount")
+        .and_then(|count| count.parse().ok())
+        .ok_or_else(|| ReaderError::InvalidData(".ifo file has no wordcount".to_string()))?;
+    let offset_bits = match field("idxoffsetbits").as_deref() {
+        None | Some("32") => 32,
+        Some("64") => 64,
+        Some(bits) => {
+            return Err(ReaderError::InvalidData(format!(
+                "unsupported idxoffsetbits {}",
+                bits
+            )))
+        }
+    };
+
+    Ok(Ifo {
+        name,
+        word_count,
+        author: field("author"),
+        // descriptions use <br> for line breaks
+        description: field("description").map(|text| text.replace("<br>", "\n")),
+        offset_bits,
+        same_type_sequence: field("sametypesequence"),
+    })
+}
+
+// function to parse an index, every word is followed by the offset and size of its definition
+fn parse_index(data: &[u8], offset_bits: u32) -> Result<Vec<Headword>, ReaderError> {
+    let broken = || ReaderError::InvalidData("index file is truncated".to_string());
+    let width = if offset_bits == 64 { 8 } else { 4 };
+    let mut headwords = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let end = rest.iter().position(|&byte| byte == 0).ok_or_else(broken)?;
+        let numbers = rest.get(end + 1..end + 1 + width + 4).ok_or_else(broken)?;
+        let offset = numbers[..width]
+            .iter()
+            .fold(0u64, |offset, &byte| offset << 8 | byte as u64);
+        let size = u32::from_be_bytes([
+            numbers[width],
+            numbers[width + 1],
+            numbers[width + 2],
+            numbers[width + 3],
+        ]);
+
+        headwords.push(Headword {
+            word: String::from_utf8_lossy(&rest[..end]).into_owned(),
+            offset,
+            size,
+        });
+        rest = &rest[end + 1 + width + 4..];
+    }
+
+    Ok(headwords)
+}
+
+// function to parse a synonyms file, every word is followed by the number of its index word
+fn parse_synonyms(data: &[u8], headwords: usize) -> Result<Vec<(String, u32)>, ReaderError> {
+    let broken = || ReaderError::InvalidData("synonyms file is truncated".to_string());
+    let mut synonyms = Vec::new();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let end = rest.iter().position(|&byte| byte == 0).ok_or_else(broken)?;
+        let number = rest.get(end + 1..end + 5).ok_or_else(broken)?;
+        let headword = u32::from_be_bytes([number[0], number[1], number[2], number[3]]);
+        if (headword as usize) < headwords {
+            synonyms.push((fold(&String::from_utf8_lossy(&rest[..end])), headword));
+        }
+        rest = &rest[end + 5..];
+    }
+
+    Ok(synonyms)
+}
+
+// function to get the plain text of a definition made of typed fields
+// with a sametypesequence the types are left out and the last field has no terminator or size
+fn definition_text(data: &[u8], same_type_sequence: Option<&str>) -> String {
+    let sequence = same_type_sequence.map(str::as_bytes);
+    let mut parts = Vec::new();
+    let mut rest = data;
+    let mut index = 0;
+
+    while !rest.is_empty() {
+        let kind = match sequence {
+            Some(sequence) => match sequence.get(index) {
+                Some(kind) => *kind,
+                None => break,
+            },
+            None => {
+                let kind = rest[0];
+                rest = &rest[1..];
+                kind
+            }
+        };
+        let last = sequence.is_some_and(|sequence| index + 1 == sequence.len());
+        index += 1;
+
+        // lowercase types are zero terminated text, uppercase types are binary data with a size
+        let field;
+        if last {
+            field = rest;
+            rest = &[];
+        } else if kind.is_ascii_uppercase() {
+            let Some(size) = rest.get(..4) else { break };
+            let size = u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize;
+            let end = (4 + size).min(rest.len());
+            field = &rest[4..end];
+            rest = &rest[end..];
+        } else {
+            let end = rest
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(rest.len());
+            field = &rest[..end];
+            rest = rest.get(end + 1..).unwrap_or_default();
+        }
+
+        if let Some(text) = field_text(kind, field) {
+            parts.push(text);
+        }
+    }
+
+    parts.join("\n")
+}
+
+// function to get the plain text of a definition field, binary fields and resource lists have none
+fn field_text(kind: u8, field: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(field).replace("\r\n", "\n");
+    let text = match kind {
+        b'm' | b'l' | b'y' | b'w' | b'n' => text,
+        b't' => format!("[{}]", text.trim()), // phonetic transcription
+        b'h' => html_to_text(&format!("<div>{}</div>", text)),
+        // pango markup, xdxf and kingsoft xml keep line breaks as written
+        b'g' | b'x' | b'k' => html_to_text(&format!("<div>{}</div>", text.replace('\n', "<br/>"))),
+        _ => return None,
+    };
+
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+// function to fold a word for comparison: lowercase, single spaces and straight apostrophes
+fn fold(word: &str) -> String {
+    word.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace(['\u{2019}', '\u{2018}'], "'")
+        .to_lowercase()
+}
+
+// function to get the word to look up from a selection, punctuation around it is dropped
+fn lookup_key(selection: &str) -> String {
+    fold(selection.trim_matches(|c: char| !c.is_alphanumeric()))
+}
+
+// function to get the edit distance between two words, or none if it is more than max_distance
+fn edit_distance(word: &[char], other: &str, max_distance: usize) -> Option<usize> {
+    let other: Vec<char> = other.chars().collect();
+    if word.len().abs_diff(other.len()) > max_distance {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=other.len()).collect();
+    for (i, a) in word.iter().enumerate() {
+        let mut current = vec![i + 1; other.len() + 1];
+        for (j, b) in other.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|&min| min > max_distance) {
+            return None; // every path is already too far
+        }
+        previous = current;
+    }
+
+    let distance = previous[other.len()];
+    (distance <= max_distance).then_some(distance)
+}
+
+// function to find the .ifo files in the dictionaries folder and its sub folders
+// the id of a dictionary is the path of its .ifo file inside the dictionaries folder
+fn find_dictionaries() -> Result<Vec<(String, PathBuf)>, ReaderError> {
+    let entries = match read_dir(DICTIONARY_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            let Ok(inner) = std::fs::read_dir(&path) else {
+                continue;
+            };
+            for entry in inner.flatten() {
+                let id = format!("{}/{}", name, entry.file_name().to_string_lossy());
+                paths.push((id, entry.path()));
+            }
+        } else {
+            paths.push((name, path));
+        }
+    }
+
+    let mut dictionaries: Vec<(String, PathBuf)> = paths
+        .into_iter()
+        .filter(|(_, path)| path.is_file() && has_suffix(path, ".ifo"))
+        .collect();
+    dictionaries.sort();
+    Ok(dictionaries)
+}
+
+// function to lock the loaded dictionaries
+fn cache() -> MutexGuard<'static, Vec<CachedDictionary>> {
+    static CACHE: OnceLock<Mutex<Vec<CachedDictionary>>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Mutex::new(Vec::new()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// function to load the dictionaries of the dictionaries folder
+// dictionaries are kept loaded and only read again when their files change
+fn load_dictionaries() -> Result<Vec<CachedDictionary>, ReaderError> {
+    let mut cache = cache();
+    let mut loaded = Vec::new();
+
+    for (id, ifo) in find_dictionaries()? {
+        let files = DictionaryFiles::find(&ifo);
+        let stamp = files
+            .as_ref()
+            .map(DictionaryFiles::stamp)
+            .unwrap_or_default();
+        let cached = cache
+            .iter()
+            .find(|cached| cached.id == id && cached.stamp == stamp);
+
+        let dictionary = match cached {
+            Some(cached) => cached.dictionary.clone(),
+            None => files
+                .and_then(|files| Dictionary::load(id.clone(), &files))
+                .map(Arc::new)
+                .map_err(|e| e.to_string()),
+        };
+        loaded.push(CachedDictionary {
+            id,
+            stamp,
+            dictionary,
+        });
+    }
+
+    *cache = loaded.clone();
+    Ok(loaded)
+}
+
+// dictionaries_list command: lists the dictionaries of the dictionaries folder when invoked
+#[tauri::command]
+pub fn dictionaries_list() -> Result<Vec<DictionaryInfo>, ReaderError> {
+    Ok(load_dictionaries()?
+        .into_iter()
+        .map(|cached| match cached.dictionary {
+            Ok(dictionary) => dictionary.info(),
+            Err(error) => DictionaryInfo {
+                name: cached.id.clone(),
+                id: cached.id,
+                word_count: 0,
+                author: None,
+                description: None,
+                error: Some(error),
+            },
+        })
+        .collect())
+}
+
+// dictionary_lookup command: looks up a selected word in all dictionaries when invoked
+// exact matches come first, then words starting with the selection,
+// and only when neither is found, words one or two letters away from it
+#[tauri::command]
+pub fn dictionary_lookup(
+    word: &str,
+    limit: Option<usize>,
+) -> Result<Vec<DictionaryEntry>, ReaderError> {
+    let key = lookup_key(word);
+    if key.is_empty() {
+        return Err(ReaderError::ValidationError(
+            "select a word to look up".to_string(),
+        ));
+    }
+    if key.chars().count() > MAX_WORD_LENGTH {
+        return Err(ReaderError::ValidationError(
+            "selection is too long to look up".to_string(),
+        ));
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let dictionaries: Vec<Arc<Dictionary>> = load_dictionaries()?
+        .into_iter()
+        .filter_map(|cached| cached.dictionary.ok())
+        .collect();
+    if dictionaries.is_empty() {
+        return Err(ReaderError::NotFound(format!(
+            "no dictionaries installed, add stardict files to the {} folder",
+            DICTIONARY_DIR
+        )));
+    }
+
+    // matches of every kind, in the order of the dictionaries
+    let mut matches: Vec<(MatchKind, &Arc<Dictionary>, u32)> = Vec::new();
+    for dictionary in &dictionaries {
+        let exact = dictionary.exact(&key);
+        matches.extend(exact.into_iter().map(|h| (MatchKind::Exact, dictionary, h)));
+    }
+    for dictionary in &dictionaries {
+        let prefixed = dictionary.prefixed(&key);
+        matches.extend(
+            prefixed
+                .into_iter()
+                .map(|h| (MatchKind::Prefix, dictionary, h)),
+        );
+    }
+    if matches.is_empty() && key.chars().count() >= 3 {
+        let max_distance = if key.chars().count() <= 5 { 1 } else { 2 };
+        for dictionary in &dictionaries {
+            let fuzzy = dictionary.fuzzy(&key, max_distance);
+            matches.extend(fuzzy.into_iter().map(|h| (MatchKind::Fuzzy, dictionary, h)));
+        }
+    }
+
+    // a headword reached through several keys (synonyms, case variants) is returned once
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for (match_kind, dictionary, headword) in matches {
+        if entries.len() == limit {
+            break;
+        }
+        if !seen.insert((dictionary.id.as_str(), headword)) {
+            continue;
+        }
+
+        let headword = &dictionary.headwords[headword as usize];
+        // a definition outside a broken definitions file only leaves out its own entry
+        let Ok(definition) = dictionary.definition(headword) else {
+            continue;
+        };
+        entries.push(DictionaryEntry {
+            dictionary_id: dictionary.id.clone(),
+            dictionary: dictionary.ifo.name.clone(),
+            word: headword.word.clone(),
+            definition,
+            match_kind,
+        });
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,459 @@
+/*
+    reader module:
+    document.rs reads plain text, markdown, html, docx and odt documents and packs them as single chapter epubs,
+    so the viewer, positions, highlights and search handle them the same way as epubs
+*/
+
+// importing crates and modules
+use crate::functions::{delete_file, read_bytes, save_bytes};
+use crate::reader::cache::open_book;
+use crate::reader::library::{hash_file, BookFormat};
+use crate::reader::markdown::{render_markdown, Heading};
+use crate::reader::office::{convert_docx, convert_odt};
+use crate::reader::xml::{
+    collapse_whitespace, decode_entities, escape_xml, resolve_path, tokenize, Token,
+};
+use crate::reader::ReaderError;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use regex::Regex;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path};
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// number of bytes looked at while detecting the encoding
+const SNIFF_LENGTH: usize = 4096;
+
+// style of documents made from plain text and markdown
+const DOCUMENT_STYLE: &str = "pre { white-space: pre-wrap; } \
+    table { border-collapse: collapse; } \
+    th, td { border: 1px solid #888; padding: 0.25em 0.5em; } \
+    blockquote { border-left: 3px solid #888; margin-left: 0; padding-left: 1em; } \
+    img { max-width: 100%; }";
+
+// image types a document can link to next to it, only these files of its folder are served
+const LINKED_IMAGE_TYPES: [(&str, &str); 8] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("bmp", "image/bmp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+];
+
+// regex matching charset declarations of html documents, <meta charset> and <meta http-equiv>
+fn charset_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?i)<meta\s[^>]*charset\s*=\s*["']?\s*([\w:.-]+)[^>]*>"#).unwrap()
+    })
+}
+
+// function to detect utf-16 text without a byte order mark from the zero bytes of ascii characters
+fn utf16_without_bom(data: &[u8]) -> Option<&'static Encoding> {
+    let sample = &data[..data.len().min(SNIFF_LENGTH)];
+    if sample.len() < 4 {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let zeros = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 10 > pairs * 4 && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 > pairs * 4 && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+// function to decode a text file: byte order mark, utf-16, utf-8, the charset declared by html, then windows-1252
+pub fn decode_text(data: &[u8], html: bool) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data) {
+        let (text, _) = encoding.decode_without_bom_handling(&data[bom_length..]);
+        return text.into_owned();
+    }
+    if let Some(encoding) = utf16_without_bom(data) {
+        return encoding.decode_without_bom_handling(data).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        return text.to_string();
+    }
+
+    let declared = html
+        .then(|| {
+            let head = String::from_utf8_lossy(&data[..data.len().min(SNIFF_LENGTH)]);
+            let label = charset_regex()
+                .captures(&head)?
+                .get(1)?
+                .as_str()
+                .to_string();
+            Encoding::for_label(label.as_bytes())
+        })
+        .flatten()
+        .filter(|encoding| encoding.is_ascii_compatible());
+    let (text, _) = declared
+        .unwrap_or(WINDOWS_1252)
+        .decode_without_bom_handling(data);
+    text.into_owned()
+}
+
+// function to convert plain text to xhtml, paragraphs are separated by blank lines
+fn text_to_xhtml(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut body = String::new();
+    for paragraph in text.split("\n\n") {
+        let lines: Vec<String> = paragraph
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| escape_xml(line.trim_end()))
+            .collect();
+        if !lines.is_empty() {
+            body.push_str(&format!("<p>{}</p>\n", lines.join("<br/>\n")));
+        }
+    }
+    body
+}
+
+// function to read the title and the headings with ids of an html document
+fn html_outline(source: &str) -> (Option<String>, Vec<Heading>) {
+    let mut title = None;
+    let mut headings = Vec::new();
+    let mut element: Option<(String, Option<String>, String)> = None; // element being read: name, id and text
+
+    for token in tokenize(source) {
+        match token {
+            Token::Tag(tag) => {
+                let read = matches!(tag.name.as_str(), "title" | "h1" | "h2" | "h3");
+                if read && tag.is_open() {
+                    element = Some((tag.name.clone(), tag.attr("id"), String::new()));
+                } else if tag.is_close()
+                    && element.as_ref().is_some_and(|(name, ..)| *name == tag.name)
+                {
+                    let Some((name, id, text)) = element.take() else {
+                        continue;
+                    };
+                    let text = collapse_whitespace(&text);
+                    if text.is_empty() {
+                        continue;
+                    }
+                    match (name.as_str(), id) {
+                        ("title", _) => {
+                            title.get_or_insert(text);
+                        }
+                        (_, Some(id)) => headings.push(Heading {
+                            level: name[1..].parse().unwrap_or(1),
+                            id,
+                            text,
+                        }),
+                        _ => {}
+                    }
+                }
+            }
+            Token::Text(raw) => {
+                if let Some((_, _, text)) = element.as_mut() {
+                    text.push_str(&decode_entities(raw));
+                }
+            }
+        }
+    }
+
+    (title, headings)
+}
+
+// function to wrap a body into an xhtml document
+fn xhtml_document(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\"><head><meta charset=\"utf-8\"/><title>{}</title>\
+        <style>{}</style></head><body>\n{}</body></html>\n",
+        escape_xml(title),
+        DOCUMENT_STYLE,
+        body
+    )
+}
+
+// function to write the ncx of a document, headings are nested by their level
+fn document_ncx(title: &str, document: &str, headings: &[Heading]) -> String {
+    let mut nav_map = String::new();
+    let mut open: Vec<usize> = Vec::new(); // levels of nav points not closed yet
+
+    let fallback = [Heading {
+        level: 1,
+        id: String::new(),
+        text: title.to_string(),
+    }];
+    let headings = if headings.is_empty() {
+        &fallback[..]
+    } else {
+        headings
+    };
+
+    for (index, heading) in headings.iter().enumerate() {
+        while open.last().is_some_and(|level| *level >= heading.level) {
+            nav_map.push_str("</navPoint>");
+            open.pop();
+        }
+        let src = match heading.id.as_str() {
+            "" => document.to_string(),
+            id => format!("{}#{}", document, id),
+        };
+        nav_map.push_str(&format!(
+            "<navPoint id=\"nav{0}\" playOrder=\"{0}\"><navLabel><text>{1}</text></navLabel><content src=\"{2}\"/>",
+            index + 1,
+            escape_xml(&heading.text),
+            escape_xml(&src)
+        ));
+        open.push(heading.level);
+    }
+    nav_map.push_str(&"</navPoint>".repeat(open.len()));
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\
+        <head/><docTitle><text>{}</text></docTitle><navMap>{}</navMap></ncx>\n",
+        escape_xml(title),
+        nav_map
+    )
+}
+
+// function to list the images next to a document that it shows, as paths relative to its folder with their media type
+// links to other folders, absolute paths and urls are left out
+fn linked_images(document: &str) -> Vec<(String, &'static str)> {
+    let mut images: Vec<(String, &'static str)> = Vec::new();
+    for token in tokenize(document) {
+        let Token::Tag(tag) = token else { continue };
+        let source = match tag.name.as_str() {
+            "img" => tag.attr("src"),
+            "image" => tag.attr("href").or_else(|| tag.attr("xlink:href")),
+            _ => None,
+        };
+        let Some(source) = source else { continue };
+        let source = source.split(['#', '?']).next().unwrap_or_default();
+        if source.contains(':')
+            || source.starts_with('/')
+            || source.split('/').any(|part| part == "..")
+        {
+            continue;
+        }
+
+        let path = resolve_path("", source);
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase())
+            .unwrap_or_default();
+        let media_type = LINKED_IMAGE_TYPES
+            .iter()
+            .find(|(image_extension, _)| *image_extension == extension)
+            .map(|(_, media_type)| *media_type);
+        if let Some(media_type) = media_type {
+            if !images.iter().any(|(image, _)| *image == path) {
+                images.push((path, media_type));
+            }
+        }
+    }
+    images
+}
+
+// function to write the package of a document, the uid meta keeps the epub functions from saving a new one
+// images the document links to are listed in the manifest, as they are the only files served from its folder
+fn document_opf(
+    title: Option<&str>,
+    uid: &str,
+    document: &str,
+    media_type: &str,
+    images: &[(String, &str)],
+) -> String {
+    let title = title
+        .map(|title| format!("<dc:title>{}</dc:title>", escape_xml(title)))
+        .unwrap_or_default();
+    let images: String = images
+        .iter()
+        .enumerate()
+        .map(|(index, (path, media_type))| {
+            format!(
+                "<item id=\"image{}\" href=\"{}\" media-type=\"{}\"/>",
+                index + 1,
+                escape_xml(&path.replace('%', "%25")),
+                media_type
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"2.0\" unique-identifier=\"uid\">\
+        <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">{0}<dc:identifier id=\"uid\">{1}</dc:identifier>\
+        <meta name=\"hub.students.adnarayan\" content=\"{1}\"/></metadata>\
+        <manifest><item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\
+        <item id=\"document\" href=\"{2}\" media-type=\"{3}\"/>{4}</manifest>\
+        <spine toc=\"ncx\"><itemref idref=\"document\"/></spine></package>\n",
+        title, uid, document, media_type, images
+    )
+}
+
+// function to generate path of the cached epub of a converted word processor file
+fn converted_path(hash: &str) -> String {
+    format!("documents/{}.epub", hash)
+}
+
+// function to delete the cached conversion of a document
+pub fn clear_document_cache(hash: &str) {
+    let _ = delete_file(&converted_path(hash));
+}
+
+// function to pack a document as an epub with a single chapter
+// the chapter is at the root of the archive, so relative links of the document keep working
+// docx and odt conversions are saved by content hash, as converting a large file takes time
+pub fn document_epub(path: &str, format: BookFormat) -> Result<Vec<u8>, ReaderError> {
+    let hash = hash_file(path)?;
+    if format.is_office() {
+        if let Ok(converted) = read_bytes(&converted_path(&hash)) {
+            return Ok(converted);
+        }
+    }
+
+    let data = std::fs::read(path)?;
+    let uid = hash[..16].to_string(); // same id as the library entry
+    let file_name = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut images = Vec::new();
+    let (title, headings, document, name, media_type) = match format {
+        BookFormat::Text => {
+            let body = text_to_xhtml(&decode_text(&data, false));
+            let document = xhtml_document(&file_name, &body);
+            (
+                None,
+                Vec::new(),
+                document,
+                "index.xhtml",
+                "application/xhtml+xml",
+            )
+        }
+        BookFormat::Markdown => {
+            let (body, headings) = render_markdown(&decode_text(&data, false));
+            let title = headings
+                .iter()
+                .find(|heading| heading.level == 1)
+                .map(|heading| heading.text.clone());
+            let document = xhtml_document(title.as_deref().unwrap_or(&file_name), &body);
+            (
+                title,
+                headings,
+                document,
+                "index.xhtml",
+                "application/xhtml+xml",
+            )
+        }
+        BookFormat::Html => {
+            // the document is stored as utf-8, so its own charset declaration is replaced
+            let source = decode_text(&data, true);
+            let source = charset_regex()
+                .replace_all(&source, "<meta charset=\"utf-8\"/>")
+                .into_owned();
+            let (title, headings) = html_outline(&source);
+            (title, headings, source, "index.html", "text/html")
+        }
+        BookFormat::Docx | BookFormat::Odt => {
+            let converted = match format {
+                BookFormat::Docx => convert_docx(path)?,
+                _ => convert_odt(path)?,
+            };
+            images = converted.images;
+            let title_text = converted.title.as_deref().unwrap_or(&file_name);
+            let document = xhtml_document(title_text, &converted.body);
+            (
+                converted.title,
+                converted.headings,
+                document,
+                "index.xhtml",
+                "application/xhtml+xml",
+            )
+        }
+        BookFormat::Pdf | BookFormat::Epub | BookFormat::Comic => {
+            return Err(ReaderError::InvalidData(format!(
+                "{} is not a text document",
+                path
+            )))
+        }
+    };
+
+    // images of converted office files are inside the epub, other documents link to files next to them
+    let linked = if format.is_office() {
+        Vec::new()
+    } else {
+        linked_images(&document)
+    };
+    let title_text = title.clone().unwrap_or(file_name);
+    let files = [
+        (
+            "META-INF/container.xml",
+            "<?xml version=\"1.0\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\
+            <rootfiles><rootfile full-path=\"content.opf\" media-type=\"application/oebps-package+xml\"/></rootfiles></container>\n"
+                .to_string(),
+        ),
+        (
+            "content.opf",
+            document_opf(title.as_deref(), &uid, name, media_type, &linked),
+        ),
+        ("toc.ncx", document_ncx(&title_text, name, &headings)),
+        (name, document),
+    ];
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    writer.start_file("mimetype", options)?;
+    writer.write_all(b"application/epub+zip")?;
+    for (name, content) in files {
+        writer.start_file(name, options)?;
+        writer.write_all(content.as_bytes())?;
+    }
+    for (name, data) in images {
+        writer.start_file(name, options)?;
+        writer.write_all(&data)?;
+    }
+    let epub = writer.finish()?.into_inner();
+
+    if format.is_office() {
+        save_bytes(&converted_path(&hash), &epub)?;
+    }
+    Ok(epub)
+}
+
+// function to read a file next to a document, e.g. an image linked from markdown notes
+// only images the document shows can be read, which are listed in the manifest of its epub
+pub fn read_document_resource(path: &str, entry: &str) -> Result<Vec<u8>, ReaderError> {
+    let (_, package) = open_book(path)?;
+    let linked = package
+        .item_by_href(entry)
+        .is_some_and(|item| item.id.starts_with("image") && item.media_type.starts_with("image/"));
+    let relative = Path::new(entry);
+    let inside = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !linked || !inside {
+        return Err(ReaderError::NotFound(format!(
+            "{} is not an image shown by the document",
+            entry
+        )));
+    }
+
+    let folder = Path::new(path).parent().unwrap_or(Path::new(""));
+    std::fs::read(folder.join(relative)).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ReaderError::NotFound(format!("{} not found", entry)),
+        _ => e.into(),
+    })
+}
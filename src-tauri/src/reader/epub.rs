@@ -0,0 +1,298 @@
+/*
+    reader module:
+    epub.rs contains functions to locate and parse the package (opf) of an epub file
+*/
+
+// importing crates and modules
+use crate::reader::xml::{
+    collapse_whitespace, decode_entities, parent_dir, resolve_href, resolve_path, tokenize, Token,
+};
+use crate::reader::ReaderError;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+
+// ManifestItem struct (href is resolved to a path inside the archive)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestItem {
+    pub id: String,
+    pub href: String,
+    pub media_type: String,
+    pub properties: Vec<String>,
+}
+
+// SpineItem struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpineItem {
+    pub idref: String,
+    pub linear: bool,
+}
+
+// GuideReference struct (epub 2 <guide> entries)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GuideReference {
+    pub kind: String,
+    pub title: String,
+    pub href: String,
+}
+
+// Metadata struct: dublin core metadata of the book
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+    pub publisher: Option<String>,
+    pub identifier: Option<String>,
+    pub description: Option<String>,
+    pub date: Option<String>,
+    pub subjects: Vec<String>,
+    pub cover_id: Option<String>, // id of the cover image from <meta name="cover" content="...">
+}
+
+// Package struct: parsed content of the opf file
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Package {
+    pub opf_path: String,
+    pub version: String,
+    pub metadata: Metadata,
+    pub manifest: Vec<ManifestItem>,
+    pub spine: Vec<SpineItem>,
+    pub spine_toc: Option<String>, // id of the ncx file referenced by <spine toc="...">
+    pub guide: Vec<GuideReference>,
+}
+
+// implementations for Package struct
+impl Package {
+    // returns the directory containing the opf file, hrefs inside it are relative to this
+    pub fn base_dir(&self) -> &str {
+        parent_dir(&self.opf_path)
+    }
+
+    // returns manifest item with the given id
+    pub fn item_by_id(&self, id: &str) -> Option<&ManifestItem> {
+        self.manifest.iter().find(|item| item.id == id)
+    }
+
+    // returns manifest item with the given archive path
+    pub fn item_by_href(&self, href: &str) -> Option<&ManifestItem> {
+        let path = href.split('#').next().unwrap_or(href);
+        self.manifest.iter().find(|item| item.href == path)
+    }
+
+    // returns manifest items of the spine in reading order
+    pub fn spine_items(&self) -> Vec<&ManifestItem> {
+        self.spine
+            .iter()
+            .filter_map(|spine_item| self.item_by_id(&spine_item.idref))
+            .collect()
+    }
+
+    // returns the cover image declared in the manifest (epub 3 property or epub 2 meta), if any
+    pub fn cover_item(&self) -> Option<&ManifestItem> {
+        self.manifest
+            .iter()
+            .find(|item| item.properties.iter().any(|p| p == "cover-image"))
+            .or_else(|| {
+                self.metadata
+                    .cover_id
+                    .as_deref()
+                    .and_then(|id| self.item_by_id(id))
+            })
+    }
+
+    // returns the epub 3 navigation document, if any
+    pub fn nav_item(&self) -> Option<&ManifestItem> {
+        self.manifest
+            .iter()
+            .find(|item| item.properties.iter().any(|p| p == "nav"))
+    }
+
+    // returns the epub 2 ncx document, if any
+    pub fn ncx_item(&self) -> Option<&ManifestItem> {
+        self.spine_toc
+            .as_deref()
+            .and_then(|id| self.item_by_id(id))
+            .or_else(|| {
+                self.manifest
+                    .iter()
+                    .find(|item| item.media_type == "application/x-dtbncx+xml")
+            })
+    }
+}
+
+// function to open an epub file as a zip archive
+pub fn open_archive(path: &str) -> Result<ZipArchive<File>, ReaderError> {
+    let file = File::open(path)?;
+    Ok(ZipArchive::new(file)?)
+}
+
+// function to read an entry of the archive as a string
+pub fn read_entry_string<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<String, ReaderError> {
+    let bytes = read_entry_bytes(archive, name)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// function to read an entry of the archive as bytes
+pub fn read_entry_bytes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, ReaderError> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|_| ReaderError::NotFound(format!("{} not found in archive", name)))?;
+
+    let mut buffer = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+// function to find path of the opf file from META-INF/container.xml (or the first .opf file as fallback)
+pub fn find_opf_path<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<String, ReaderError> {
+    if let Ok(container) = read_entry_string(archive, "META-INF/container.xml") {
+        for token in tokenize(&container) {
+            if let Token::Tag(tag) = token {
+                if tag.name == "rootfile" && !tag.is_close() {
+                    if let Some(full_path) = tag.attr("full-path") {
+                        return Ok(resolve_path("", &full_path));
+                    }
+                }
+            }
+        }
+    }
+
+    archive
+        .file_names()
+        .find(|name| name.ends_with(".opf"))
+        .map(|name| name.to_string())
+        .ok_or_else(|| ReaderError::NotFound("opf file not found".to_string()))
+}
+
+// function to read and parse the package of an epub archive
+pub fn read_package<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Package, ReaderError> {
+    let opf_path = find_opf_path(archive)?;
+    let opf_data = read_entry_string(archive, &opf_path)?;
+
+    Ok(parse_package(&opf_path, &opf_data))
+}
+
+// function to parse opf data into Package struct
+pub fn parse_package(opf_path: &str, opf_data: &str) -> Package {
+    let base_dir = parent_dir(opf_path);
+    let mut package = Package {
+        opf_path: opf_path.to_string(),
+        version: String::new(),
+        metadata: Metadata::default(),
+        manifest: Vec::new(),
+        spine: Vec::new(),
+        spine_toc: None,
+        guide: Vec::new(),
+    };
+
+    let mut element: Option<(String, String)> = None; // dublin core element being read and its text
+
+    for token in tokenize(opf_data) {
+        let tag = match token {
+            Token::Text(raw) => {
+                if let Some((_, text)) = element.as_mut() {
+                    text.push_str(&decode_entities(raw));
+                }
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        if tag.is_close() {
+            if element.as_ref().is_some_and(|(name, _)| *name == tag.name) {
+                if let Some((name, text)) = element.take() {
+                    add_metadata(&mut package.metadata, &name, collapse_whitespace(&text));
+                }
+            }
+            continue;
+        }
+
+        match tag.name.as_str() {
+            "title" | "creator" | "language" | "publisher" | "identifier" | "description"
+            | "date" | "subject"
+                if tag.is_open() =>
+            {
+                element = Some((tag.name.clone(), String::new()))
+            }
+            "meta" if tag.attr("name").as_deref() == Some("cover") => {
+                package.metadata.cover_id = tag.attr("content")
+            }
+            "package" => package.version = tag.attr("version").unwrap_or_default(),
+            "item" => {
+                if let (Some(id), Some(href)) = (tag.attr("id"), tag.attr("href")) {
+                    package.manifest.push(ManifestItem {
+                        id,
+                        href: resolve_path(base_dir, &href),
+                        media_type: tag.attr("media-type").unwrap_or_default(),
+                        properties: tag
+                            .attr("properties")
+                            .unwrap_or_default()
+                            .split_whitespace()
+                            .map(|p| p.to_string())
+                            .collect(),
+                    });
+                }
+            }
+            "spine" => package.spine_toc = tag.attr("toc"),
+            "itemref" => {
+                if let Some(idref) = tag.attr("idref") {
+                    package.spine.push(SpineItem {
+                        idref,
+                        linear: tag.attr("linear").as_deref() != Some("no"),
+                    });
+                }
+            }
+            "reference" => {
+                if let Some(href) = tag.attr("href") {
+                    package.guide.push(GuideReference {
+                        kind: tag.attr("type").unwrap_or_default(),
+                        title: tag.attr("title").unwrap_or_default(),
+                        href: resolve_href(base_dir, &href),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    package
+}
+
+// function to add a dublin core element to metadata (the first title, language etc. wins)
+fn add_metadata(metadata: &mut Metadata, name: &str, value: String) {
+    if value.is_empty() {
+        return;
+    }
+
+    match name {
+        "title" => {
+            metadata.title.get_or_insert(value);
+        }
+        "creator" => metadata.authors.push(value),
+        "language" => {
+            metadata.language.get_or_insert(value);
+        }
+        "publisher" => {
+            metadata.publisher.get_or_insert(value);
+        }
+        "identifier" => {
+            metadata.identifier.get_or_insert(value);
+        }
+        "description" => {
+            metadata.description.get_or_insert(value);
+        }
+        "date" => {
+            metadata.date.get_or_insert(value);
+        }
+        "subject" => metadata.subjects.push(value),
+        _ => {}
+    }
+}
@@ -0,0 +1,202 @@
+/*
+    reader module:
+    excerpt.rs gets the plain text of an epub chapter or a pdf page range, split in chunks for the study buddy
+*/
+
+// importing crates and modules
+use crate::reader::cache::open_book;
+use crate::reader::epub::read_entry_string;
+use crate::reader::pdf_text::extract_pages;
+use crate::reader::text::html_to_text;
+use crate::reader::toc::{chapter_span, read_toc, spine_chapters};
+use crate::reader::ReaderError;
+use serde::{Deserialize, Serialize};
+
+// maximum number of characters returned if the ui does not set a limit (about 25k tokens)
+const DEFAULT_MAX_CHARS: usize = 100_000;
+
+// size of chunks if the ui does not set one, small enough for the models with 8k context
+const DEFAULT_CHUNK_CHARS: usize = 12_000;
+
+// smallest chunk size accepted, smaller chunks are not useful as llm context
+const MIN_CHUNK_CHARS: usize = 500;
+
+// TextLimits struct: limits set by the ui depending on the model being used
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TextLimits {
+    pub max_chars: Option<usize>,   // text after this is cut off
+    pub chunk_chars: Option<usize>, // maximum length of a chunk
+}
+
+// TextChunk struct: a part of the text which can be sent in a single request
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TextChunk {
+    pub index: usize,
+    pub text: String,
+    pub chars: usize,
+}
+
+// Excerpt struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Excerpt {
+    pub title: Option<String>, // chapter title for epubs
+    pub text: String,
+    pub chars: usize,       // length of text in characters
+    pub total_chars: usize, // length before cutting off at the limit
+    pub truncated: bool,
+    pub chunks: Vec<TextChunk>,
+}
+
+// function to find the byte index of the nth character, or the text length if it is shorter
+fn char_index(text: &str, n: usize) -> usize {
+    text.char_indices()
+        .nth(n)
+        .map(|(index, _)| index)
+        .unwrap_or(text.len())
+}
+
+// function to find where to cut a text at most max_chars long
+// prefers the end of a paragraph, then the end of a sentence, then a space, within the last quarter
+fn cut_index(text: &str, max_chars: usize) -> usize {
+    let limit = char_index(text, max_chars);
+    if limit == text.len() {
+        return limit;
+    }
+
+    let min = char_index(text, max_chars - max_chars / 4);
+    let window = &text[min..limit];
+    let found = window
+        .rfind('\n')
+        .map(|index| index + 1)
+        .or_else(|| window.rfind(". ").map(|index| index + 2))
+        .or_else(|| window.rfind(' ').map(|index| index + 1));
+
+    match found {
+        Some(index) => min + index,
+        None => limit, // no break in the window: cut inside the word
+    }
+}
+
+// function to split a text into chunks of at most chunk_chars characters
+pub fn split_chunks(text: &str, chunk_chars: usize) -> Vec<TextChunk> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while !rest.trim().is_empty() {
+        let end = cut_index(rest, chunk_chars);
+        let chunk = rest[..end].trim();
+        if !chunk.is_empty() {
+            chunks.push(TextChunk {
+                index: chunks.len(),
+                text: chunk.to_string(),
+                chars: chunk.chars().count(),
+            });
+        }
+        rest = &rest[end..];
+    }
+
+    chunks
+}
+
+// function to apply the limits to a text
+fn excerpt(title: Option<String>, text: String, limits: Option<TextLimits>) -> Excerpt {
+    let limits = limits.unwrap_or_default();
+    let max_chars = limits.max_chars.unwrap_or(DEFAULT_MAX_CHARS).max(1);
+    let chunk_chars = limits
+        .chunk_chars
+        .unwrap_or(DEFAULT_CHUNK_CHARS)
+        .clamp(MIN_CHUNK_CHARS, max_chars.max(MIN_CHUNK_CHARS));
+
+    let total_chars = text.chars().count();
+    let truncated = total_chars > max_chars;
+    let text = if truncated {
+        text[..cut_index(&text, max_chars)].trim_end().to_string()
+    } else {
+        text
+    };
+
+    Excerpt {
+        title,
+        chars: text.chars().count(),
+        total_chars,
+        truncated,
+        chunks: split_chunks(&text, chunk_chars),
+        text,
+    }
+}
+
+// e_pub_chapter_text command: returns the text of the chapter containing a spine item when invoked
+#[tauri::command]
+pub fn e_pub_chapter_text(
+    path: &str,
+    spine_index: usize,
+    limits: Option<TextLimits>,
+) -> Result<Excerpt, ReaderError> {
+    let (mut archive, package) = open_book(path)?;
+    if spine_index >= package.spine.len() {
+        return Err(ReaderError::NotFound(format!(
+            "spine item {} in {}",
+            spine_index, path
+        )));
+    }
+
+    // without a toc every spine document is a chapter of its own
+    let (title, span) = match read_toc(&mut archive, &package) {
+        Ok(toc) => (
+            spine_chapters(&package, &toc)
+                .get(spine_index)
+                .cloned()
+                .flatten(),
+            chapter_span(&package, &toc, spine_index),
+        ),
+        Err(_) => (None, spine_index..spine_index + 1),
+    };
+
+    let mut documents = Vec::new();
+    for spine_item in &package.spine[span] {
+        let Some(item) = package.item_by_id(&spine_item.idref) else {
+            continue;
+        };
+        let Ok(data) = read_entry_string(&mut archive, &item.href) else {
+            continue;
+        };
+        let text = html_to_text(&data);
+        if !text.is_empty() {
+            documents.push(text);
+        }
+    }
+
+    Ok(excerpt(title, documents.join("\n"), limits))
+}
+
+// pdf_pages_text command: returns the text of pdf pages in the given range (1 based, inclusive) when invoked
+#[tauri::command]
+pub fn pdf_pages_text(
+    path: &str,
+    first_page: u32,
+    last_page: u32,
+    limits: Option<TextLimits>,
+) -> Result<Excerpt, ReaderError> {
+    if first_page == 0 || last_page < first_page {
+        return Err(ReaderError::ValidationError(format!(
+            "invalid page range {}-{}",
+            first_page, last_page
+        )));
+    }
+
+    let pages = extract_pages(path, Some(first_page), Some(last_page))?;
+    if pages.is_empty() {
+        return Err(ReaderError::NotFound(format!(
+            "page {} in {}",
+            first_page, path
+        )));
+    }
+
+    let text = pages
+        .into_iter()
+        .map(|page| page.text)
+        .filter(|text| !text.trim().is_empty())
+        .collect::<Vec<String>>()
+        .join("\n");
+    Ok(excerpt(None, text, limits))
+}
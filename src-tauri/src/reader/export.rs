@@ -0,0 +1,530 @@
+/*
+    reader module:
+    export.rs exports highlights, notes and bookmarks of books to markdown, html, csv, json or anki import files
+*/
+
+// importing crates and modules
+use crate::reader::bookmarks::{load_bookmarks, Bookmark};
+use crate::reader::cache::open_book;
+use crate::reader::highlights::{highlights_key, load_highlights, Highlight, HighlightRange};
+use crate::reader::library::{Book, BookFormat, Library};
+use crate::reader::position::{cfi_spine_index, Locator};
+use crate::reader::toc::{read_toc, spine_chapters};
+use crate::reader::ReaderError;
+use serde::{Deserialize, Serialize};
+use tauri::WebviewWindow;
+use tauri_plugin_dialog::DialogExt;
+
+// formats highlights can be exported to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Csv,
+    Json,
+    Anki, // tab separated notes for anki's "import file", one card per highlight with a note
+}
+
+// implementations for ExportFormat enum
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Anki => "txt",
+        }
+    }
+
+    fn filter_name(self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::Html => "HTML",
+            Self::Csv => "CSV",
+            Self::Json => "JSON",
+            Self::Anki => "Anki notes",
+        }
+    }
+}
+
+// ExportedHighlight struct: a highlight with the chapter or page it belongs to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportedHighlight {
+    pub context: Option<String>, // chapter title for epubs, page for pdfs
+    #[serde(flatten)]
+    pub highlight: Highlight,
+}
+
+// ExportedBookmark struct: a bookmark with the chapter or page it belongs to
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportedBookmark {
+    pub context: Option<String>,
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+}
+
+// ExportedBook struct: highlights and bookmarks of a book in reading order
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportedBook {
+    pub book_id: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub format: BookFormat,
+    pub highlights: Vec<ExportedHighlight>,
+    pub bookmarks: Vec<ExportedBookmark>,
+}
+
+// function to find the chapter title of every spine item of an epub file
+fn e_pub_chapters(path: &str) -> Result<Vec<Option<String>>, ReaderError> {
+    let (mut archive, package) = open_book(path)?;
+    let toc = read_toc(&mut archive, &package)?;
+
+    Ok(spine_chapters(&package, &toc))
+}
+
+// function to collect highlights and bookmarks of a library book with their context, in reading order
+pub fn export_book(book: &Book) -> Result<ExportedBook, ReaderError> {
+    let mut highlights = load_highlights(highlights_key(book))?;
+    let mut bookmarks = load_bookmarks(&book.id)?;
+
+    // chapters are only a nice to have, the export should work even if the file was moved
+    let chapters = match book.format {
+        BookFormat::Pdf | BookFormat::Comic => Vec::new(),
+        _ => e_pub_chapters(&book.path).unwrap_or_default(),
+    };
+
+    // sort by spine position for epubs and by page and top of the first rect for pdfs
+    highlights.sort_by(|a, b| {
+        sort_key(&a.range)
+            .partial_cmp(&sort_key(&b.range))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.created_at.cmp(&b.created_at))
+    });
+    bookmarks.sort_by(|a, b| {
+        locator_sort_key(&a.locator)
+            .partial_cmp(&locator_sort_key(&b.locator))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.created_at.cmp(&b.created_at))
+    });
+
+    let highlights = highlights
+        .into_iter()
+        .map(|highlight| {
+            let context = match &highlight.range {
+                HighlightRange::Epub { cfi_range } => cfi_spine_index(cfi_range)
+                    .and_then(|index| chapters.get(index).cloned().flatten()),
+                HighlightRange::Pdf { page, .. } => Some(format!("Page {}", page)),
+            };
+            ExportedHighlight { context, highlight }
+        })
+        .collect();
+
+    let bookmarks = bookmarks
+        .into_iter()
+        .map(|bookmark| {
+            let context = match &bookmark.locator {
+                Locator::Epub { cfi, .. } => {
+                    cfi_spine_index(cfi).and_then(|index| chapters.get(index).cloned().flatten())
+                }
+                Locator::Pdf { page, .. } | Locator::Comic { page, .. } => {
+                    Some(format!("Page {}", page))
+                }
+            };
+            ExportedBookmark { context, bookmark }
+        })
+        .collect();
+
+    Ok(ExportedBook {
+        book_id: book.id.clone(),
+        title: book.metadata.title.clone(),
+        authors: book.metadata.authors.clone(),
+        format: book.format,
+        highlights,
+        bookmarks,
+    })
+}
+
+// function to get the position of a highlight used for sorting
+fn sort_key(range: &HighlightRange) -> (usize, f64) {
+    match range {
+        HighlightRange::Epub { cfi_range } => {
+            (cfi_spine_index(cfi_range).unwrap_or(usize::MAX), 0.0)
+        }
+        HighlightRange::Pdf { page, rects } => (
+            *page as usize,
+            rects.first().map(|rect| rect.y).unwrap_or_default(),
+        ),
+    }
+}
+
+// function to get the position of a bookmark used for sorting
+fn locator_sort_key(locator: &Locator) -> (usize, f64) {
+    match locator {
+        Locator::Epub { cfi, .. } => (cfi_spine_index(cfi).unwrap_or(usize::MAX), 0.0),
+        Locator::Pdf {
+            page,
+            scroll_offset,
+            ..
+        } => (*page as usize, *scroll_offset),
+        Locator::Comic { page, .. } => (*page as usize, 0.0),
+    }
+}
+
+// function to convert exported books to the text of the chosen format
+pub fn render_export(books: &[ExportedBook], format: ExportFormat) -> Result<String, ReaderError> {
+    Ok(match format {
+        ExportFormat::Markdown => render_markdown(books),
+        ExportFormat::Html => render_html(books),
+        ExportFormat::Csv => render_csv(books),
+        ExportFormat::Json => serde_json::to_string_pretty(books)?,
+        ExportFormat::Anki => render_anki(books),
+    })
+}
+
+// function to render highlights as markdown, grouped by book and chapter
+fn render_markdown(books: &[ExportedBook]) -> String {
+    let mut markdown = String::new();
+
+    for book in books {
+        markdown.push_str(&format!("# {}\n\n", book.title));
+        if !book.authors.is_empty() {
+            markdown.push_str(&format!("*{}*\n\n", book.authors.join(", ")));
+        }
+
+        let mut context: Option<&str> = None;
+        for exported in &book.highlights {
+            if exported.context.is_some() && exported.context.as_deref() != context {
+                context = exported.context.as_deref();
+                markdown.push_str(&format!("## {}\n\n", context.unwrap_or_default()));
+            }
+
+            let highlight = &exported.highlight;
+            for line in highlight.text.lines() {
+                markdown.push_str(&format!("> {}\n", line));
+            }
+            if highlight.text.is_empty() {
+                markdown.push_str(">\n");
+            }
+            markdown.push_str(&format!("\nColor: {}\n", highlight.color));
+            if let Some(note) = &highlight.note {
+                markdown.push_str(&format!("\nNote: {}\n", note));
+            }
+            if !highlight.tags.is_empty() {
+                let tags: Vec<String> = highlight
+                    .tags
+                    .iter()
+                    .map(|tag| format!("#{}", tag.replace(' ', "-")))
+                    .collect();
+                markdown.push_str(&format!("\nTags: {}\n", tags.join(" ")));
+            }
+            markdown.push('\n');
+        }
+
+        if !book.bookmarks.is_empty() {
+            markdown.push_str("## Bookmarks\n\n");
+            for exported in &book.bookmarks {
+                let bookmark = &exported.bookmark;
+                markdown.push_str(&format!("- **{}**", bookmark.label));
+                if let Some(context) = &exported.context {
+                    markdown.push_str(&format!(" ({})", context));
+                }
+                if let Some(snippet) = &bookmark.snippet {
+                    markdown.push_str(&format!(": {}", snippet));
+                }
+                markdown.push('\n');
+            }
+            markdown.push('\n');
+        }
+    }
+
+    markdown
+}
+
+// function to escape text for html
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// function to render highlights as a standalone html document
+fn render_html(books: &[ExportedBook]) -> String {
+    let mut body = String::new();
+
+    for book in books {
+        body.push_str(&format!(
+            "<section>\n<h1>{}</h1>\n",
+            escape_html(&book.title)
+        ));
+        if !book.authors.is_empty() {
+            body.push_str(&format!(
+                "<p class=\"authors\">{}</p>\n",
+                escape_html(&book.authors.join(", "))
+            ));
+        }
+
+        let mut context: Option<&str> = None;
+        for exported in &book.highlights {
+            if exported.context.is_some() && exported.context.as_deref() != context {
+                context = exported.context.as_deref();
+                body.push_str(&format!(
+                    "<h2>{}</h2>\n",
+                    escape_html(context.unwrap_or_default())
+                ));
+            }
+
+            // colors are validated when saving highlights, so they are safe to use in style
+            let highlight = &exported.highlight;
+            body.push_str(&format!(
+                "<blockquote style=\"border-color: {}\">{}</blockquote>\n",
+                escape_html(&highlight.color),
+                escape_html(&highlight.text).replace('\n', "<br>")
+            ));
+            if let Some(note) = &highlight.note {
+                body.push_str(&format!(
+                    "<p class=\"note\">{}</p>\n",
+                    escape_html(note).replace('\n', "<br>")
+                ));
+            }
+            if !highlight.tags.is_empty() {
+                let tags: Vec<String> = highlight
+                    .tags
+                    .iter()
+                    .map(|tag| format!("<span class=\"tag\">{}</span>", escape_html(tag)))
+                    .collect();
+                body.push_str(&format!("<p class=\"tags\">{}</p>\n", tags.join(" ")));
+            }
+        }
+
+        if !book.bookmarks.is_empty() {
+            body.push_str("<h2>Bookmarks</h2>\n<ul class=\"bookmarks\">\n");
+            for exported in &book.bookmarks {
+                let bookmark = &exported.bookmark;
+                body.push_str(&format!(
+                    "<li><strong>{}</strong>",
+                    escape_html(&bookmark.label)
+                ));
+                if let Some(context) = &exported.context {
+                    body.push_str(&format!(" ({})", escape_html(context)));
+                }
+                if let Some(snippet) = &bookmark.snippet {
+                    body.push_str(&format!(
+                        "<br><span class=\"snippet\">{}</span>",
+                        escape_html(snippet)
+                    ));
+                }
+                body.push_str("</li>\n");
+            }
+            body.push_str("</ul>\n");
+        }
+        body.push_str("</section>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Highlights</title>
+<style>
+body {{ font-family: sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; }}
+blockquote {{ margin: 1rem 0 0.25rem; padding: 0.25rem 1rem; border-left: 0.3rem solid; }}
+.authors {{ font-style: italic; }}
+.note {{ margin: 0 1.3rem; color: #555; }}
+.tag {{ font-size: 0.8rem; padding: 0.1rem 0.4rem; border-radius: 0.3rem; background: #eee; }}
+.tags {{ margin: 0.25rem 1.3rem; }}
+.bookmarks li {{ margin: 0.5rem 0; }}
+.snippet {{ color: #555; }}
+</style>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        body
+    )
+}
+
+// function to escape a csv field
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// function to render highlights and bookmarks as csv with one per row (bookmarks have their snippet as text)
+fn render_csv(books: &[ExportedBook]) -> String {
+    let mut csv =
+        String::from("type,book,authors,chapter,page,label,text,note,color,tags,created_at\n");
+    let mut push_row = |row: [String; 11]| {
+        let row: Vec<String> = row.iter().map(|field| escape_csv(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    };
+
+    for book in books {
+        for exported in &book.highlights {
+            let highlight = &exported.highlight;
+            let (chapter, page) = match &highlight.range {
+                HighlightRange::Epub { .. } => (exported.context.clone(), None),
+                HighlightRange::Pdf { page, .. } => (None, Some(page.to_string())),
+            };
+
+            push_row([
+                "highlight".to_string(),
+                book.title.clone(),
+                book.authors.join("; "),
+                chapter.unwrap_or_default(),
+                page.unwrap_or_default(),
+                String::new(),
+                highlight.text.clone(),
+                highlight.note.clone().unwrap_or_default(),
+                highlight.color.clone(),
+                highlight.tags.join("; "),
+                highlight.created_at.to_string(),
+            ]);
+        }
+
+        for exported in &book.bookmarks {
+            let bookmark = &exported.bookmark;
+            let (chapter, page) = match &bookmark.locator {
+                Locator::Epub { .. } => (exported.context.clone(), None),
+                Locator::Pdf { page, .. } | Locator::Comic { page, .. } => {
+                    (None, Some(page.to_string()))
+                }
+            };
+
+            push_row([
+                "bookmark".to_string(),
+                book.title.clone(),
+                book.authors.join("; "),
+                chapter.unwrap_or_default(),
+                page.unwrap_or_default(),
+                bookmark.label.clone(),
+                bookmark.snippet.clone().unwrap_or_default(),
+                String::new(),
+                String::new(),
+                String::new(),
+                bookmark.created_at.to_string(),
+            ]);
+        }
+    }
+
+    csv
+}
+
+// function to convert text to an anki field (html is enabled, tabs would split the field)
+fn anki_field(text: &str) -> String {
+    escape_html(text)
+        .replace(['\t', '\r'], " ")
+        .replace('\n', "<br>")
+}
+
+// function to convert text to an anki tag (tags are separated by spaces)
+fn anki_tag(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join("_")
+}
+
+// function to render highlights with notes as an anki import file (.apkg would need an sqlite database)
+// front: highlighted text, back: note with book and chapter, tagged with book title and highlight tags
+fn render_anki(books: &[ExportedBook]) -> String {
+    let mut notes = String::from(
+        "#separator:tab\n#html:true\n#notetype:Basic\n#tags column:3\n#deck column:4\n",
+    );
+
+    for book in books {
+        // deck names are not html, and :: would create a sub deck
+        let deck = book
+            .title
+            .replace(['\t', '\r', '\n'], " ")
+            .replace("::", ":");
+        let book_tag = anki_tag(&book.title);
+
+        for exported in &book.highlights {
+            let highlight = &exported.highlight;
+            let Some(note) = highlight.note.as_deref() else {
+                continue; // a card needs something on its back
+            };
+            if highlight.text.trim().is_empty() {
+                continue;
+            }
+
+            let source = match &exported.context {
+                Some(context) => format!("{} - {}", book.title, context),
+                None => book.title.clone(),
+            };
+            let back = format!(
+                "{}<br><br><small>{}</small>",
+                anki_field(note),
+                anki_field(&source)
+            );
+
+            let mut tags = vec![book_tag.clone()];
+            tags.extend(highlight.tags.iter().map(|tag| anki_tag(tag)));
+
+            notes.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                anki_field(&highlight.text),
+                back,
+                tags.join(" "),
+                deck
+            ));
+        }
+    }
+
+    notes
+}
+
+// highlights_export command: exports highlights of a book (or of all library books if no id is given)
+// to a file chosen in a save dialog, returns the saved path or None if cancelled when invoked
+#[tauri::command]
+pub fn highlights_export(
+    window: WebviewWindow,
+    book_id: Option<String>,
+    format: ExportFormat,
+) -> Result<Option<String>, ReaderError> {
+    let library = Library::load()?;
+
+    let books = match &book_id {
+        Some(id) => {
+            let book = library
+                .book(id)
+                .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", id)))?;
+            vec![export_book(book)?]
+        }
+        None => library
+            .books
+            .iter()
+            .map(export_book)
+            .filter(|exported| {
+                !matches!(exported, Ok(book) if book.highlights.is_empty() && book.bookmarks.is_empty())
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let file_name = match books.as_slice() {
+        [book] if book_id.is_some() => format!("{} highlights", book.title),
+        _ => "highlights".to_string(),
+    };
+
+    let Some(path) = window
+        .dialog()
+        .file()
+        .add_filter(format.filter_name(), &[format.extension()])
+        .set_file_name(format!("{}.{}", file_name, format.extension()))
+        .blocking_save_file()
+    else {
+        return Ok(None); // dialog was cancelled
+    };
+    let path = path
+        .into_path()
+        .map_err(|_| ReaderError::InvalidData("save location is not a file path".to_string()))?;
+
+    std::fs::write(&path, render_export(&books, format)?)?;
+    Ok(Some(path.to_string_lossy().to_string()))
+}
@@ -0,0 +1,557 @@
+/*
+    reader module:
+    highlights.rs declares the highlight model and commands to store highlights of books
+*/
+
+// importing crates and modules
+use crate::functions::{current_time_ms, delete_file, read_data};
+use crate::reader::library::{is_book_id, Book, BookFormat, Library};
+use crate::reader::{save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+
+// limits used while validating highlights
+const MAX_TEXT_LENGTH: usize = 20_000;
+const MAX_NOTE_LENGTH: usize = 20_000;
+const MAX_TAGS: usize = 32;
+const MAX_TAG_LENGTH: usize = 64;
+
+// named colors accepted besides hex colors
+const NAMED_COLORS: [&str; 8] = [
+    "yellow", "green", "blue", "red", "magenta", "orange", "purple", "pink",
+];
+
+// HighlightRange enum: the highlighted range, depending on the book format
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "format")]
+pub enum HighlightRange {
+    Epub {
+        cfi_range: String,
+    },
+    Pdf {
+        page: u32,        // 1 based page number
+        rects: Vec<Rect>, // rectangles of the selected text layer spans
+    },
+}
+
+// Rect struct: a rectangle on a pdf page, relative to the page size (0 - 1) so it does not depend on zoom
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// Highlight struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Highlight {
+    pub id: String,
+    #[serde(flatten)]
+    pub range: HighlightRange,
+    pub text: String,
+    pub color: String,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+    pub created_at: u128,
+    pub updated_at: u128,
+}
+
+// NewHighlight struct: highlight data sent by the ui (cfiRange is accepted as the epub viewer names it so)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewHighlight {
+    #[serde(alias = "cfiRange")]
+    pub cfi_range: String,
+    #[serde(default)]
+    pub text: String,
+    pub color: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// NewPdfHighlight struct: pdf highlight data sent by the ui
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewPdfHighlight {
+    pub page: u32,
+    pub rects: Vec<Rect>,
+    #[serde(default)]
+    pub text: String,
+    pub color: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// PdfData struct: data needed by the pdf viewer to show highlights
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PdfData {
+    pub hash: String,
+    pub title: String,
+    pub highlights: Vec<Highlight>,
+}
+
+// HighlightUpdate struct: fields of a highlight which can be changed, missing fields are kept as they are
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HighlightUpdate {
+    pub color: Option<String>,
+    pub note: Option<String>, // an empty note removes the note
+    pub tags: Option<Vec<String>>,
+}
+
+// highlights saved by older versions of the app: [{ color, cfiRange }]
+#[derive(Deserialize)]
+struct LegacyHighlight {
+    color: String,
+    #[serde(rename = "cfiRange")]
+    cfi_range: String,
+}
+
+// implementations for Highlight struct
+impl Highlight {
+    // checks that all fields are within limits
+    pub fn validate(&self) -> Result<(), ReaderError> {
+        match &self.range {
+            HighlightRange::Epub { cfi_range } => validate_cfi(cfi_range)?,
+            HighlightRange::Pdf { page, rects } => validate_pdf_range(*page, rects)?,
+        }
+        validate_color(&self.color)?;
+
+        if self.text.chars().count() > MAX_TEXT_LENGTH {
+            return Err(ReaderError::ValidationError(format!(
+                "highlighted text is longer than {} characters",
+                MAX_TEXT_LENGTH
+            )));
+        }
+        if self
+            .note
+            .as_ref()
+            .is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH)
+        {
+            return Err(ReaderError::ValidationError(format!(
+                "note is longer than {} characters",
+                MAX_NOTE_LENGTH
+            )));
+        }
+        validate_tags(&self.tags)
+    }
+
+    // applies an update to the highlight
+    fn apply(&mut self, update: HighlightUpdate) {
+        if let Some(color) = update.color {
+            self.color = color;
+        }
+        if let Some(note) = update.note {
+            self.note = Some(note).filter(|note| !note.trim().is_empty());
+        }
+        if let Some(tags) = update.tags {
+            self.tags = tags;
+        }
+        self.updated_at = current_time_ms();
+    }
+}
+
+// function to validate an epub cfi range
+fn validate_cfi(cfi: &str) -> Result<(), ReaderError> {
+    if cfi.starts_with("epubcfi(") && cfi.ends_with(')') {
+        Ok(())
+    } else {
+        Err(ReaderError::ValidationError(format!(
+            "{} is not an epub cfi",
+            cfi
+        )))
+    }
+}
+
+// function to validate page and rectangles of a pdf highlight
+fn validate_pdf_range(page: u32, rects: &[Rect]) -> Result<(), ReaderError> {
+    if page == 0 {
+        return Err(ReaderError::ValidationError(
+            "page numbers start from 1".to_string(),
+        ));
+    }
+    if rects.is_empty() {
+        return Err(ReaderError::ValidationError(
+            "highlight has no rectangles".to_string(),
+        ));
+    }
+
+    // allow a little overflow, text layer spans may slightly cross the page border
+    let in_page = |value: f64| value.is_finite() && (-0.05..=1.05).contains(&value);
+    let valid = rects.iter().all(|rect| {
+        in_page(rect.x)
+            && in_page(rect.y)
+            && in_page(rect.x + rect.width)
+            && in_page(rect.y + rect.height)
+            && rect.width >= 0.0
+            && rect.height >= 0.0
+    });
+    if !valid {
+        return Err(ReaderError::ValidationError(
+            "highlight rectangles must be relative to the page size".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// function to validate a color (#rgb, #rrggbb, #rrggbbaa or a named color)
+pub fn validate_color(color: &str) -> Result<(), ReaderError> {
+    let is_hex = color.strip_prefix('#').is_some_and(|hex| {
+        [3, 6, 8].contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+
+    if is_hex || NAMED_COLORS.contains(&color) {
+        Ok(())
+    } else {
+        Err(ReaderError::ValidationError(format!(
+            "{} is not a valid color",
+            color
+        )))
+    }
+}
+
+// function to validate tags of a highlight
+pub fn validate_tags(tags: &[String]) -> Result<(), ReaderError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ReaderError::ValidationError(format!(
+            "a highlight can have at most {} tags",
+            MAX_TAGS
+        )));
+    }
+    if let Some(tag) = tags
+        .iter()
+        .find(|tag| tag.trim().is_empty() || tag.chars().count() > MAX_TAG_LENGTH)
+    {
+        return Err(ReaderError::ValidationError(format!(
+            "\"{}\" is not a valid tag",
+            tag
+        )));
+    }
+    Ok(())
+}
+
+// function to generate an id not used by the given highlights
+pub fn new_highlight_id<'a>(existing: impl Iterator<Item = &'a Highlight>) -> String {
+    let used: Vec<&str> = existing.map(|highlight| highlight.id.as_str()).collect();
+    let mut id = current_time_ms();
+    while used.contains(&id.to_string().as_str()) {
+        id += 1;
+    }
+    id.to_string()
+}
+
+// function to get the key highlights of a library book are saved with (uid for epubs, content hash for pdfs)
+pub fn highlights_key(book: &Book) -> &str {
+    match book.format {
+        BookFormat::Pdf | BookFormat::Comic => &book.hash,
+        BookFormat::Epub
+        | BookFormat::Text
+        | BookFormat::Markdown
+        | BookFormat::Html
+        | BookFormat::Docx
+        | BookFormat::Odt => &book.id,
+    }
+}
+
+// function to generate path of the highlights file of a book (epub uid or pdf content hash)
+fn highlights_path(key: &str) -> String {
+    format!("highlights/{}.json", key)
+}
+
+// function to check a highlights key sent by the webview before it is used in a path, keys are hex ids or hashes
+fn validate_key(key: &str) -> Result<(), ReaderError> {
+    if !is_book_id(key) {
+        return Err(ReaderError::ValidationError(format!(
+            "{} is not a book id or hash",
+            key
+        )));
+    }
+    Ok(())
+}
+
+// function to load highlights of a book (highlights saved by older versions are converted)
+pub fn load_highlights(key: &str) -> Result<Vec<Highlight>, ReaderError> {
+    let data = match read_data(&highlights_path(key)) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    match serde_json::from_str::<Vec<Highlight>>(&data) {
+        Ok(highlights) => Ok(highlights),
+        Err(e) => {
+            let legacy: Vec<Option<LegacyHighlight>> =
+                serde_json::from_str(&data).map_err(|_| ReaderError::SerdeJsonError(e))?;
+
+            let mut highlights: Vec<Highlight> = Vec::new();
+            for old in legacy.into_iter().flatten() {
+                let now = current_time_ms();
+                highlights.push(Highlight {
+                    id: new_highlight_id(highlights.iter()),
+                    range: HighlightRange::Epub {
+                        cfi_range: old.cfi_range,
+                    },
+                    text: String::new(),
+                    color: old.color,
+                    note: None,
+                    tags: Vec::new(),
+                    created_at: now,
+                    updated_at: now,
+                });
+            }
+            Ok(highlights)
+        }
+    }
+}
+
+// function to save highlights of a book
+pub fn save_highlights(key: &str, highlights: &[Highlight]) -> Result<(), ReaderError> {
+    save_json(&highlights_path(key), highlights)
+}
+
+// function to move highlights of a book to its new key, e.g. when the content of a pdf changed
+pub fn move_highlights(old_key: &str, new_key: &str) -> Result<(), ReaderError> {
+    let highlights = load_highlights(old_key)?;
+    if !highlights.is_empty() {
+        save_highlights(new_key, &highlights)?;
+        delete_file(&highlights_path(old_key))?;
+    }
+    Ok(())
+}
+
+// function to delete the highlights of a book removed from the library
+pub fn remove_highlights(key: &str) -> Result<(), ReaderError> {
+    match delete_file(&highlights_path(key)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+// function to create a highlight from data sent by the ui
+fn create_highlight(
+    range: HighlightRange,
+    text: String,
+    color: String,
+    note: Option<String>,
+    tags: Vec<String>,
+    id: String,
+) -> Result<Highlight, ReaderError> {
+    let now = current_time_ms();
+    let highlight = Highlight {
+        id,
+        range,
+        text,
+        color,
+        note: note.filter(|note| !note.trim().is_empty()),
+        tags,
+        created_at: now,
+        updated_at: now,
+    };
+
+    highlight.validate()?;
+    Ok(highlight)
+}
+
+// function to add a validated highlight to the highlights file with the given key
+fn add_highlight(key: &str, highlight: Highlight) -> Result<Highlight, ReaderError> {
+    let mut highlights = load_highlights(key)?;
+    if highlights
+        .iter()
+        .any(|saved| saved.range == highlight.range)
+    {
+        return Err(ReaderError::ValidationError(
+            "range is already highlighted".to_string(),
+        ));
+    }
+
+    highlights.push(highlight.clone());
+    save_highlights(key, &highlights)?;
+    Ok(highlight)
+}
+
+// function to update a highlight in the highlights file with the given key
+fn update_highlight(
+    key: &str,
+    id: &str,
+    update: HighlightUpdate,
+) -> Result<Highlight, ReaderError> {
+    let mut highlights = load_highlights(key)?;
+    let highlight = highlights
+        .iter_mut()
+        .find(|highlight| highlight.id == id)
+        .ok_or_else(|| ReaderError::NotFound(format!("highlight {} not found", id)))?;
+
+    let mut updated = highlight.clone();
+    updated.apply(update);
+    updated.validate()?;
+    *highlight = updated.clone();
+
+    save_highlights(key, &highlights)?;
+    Ok(updated)
+}
+
+// function to delete a highlight from the highlights file with the given key
+fn delete_highlight(key: &str, id: &str) -> Result<(), ReaderError> {
+    let mut highlights = load_highlights(key)?;
+    let count = highlights.len();
+
+    highlights.retain(|highlight| highlight.id != id);
+    if highlights.len() == count {
+        return Err(ReaderError::NotFound(format!("highlight {} not found", id)));
+    }
+    save_highlights(key, &highlights)
+}
+
+// e_pub_highlights_list command: returns all highlights of an epub when invoked
+#[tauri::command]
+pub fn e_pub_highlights_list(uid: &str) -> Result<Vec<Highlight>, ReaderError> {
+    validate_key(uid)?;
+    load_highlights(uid)
+}
+
+// e_pub_highlight_add command: adds a highlight to an epub and returns it when invoked
+#[tauri::command]
+pub fn e_pub_highlight_add(uid: &str, highlight: NewHighlight) -> Result<Highlight, ReaderError> {
+    validate_key(uid)?;
+    let id = new_highlight_id(load_highlights(uid)?.iter());
+    let highlight = create_highlight(
+        HighlightRange::Epub {
+            cfi_range: highlight.cfi_range,
+        },
+        highlight.text,
+        highlight.color,
+        highlight.note,
+        highlight.tags,
+        id,
+    )?;
+
+    add_highlight(uid, highlight)
+}
+
+// e_pub_highlight_update command: changes color, note or tags of a highlight when invoked
+#[tauri::command]
+pub fn e_pub_highlight_update(
+    uid: &str,
+    id: &str,
+    update: HighlightUpdate,
+) -> Result<Highlight, ReaderError> {
+    validate_key(uid)?;
+    update_highlight(uid, id, update)
+}
+
+// e_pub_highlight_delete command: deletes a highlight of an epub when invoked
+#[tauri::command]
+pub fn e_pub_highlight_delete(uid: &str, id: &str) -> Result<(), ReaderError> {
+    validate_key(uid)?;
+    delete_highlight(uid, id)
+}
+
+// e_pub_highlight_save command: replaces all highlights of an epub with the list from the viewer when invoked
+// highlights already saved for the same range keep their id, note, tags and creation time
+#[tauri::command]
+pub fn e_pub_highlight_save(
+    uid: &str,
+    highlights: Vec<NewHighlight>,
+) -> Result<Vec<Highlight>, ReaderError> {
+    validate_key(uid)?;
+    let saved = load_highlights(uid)?;
+    let mut updated: Vec<Highlight> = Vec::with_capacity(highlights.len());
+
+    for input in highlights {
+        let range = HighlightRange::Epub {
+            cfi_range: input.cfi_range.clone(),
+        };
+        if updated.iter().any(|highlight| highlight.range == range) {
+            continue; // same range sent twice
+        }
+
+        let highlight = match saved.iter().find(|highlight| highlight.range == range) {
+            Some(existing) => {
+                let mut highlight = existing.clone();
+                if highlight.color != input.color {
+                    highlight.apply(HighlightUpdate {
+                        color: Some(input.color),
+                        ..Default::default()
+                    });
+                }
+                if highlight.text.is_empty() && !input.text.is_empty() {
+                    highlight.text = input.text;
+                }
+                highlight.validate()?;
+                highlight
+            }
+            None => {
+                let id = new_highlight_id(saved.iter().chain(updated.iter()));
+                create_highlight(range, input.text, input.color, input.note, input.tags, id)?
+            }
+        };
+        updated.push(highlight);
+    }
+
+    save_highlights(uid, &updated)?;
+    Ok(updated)
+}
+
+// pdf_data_get command: refreshes a library pdf and returns its content hash and highlights when invoked
+#[tauri::command]
+pub fn pdf_data_get(path: &str) -> Result<PdfData, ReaderError> {
+    let mut library = Library::load()?;
+    // files are only added to the library through the open file dialog
+    if !library.books.iter().any(|book| book.path == path) {
+        return Err(ReaderError::NotFound(format!("{} is not in library", path)));
+    }
+    let book = library.register(path)?;
+    library.save()?;
+
+    Ok(PdfData {
+        highlights: load_highlights(highlights_key(&book))?,
+        hash: book.hash,
+        title: book.metadata.title,
+    })
+}
+
+// pdf_highlights_list command: returns all highlights of a pdf when invoked
+#[tauri::command]
+pub fn pdf_highlights_list(hash: &str) -> Result<Vec<Highlight>, ReaderError> {
+    validate_key(hash)?;
+    load_highlights(hash)
+}
+
+// pdf_highlight_add command: adds a highlight to a pdf and returns it when invoked
+#[tauri::command]
+pub fn pdf_highlight_add(hash: &str, highlight: NewPdfHighlight) -> Result<Highlight, ReaderError> {
+    validate_key(hash)?;
+    let id = new_highlight_id(load_highlights(hash)?.iter());
+    let highlight = create_highlight(
+        HighlightRange::Pdf {
+            page: highlight.page,
+            rects: highlight.rects,
+        },
+        highlight.text,
+        highlight.color,
+        highlight.note,
+        highlight.tags,
+        id,
+    )?;
+
+    add_highlight(hash, highlight)
+}
+
+// pdf_highlight_update command: changes color, note or tags of a pdf highlight when invoked
+#[tauri::command]
+pub fn pdf_highlight_update(
+    hash: &str,
+    id: &str,
+    update: HighlightUpdate,
+) -> Result<Highlight, ReaderError> {
+    validate_key(hash)?;
+    update_highlight(hash, id, update)
+}
+
+// pdf_highlight_delete command: deletes a highlight of a pdf when invoked
+#[tauri::command]
+pub fn pdf_highlight_delete(hash: &str, id: &str) -> Result<(), ReaderError> {
+    validate_key(hash)?;
+    delete_highlight(hash, id)
+}
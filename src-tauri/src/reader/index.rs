@@ -0,0 +1,671 @@
+/*
+    reader module:
+    index.rs keeps an on-disk inverted index over the text and highlights of all library books,
+    ranked with bm25 and updated book by book in the background when the library changes
+*/
+
+// importing crates and modules
+use crate::functions::{delete_file, read_dir};
+use crate::reader::cache::open_book;
+use crate::reader::epub::read_entry_string;
+use crate::reader::highlights::{highlights_key, load_highlights, Highlight, HighlightRange};
+use crate::reader::library::{Book, BookFormat, Library};
+use crate::reader::pdf_text::page_texts;
+use crate::reader::position::Locator;
+use crate::reader::search::{snippet, spine_cfi};
+use crate::reader::text::DocumentText;
+use crate::reader::toc::{read_toc, spine_chapters};
+use crate::reader::{read_json, save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// version of the segment format, segments of other versions are rebuilt
+const INDEX_VERSION: u32 = 1;
+
+// directory of the index inside the app data directory
+const INDEX_DIR: &str = "search";
+
+// epub text is split into passages of about this many bytes (whole paragraphs are kept together)
+const PASSAGE_LENGTH: usize = 800;
+
+// held while the index is updated, so a second update waits and then finds nothing left to do
+static UPDATE_LOCK: Mutex<()> = Mutex::new(());
+
+// number of results returned if the ui does not set a limit
+const DEFAULT_LIMIT: usize = 50;
+
+// bm25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// kind of an indexed passage
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PassageKind {
+    Text,
+    Highlight,
+}
+
+// Passage struct: the unit results are returned for (a few paragraphs, a pdf page or a highlight)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Passage {
+    pub kind: PassageKind,
+    pub chapter: Option<String>,
+    pub locator: Locator,
+    pub text: String,
+}
+
+// Segment struct: index of a single book, saved as search/{book_id}.json
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Segment {
+    version: u32,
+    book_id: String,
+    hash: String,              // content hash of the indexed file
+    highlights: (usize, u128), // count and last update of the indexed highlights
+    passages: Vec<Passage>,    // text passages followed by highlight passages
+    lengths: Vec<u32>,         // number of terms of every passage
+
+    // term -> (passage, positions of the term in the passage), sorted by passage
+    postings: HashMap<String, Vec<(u32, Vec<u32>)>>,
+}
+
+// implementations for Segment struct
+impl Segment {
+    // creates a segment and its postings from passages
+    fn new(book: &Book, highlights: (usize, u128), passages: Vec<Passage>) -> Self {
+        let mut lengths = Vec::with_capacity(passages.len());
+        let mut postings: HashMap<String, Vec<(u32, Vec<u32>)>> = HashMap::new();
+
+        for (index, passage) in passages.iter().enumerate() {
+            let terms = tokenize(&passage.text);
+            lengths.push(terms.len() as u32);
+
+            for (position, (term, _)) in terms.into_iter().enumerate() {
+                let list = postings.entry(term).or_default();
+                match list.last_mut() {
+                    Some((last, positions)) if *last == index as u32 => {
+                        positions.push(position as u32)
+                    }
+                    _ => list.push((index as u32, vec![position as u32])),
+                }
+            }
+        }
+
+        Self {
+            version: INDEX_VERSION,
+            book_id: book.id.clone(),
+            hash: book.hash.clone(),
+            highlights,
+            passages,
+            lengths,
+            postings,
+        }
+    }
+
+    // returns positions of a term inside a passage
+    fn positions(&self, term: &str, passage: u32) -> Option<&Vec<u32>> {
+        let list = self.postings.get(term)?;
+        let index = list.binary_search_by_key(&passage, |(p, _)| *p).ok()?;
+        Some(&list[index].1)
+    }
+
+    // checks if a passage contains the terms of a phrase next to each other
+    fn has_phrase(&self, phrase: &[String], passage: u32) -> bool {
+        let Some(first) = phrase
+            .first()
+            .and_then(|term| self.positions(term, passage))
+        else {
+            return false;
+        };
+        first.iter().any(|start| {
+            phrase.iter().enumerate().skip(1).all(|(offset, term)| {
+                self.positions(term, passage)
+                    .is_some_and(|positions| positions.contains(&(start + offset as u32)))
+            })
+        })
+    }
+}
+
+// SearchIndex struct: segments of all books, loaded from disk on first use
+#[derive(Default)]
+pub struct SearchIndex {
+    segments: HashMap<String, Segment>,
+    loaded: bool,
+}
+
+// IndexFilter struct: limits results to a book, a shelf or a kind of passage
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct IndexFilter {
+    pub book_id: Option<String>,
+    pub shelf_id: Option<String>,
+    pub kind: Option<PassageKind>,
+}
+
+// IndexHit struct: a ranked result, the ui shows before + matched + after
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexHit {
+    pub book_id: String,
+    pub title: String,
+    pub kind: PassageKind,
+    pub chapter: Option<String>,
+    pub locator: Locator,
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+    pub score: f64,
+}
+
+// IndexFailure struct: a book which could not be indexed, its old segment is kept
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexFailure {
+    pub book_id: String,
+    pub error: String,
+}
+
+// IndexStatus struct: summary returned after updating the index
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IndexStatus {
+    pub books: usize,
+    pub passages: usize,
+    pub updated: Vec<String>,      // ids of books (re)indexed by this update
+    pub failed: Vec<IndexFailure>, // books whose file or highlights could not be read
+}
+
+// IndexTask struct: a book whose segment has to be built again, with the text passages which can be reused
+struct IndexTask {
+    book: Book,
+    highlights: Vec<Highlight>,
+    text_passages: Option<Vec<Passage>>,
+}
+
+// Query struct: terms scored with bm25 and phrases which must appear in the passage
+struct Query {
+    terms: Vec<String>,
+    phrases: Vec<Vec<String>>,
+}
+
+// function to split text into lowercase terms with their byte ranges
+fn tokenize(text: &str) -> Vec<(String, (usize, usize))> {
+    let mut terms = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(from)) => {
+                terms.push((text[from..index].to_lowercase(), (from, index)));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    terms
+}
+
+// function to parse a query, "quoted words" are phrases
+fn parse_query(query: &str) -> Query {
+    let mut terms: Vec<String> = Vec::new();
+    let mut phrases = Vec::new();
+
+    for (index, part) in query.split('"').enumerate() {
+        let tokens: Vec<String> = tokenize(part).into_iter().map(|(term, _)| term).collect();
+        // odd parts are inside quotes
+        if index % 2 == 1 && tokens.len() > 1 {
+            phrases.push(tokens.clone());
+        }
+        for token in tokens {
+            if !terms.contains(&token) {
+                terms.push(token);
+            }
+        }
+    }
+
+    Query { terms, phrases }
+}
+
+// function to get the path of the segment of a book
+fn segment_path(book_id: &str) -> String {
+    format!("{}/{}.json", INDEX_DIR, book_id)
+}
+
+// function to get the count and last update of highlights, used to detect changes
+fn highlights_signature(highlights: &[Highlight]) -> (usize, u128) {
+    let updated = highlights
+        .iter()
+        .map(|highlight| highlight.updated_at)
+        .max()
+        .unwrap_or_default();
+    (highlights.len(), updated)
+}
+
+// function to split the text of a document into passages made of whole lines
+fn split_passages(text: &str) -> Vec<(usize, usize)> {
+    let mut passages = Vec::new();
+    let mut start = 0;
+    let mut end = 0;
+
+    for line in text.split_inclusive('\n') {
+        if end > start && end - start + line.len() > PASSAGE_LENGTH {
+            passages.push((start, end));
+            start = end;
+        }
+        end += line.len();
+    }
+    if end > start {
+        passages.push((start, end));
+    }
+
+    passages
+}
+
+// function to read the text passages of an epub
+fn e_pub_passages(path: &str) -> Result<Vec<Passage>, ReaderError> {
+    let (mut archive, package) = open_book(path)?;
+    let chapters = read_toc(&mut archive, &package)
+        .map(|toc| spine_chapters(&package, &toc))
+        .unwrap_or_default();
+
+    let mut passages = Vec::new();
+    for (index, spine_item) in package.spine.iter().enumerate() {
+        let Some(item) = package.item_by_id(&spine_item.idref) else {
+            continue;
+        };
+        let Ok(data) = read_entry_string(&mut archive, &item.href) else {
+            continue;
+        };
+        let document = DocumentText::new(&data);
+
+        for (start, end) in split_passages(&document.text) {
+            let Some(range) = document.cfi_range(start, end) else {
+                continue;
+            };
+            passages.push(Passage {
+                kind: PassageKind::Text,
+                chapter: chapters.get(index).cloned().flatten(),
+                locator: Locator::Epub {
+                    cfi: spine_cfi(index, &spine_item.idref, &range),
+                    href: Some(item.href.clone()),
+                },
+                text: document.text[start..end].trim_end().to_string(),
+            });
+        }
+    }
+
+    Ok(passages)
+}
+
+// function to get the locator of a pdf page
+fn pdf_page_locator(page: u32, page_count: Option<u32>) -> Locator {
+    Locator::Pdf {
+        page,
+        page_count,
+        scroll_offset: 0.0,
+        zoom: 1.0,
+        rotation: 0,
+    }
+}
+
+// function to read the text passages (one per page) of a pdf
+fn pdf_passages(path: &str) -> Result<Vec<Passage>, ReaderError> {
+    let pages = page_texts(path)?;
+    let page_count = pages.len() as u32;
+
+    Ok(pages
+        .into_iter()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(index, text)| Passage {
+            kind: PassageKind::Text,
+            chapter: None,
+            locator: pdf_page_locator(index as u32 + 1, Some(page_count)),
+            text: text.trim().to_string(),
+        })
+        .collect())
+}
+
+// function to create passages from highlights, the note is searchable together with the text
+fn highlight_passages(highlights: &[Highlight]) -> Vec<Passage> {
+    highlights
+        .iter()
+        .map(|highlight| Passage {
+            kind: PassageKind::Highlight,
+            chapter: None,
+            locator: match &highlight.range {
+                HighlightRange::Epub { cfi_range } => Locator::Epub {
+                    cfi: cfi_range.clone(),
+                    href: None,
+                },
+                HighlightRange::Pdf { page, .. } => pdf_page_locator(*page, None),
+            },
+            text: match &highlight.note {
+                Some(note) => format!("{}\n{}", highlight.text, note),
+                None => highlight.text.clone(),
+            },
+        })
+        .collect()
+}
+
+// implementations for SearchIndex struct
+impl SearchIndex {
+    // loads saved segments once, segments of older versions are dropped
+    fn load(&mut self) {
+        if self.loaded {
+            return;
+        }
+        self.loaded = true;
+
+        let Ok(entries) = read_dir(INDEX_DIR) else {
+            return; // nothing indexed yet
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(book_id) = name.strip_suffix(".json") else {
+                continue;
+            };
+
+            match read_json::<Option<Segment>>(&segment_path(book_id)) {
+                Ok(Some(segment)) if segment.version == INDEX_VERSION => {
+                    self.segments.insert(book_id.to_string(), segment);
+                }
+                _ => {
+                    let _ = delete_file(&segment_path(book_id));
+                }
+            }
+        }
+    }
+
+    // drops the segment of a book removed from the library
+    pub fn remove(&mut self, book_id: &str) {
+        self.segments.remove(book_id);
+        let _ = delete_file(&segment_path(book_id));
+    }
+
+    // returns the books whose segment is missing or out of date, segments of removed books are dropped
+    fn tasks(&mut self, library: &Library, failed: &mut Vec<IndexFailure>) -> Vec<IndexTask> {
+        self.load();
+
+        // drop books removed from the library
+        let ids: HashSet<&str> = library.books.iter().map(|book| book.id.as_str()).collect();
+        let removed: Vec<String> = self
+            .segments
+            .keys()
+            .filter(|id| !ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+        for id in removed {
+            self.remove(&id);
+        }
+
+        let mut tasks = Vec::new();
+        for book in &library.books {
+            // a corrupt highlights file only keeps its own book from being updated
+            let highlights = match load_highlights(highlights_key(book)) {
+                Ok(highlights) => highlights,
+                Err(e) => {
+                    failed.push(IndexFailure {
+                        book_id: book.id.clone(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let existing = self.segments.get(&book.id);
+            let signature = highlights_signature(&highlights);
+            if existing.is_some_and(|s| s.hash == book.hash && s.highlights == signature) {
+                continue;
+            }
+
+            // reuse text passages if only the highlights changed
+            let text_passages =
+                existing
+                    .filter(|segment| segment.hash == book.hash)
+                    .map(|segment| {
+                        segment
+                            .passages
+                            .iter()
+                            .filter(|passage| passage.kind == PassageKind::Text)
+                            .cloned()
+                            .collect()
+                    });
+            tasks.push(IndexTask {
+                book: book.clone(),
+                highlights,
+                text_passages,
+            });
+        }
+
+        tasks
+    }
+
+    // returns a summary of the index
+    fn status(&self, updated: Vec<String>, failed: Vec<IndexFailure>) -> IndexStatus {
+        IndexStatus {
+            books: self.segments.len(),
+            passages: self.segments.values().map(|s| s.passages.len()).sum(),
+            updated,
+            failed,
+        }
+    }
+
+    // returns passages matching the query, best first
+    pub fn search(
+        &self,
+        library: &Library,
+        query: &str,
+        filter: &IndexFilter,
+        limit: usize,
+    ) -> Vec<IndexHit> {
+        let query = parse_query(query);
+
+        let shelf_books = filter.shelf_id.as_ref().map(|id| {
+            library
+                .shelves
+                .iter()
+                .find(|shelf| shelf.id == *id)
+                .map(|shelf| shelf.book_ids.clone())
+                .unwrap_or_default()
+        });
+        let segments: Vec<&Segment> = self
+            .segments
+            .values()
+            .filter(|s| filter.book_id.as_ref().is_none_or(|id| *id == s.book_id))
+            .filter(|s| {
+                shelf_books
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&s.book_id))
+            })
+            .collect();
+
+        // collection statistics used by bm25
+        let passage_count: usize = segments.iter().map(|s| s.passages.len()).sum();
+        let total_length: u64 = segments
+            .iter()
+            .flat_map(|s| s.lengths.iter())
+            .map(|length| *length as u64)
+            .sum();
+        if passage_count == 0 {
+            return Vec::new();
+        }
+        let average_length = total_length as f64 / passage_count as f64;
+
+        let mut scores: HashMap<(usize, u32), f64> = HashMap::new();
+        for term in &query.terms {
+            let document_frequency: usize = segments
+                .iter()
+                .filter_map(|s| s.postings.get(term))
+                .map(|list| list.len())
+                .sum();
+            let idf = ((passage_count as f64 - document_frequency as f64 + 0.5)
+                / (document_frequency as f64 + 0.5)
+                + 1.0)
+                .ln();
+
+            for (segment_index, segment) in segments.iter().enumerate() {
+                for (passage, positions) in segment.postings.get(term).into_iter().flatten() {
+                    let frequency = positions.len() as f64;
+                    let length = segment.lengths[*passage as usize] as f64;
+                    let score = idf * frequency * (K1 + 1.0)
+                        / (frequency + K1 * (1.0 - B + B * length / average_length.max(1.0)));
+                    *scores.entry((segment_index, *passage)).or_default() += score;
+                }
+            }
+        }
+
+        let mut ranked: Vec<((usize, u32), f64)> = scores
+            .into_iter()
+            .filter(|((segment_index, passage), _)| {
+                let segment = segments[*segment_index];
+                filter
+                    .kind
+                    .is_none_or(|kind| segment.passages[*passage as usize].kind == kind)
+                    && query
+                        .phrases
+                        .iter()
+                        .all(|phrase| segment.has_phrase(phrase, *passage))
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(limit);
+
+        ranked
+            .into_iter()
+            .map(|((segment_index, passage), score)| {
+                let segment = segments[segment_index];
+                let passage = &segment.passages[passage as usize];
+
+                // show the text around the first query term found in the passage
+                let (start, end) = tokenize(&passage.text)
+                    .into_iter()
+                    .find(|(term, _)| query.terms.contains(term))
+                    .map(|(_, range)| range)
+                    .unwrap_or((0, 0));
+                let (before, after) = snippet(&passage.text, start, end);
+
+                IndexHit {
+                    book_id: segment.book_id.clone(),
+                    title: library
+                        .book(&segment.book_id)
+                        .map(|book| book.metadata.title.clone())
+                        .unwrap_or_default(),
+                    kind: passage.kind,
+                    chapter: passage.chapter.clone(),
+                    locator: passage.locator.clone(),
+                    before,
+                    matched: passage.text[start..end].to_string(),
+                    after,
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+// function to read a book and save its new segment
+fn build_segment(task: IndexTask) -> Result<Segment, ReaderError> {
+    let IndexTask {
+        book,
+        highlights,
+        text_passages,
+    } = task;
+
+    let mut passages = match text_passages {
+        Some(passages) => passages,
+        None => match book.format {
+            BookFormat::Pdf => pdf_passages(&book.path)?,
+            // comics are images, only their highlights and notes could be searched
+            BookFormat::Comic => Vec::new(),
+            // documents are read as epubs with a single chapter
+            _ => e_pub_passages(&book.path)?,
+        },
+    };
+    passages.extend(highlight_passages(&highlights));
+
+    let segment = Segment::new(&book, highlights_signature(&highlights), passages);
+    save_json(&segment_path(&book.id), &segment)?;
+    Ok(segment)
+}
+
+// function to bring the index up to date with the library, only changed books are read again
+// books are read without holding the index, so searches are not blocked by an update
+pub fn update_index(index: &Mutex<SearchIndex>) -> Result<IndexStatus, ReaderError> {
+    let _updating = UPDATE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let library = Library::load()?;
+
+    let mut failed = Vec::new();
+    let tasks = index.lock().unwrap().tasks(&library, &mut failed);
+
+    let mut updated = Vec::new();
+    for task in tasks {
+        let book_id = task.book.id.clone();
+        match build_segment(task) {
+            Ok(segment) => {
+                index
+                    .lock()
+                    .unwrap()
+                    .segments
+                    .insert(book_id.clone(), segment);
+                updated.push(book_id);
+            }
+            // keep the old segment of books which are missing or can not be read
+            Err(e) => failed.push(IndexFailure {
+                book_id,
+                error: e.to_string(),
+            }),
+        }
+    }
+
+    Ok(index.lock().unwrap().status(updated, failed))
+}
+
+// function to update the index in a background thread, e.g. after a book was added
+pub fn update_in_background(index: &Arc<Mutex<SearchIndex>>) {
+    let index = Arc::clone(index);
+    thread::spawn(move || update_index(&index));
+}
+
+// function to initiate search index state
+pub fn init_state() -> Arc<Mutex<SearchIndex>> {
+    Arc::new(Mutex::new(SearchIndex::default()))
+}
+
+// library_index_update command: indexes new and changed library books when invoked
+#[tauri::command]
+pub fn library_index_update(
+    state: tauri::State<Arc<Mutex<SearchIndex>>>,
+) -> Result<IndexStatus, ReaderError> {
+    update_index(&state)
+}
+
+// library_search command: searches all library books and highlights when invoked
+// the index is searched as it is and updated in the background afterwards, e.g. for changed highlights
+#[tauri::command]
+pub fn library_search(
+    state: tauri::State<Arc<Mutex<SearchIndex>>>,
+    query: &str,
+    filter: Option<IndexFilter>,
+    limit: Option<usize>,
+) -> Result<Vec<IndexHit>, ReaderError> {
+    if query.trim().is_empty() {
+        return Err(ReaderError::ValidationError(
+            "search query is empty".to_string(),
+        ));
+    }
+
+    let library = Library::load()?;
+    let hits = {
+        let mut index = state.lock().unwrap();
+        index.load();
+        index.search(
+            &library,
+            query,
+            &filter.unwrap_or_default(),
+            limit.unwrap_or(DEFAULT_LIMIT),
+        )
+    };
+
+    update_in_background(&state);
+    Ok(hits)
+}
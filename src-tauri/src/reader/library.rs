@@ -0,0 +1,737 @@
+/*
+    reader module:
+    library.rs contains the persistent catalog of opened books and user defined shelves
+*/
+
+// importing crates and modules
+use crate::functions::{current_time_ms, system_time_to_ms};
+use crate::reader::bookmarks::{move_bookmarks, remove_bookmarks};
+use crate::reader::cache::open_book;
+use crate::reader::citation::CitationFields;
+use crate::reader::comic::{list_pages, read_comic_info};
+use crate::reader::cover::clear_cover_cache;
+use crate::reader::document::clear_document_cache;
+use crate::reader::epub::Metadata;
+use crate::reader::highlights::{highlights_key, move_highlights, remove_highlights};
+use crate::reader::index::{update_in_background, SearchIndex};
+use crate::reader::pdf::load_document;
+use crate::reader::pdf_info::{read_info, PdfInfo};
+use crate::reader::position::{load_positions, move_position, remove_position};
+use crate::reader::stats::move_sessions;
+use crate::reader::{ensure_uid, extract_uid, read_json, read_opf_file, save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// path of the catalog file inside the app data directory
+const LIBRARY_FILE: &str = "library/catalog.json";
+
+// formats supported by the reader
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BookFormat {
+    Pdf,
+    Epub,
+    Text,
+    Markdown,
+    Html,
+    Comic, // cbz image archive
+    Docx,
+    Odt,
+}
+
+// implementations for BookFormat enum
+impl BookFormat {
+    // detects format from the file extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "pdf" => Some(Self::Pdf),
+            "epub" => Some(Self::Epub),
+            "txt" => Some(Self::Text),
+            "md" | "markdown" => Some(Self::Markdown),
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "cbz" | "cbr" => Some(Self::Comic),
+            "docx" => Some(Self::Docx),
+            "odt" => Some(Self::Odt),
+            _ => None,
+        }
+    }
+
+    // checks if the format is a text document, which is read as a single chapter epub
+    pub fn is_document(self) -> bool {
+        matches!(
+            self,
+            Self::Text | Self::Markdown | Self::Html | Self::Docx | Self::Odt
+        )
+    }
+
+    // checks if the format is a word processor file, whose conversion is cached on disk
+    pub fn is_office(self) -> bool {
+        matches!(self, Self::Docx | Self::Odt)
+    }
+}
+
+// BookMetadata struct: descriptive metadata shown in the library
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct BookMetadata {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub language: Option<String>,
+    pub publisher: Option<String>,
+    pub identifier: Option<String>,
+    pub description: Option<String>,
+    pub date: Option<String>,
+    pub subjects: Vec<String>,
+}
+
+// implementations for BookMetadata struct
+impl BookMetadata {
+    // creates metadata from epub package metadata, falling back to file name as title
+    fn from_epub(metadata: Metadata, path: &str) -> Self {
+        Self {
+            title: metadata.title.unwrap_or_else(|| file_stem(path)),
+            authors: metadata.authors,
+            language: metadata.language,
+            publisher: metadata.publisher,
+            identifier: metadata.identifier,
+            description: metadata.description,
+            date: metadata.date,
+            subjects: metadata.subjects,
+        }
+    }
+
+    // creates metadata from the pdf document info, falling back to file name as title
+    fn from_pdf(info: PdfInfo, path: &str) -> Self {
+        // keywords are free text, most writers separate them with commas or semicolons
+        let subjects = info
+            .keywords
+            .map(|keywords| {
+                keywords
+                    .split([',', ';'])
+                    .map(str::trim)
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            title: info.title.unwrap_or_else(|| file_stem(path)),
+            authors: info.author.into_iter().collect(),
+            description: info.subject,
+            date: info.creation_date,
+            subjects,
+            ..Default::default()
+        }
+    }
+
+    // creates metadata containing only the file name as title
+    fn from_file_name(path: &str) -> Self {
+        Self {
+            title: file_stem(path),
+            ..Default::default()
+        }
+    }
+}
+
+// Book struct: an entry of the library
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Book {
+    pub id: String, // uid stored in content.opf for epubs, prefix of the content hash otherwise
+    pub path: String,
+    pub format: BookFormat,
+    pub metadata: BookMetadata,
+    pub cover: Option<String>, // path of the cover image inside the epub archive
+    pub size: u64,
+    pub hash: String, // sha256 of the file content
+    pub modified: u128,
+    pub added_at: u128,
+    pub last_opened: Option<u128>,
+    #[serde(default)]
+    pub missing: bool, // set when the file no longer exists at path
+    #[serde(default)]
+    pub progress: Option<f32>, // reading progress in percent, filled when listing
+    #[serde(default)]
+    pub trusted: bool, // content is served without sanitizing, e.g. for books relying on scripts
+    #[serde(default)]
+    pub citation: CitationFields, // metadata corrected by the user for citations
+}
+
+// Shelf struct: user defined collection of books
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Shelf {
+    pub id: String,
+    pub name: String,
+    pub book_ids: Vec<String>,
+    pub created_at: u128,
+}
+
+// Library struct: the saved catalog
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Library {
+    pub books: Vec<Book>,
+    pub shelves: Vec<Shelf>,
+}
+
+// implementations for Library struct
+impl Library {
+    // loads the catalog from app data, or returns an empty one if not found
+    pub fn load() -> Result<Self, ReaderError> {
+        read_json(LIBRARY_FILE)
+    }
+
+    // saves the catalog to app data
+    pub fn save(&self) -> Result<(), ReaderError> {
+        save_json(LIBRARY_FILE, self)
+    }
+
+    pub fn book(&self, id: &str) -> Option<&Book> {
+        self.books.iter().find(|book| book.id == id)
+    }
+
+    pub fn book_mut(&mut self, id: &str) -> Result<&mut Book, ReaderError> {
+        self.books
+            .iter_mut()
+            .find(|book| book.id == id)
+            .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", id)))
+    }
+
+    pub fn shelf_mut(&mut self, id: &str) -> Result<&mut Shelf, ReaderError> {
+        self.shelves
+            .iter_mut()
+            .find(|shelf| shelf.id == id)
+            .ok_or_else(|| ReaderError::NotFound(format!("shelf {} not found", id)))
+    }
+
+    // registers a file in the library (or refreshes its entry) and returns the entry
+    pub fn register(&mut self, path: &str) -> Result<Book, ReaderError> {
+        let file_meta = fs::metadata(path)?;
+        let modified = system_time_to_ms(file_meta.modified()?);
+
+        // skip re-reading files which did not change since they were registered
+        if let Some(book) = self.books.iter_mut().find(|book| {
+            book.path == path && book.size == file_meta.len() && book.modified == modified
+        }) {
+            book.missing = false;
+            return Ok(book.clone());
+        }
+
+        let book = read_book(path)?;
+
+        // a file changed at the same path gets a new id (pdfs, comics) or is another book,
+        // its old entry is updated when the new id is not in library yet, or marked as missing otherwise
+        let same_id = self.books.iter().position(|entry| entry.id == book.id);
+        let same_path = self
+            .books
+            .iter()
+            .position(|entry| entry.path == path && entry.id != book.id);
+        let index = match (same_id, same_path) {
+            (Some(index), Some(stale)) => {
+                self.books[stale].missing = true;
+                Some(index)
+            }
+            (Some(index), None) => Some(index),
+            (None, Some(index)) => {
+                migrate_book_data(self, index, &book)?;
+                Some(index)
+            }
+            (None, None) => None,
+        };
+
+        match index.map(|index| &mut self.books[index]) {
+            // same book seen before (possibly at another path): keep its history
+            Some(entry) => {
+                if entry.hash != book.hash {
+                    clear_cover_cache(&entry.id);
+                    clear_cover_cache(&book.id);
+                    clear_document_cache(&entry.hash);
+                }
+
+                let added_at = entry.added_at;
+                let last_opened = entry.last_opened;
+                let trusted = entry.trusted;
+                let citation = entry.citation.clone();
+                *entry = Book {
+                    added_at,
+                    last_opened,
+                    trusted,
+                    citation,
+                    ..book
+                };
+                Ok(entry.clone())
+            }
+            None => {
+                self.books.push(book.clone());
+                Ok(book)
+            }
+        }
+    }
+
+    // marks a book as opened now
+    pub fn touch(&mut self, id: &str) -> Result<Book, ReaderError> {
+        let book = self.book_mut(id)?;
+        book.last_opened = Some(current_time_ms());
+        Ok(book.clone())
+    }
+}
+
+// function to move the data kept for a library book to the id and hash of its new content
+fn migrate_book_data(library: &mut Library, index: usize, book: &Book) -> Result<(), ReaderError> {
+    let old = library.books[index].clone();
+
+    let old_key = highlights_key(&old);
+    let new_key = highlights_key(book);
+    if old_key != new_key {
+        move_highlights(old_key, new_key)?;
+    }
+
+    if old.id != book.id {
+        move_position(&old.id, &book.id)?;
+        move_bookmarks(&old.id, &book.id)?;
+        move_sessions(&old.id, &book.id)?;
+        for shelf in library.shelves.iter_mut() {
+            for book_id in shelf
+                .book_ids
+                .iter_mut()
+                .filter(|book_id| **book_id == old.id)
+            {
+                *book_id = book.id.clone();
+            }
+        }
+    }
+    Ok(())
+}
+
+// LibrarySort enum: fields the book list can be sorted by
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum LibrarySort {
+    Title,
+    Author,
+    DateAdded,
+    LastOpened,
+    Size,
+}
+
+// LibraryFilter struct: all fields are optional and combined with AND
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LibraryFilter {
+    pub format: Option<BookFormat>,
+    pub shelf_id: Option<String>,
+    pub query: Option<String>, // matched against title and authors (case insensitive)
+    pub missing: Option<bool>,
+}
+
+// function to get the file name without extension
+fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// function to check the format of a book id: prefixes of content hashes or timestamp uids, both hex digits
+// ids are used in paths inside the app data directory, so ids from the webview are checked first
+pub fn is_book_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+// function to compute sha256 of a file (streamed, books can be hundreds of MBs)
+pub fn hash_file(path: &str) -> Result<String, ReaderError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// function to read a file and create a library entry for it
+fn read_book(path: &str) -> Result<Book, ReaderError> {
+    let format = BookFormat::from_path(path)
+        .ok_or_else(|| ReaderError::InvalidData(format!("{} is not a supported format", path)))?;
+
+    let (uid, metadata, cover) = match format {
+        // documents are read through the epub made from them, which already carries a uid
+        BookFormat::Epub
+        | BookFormat::Text
+        | BookFormat::Markdown
+        | BookFormat::Html
+        | BookFormat::Docx
+        | BookFormat::Odt => {
+            // make sure the epub carries a uid before hashing, as saving it rewrites the file
+            let opf_data = read_opf_file(path)?;
+            let uid = ensure_uid(path, &opf_data);
+
+            let (_, package) = open_book(path)?;
+            let cover = package.cover_item().map(|item| item.href.clone());
+            (
+                uid,
+                BookMetadata::from_epub(package.metadata.clone(), path),
+                cover,
+            )
+        }
+        BookFormat::Pdf => {
+            // pdfs without readable info are still added, named after the file
+            let metadata = load_document(path)
+                .map(|document| BookMetadata::from_pdf(read_info(&document), path))
+                .unwrap_or_else(|_| BookMetadata::from_file_name(path));
+            (None, metadata, None)
+        }
+        BookFormat::Comic => {
+            // the pages must be readable, but tagged metadata is optional
+            list_pages(path)?;
+            let metadata =
+                read_comic_info(path).unwrap_or_else(|| BookMetadata::from_file_name(path));
+            (None, metadata, None)
+        }
+    };
+
+    let file_meta = fs::metadata(path)?;
+    let hash = hash_file(path)?;
+    let now = current_time_ms();
+
+    Ok(Book {
+        // uids written by other apps are not trusted as ids, they end up in paths
+        id: uid
+            .filter(|uid| is_book_id(uid))
+            .unwrap_or_else(|| hash[..16].to_string()),
+        path: path.to_string(),
+        format,
+        metadata,
+        cover,
+        size: file_meta.len(),
+        hash,
+        modified: system_time_to_ms(file_meta.modified()?),
+        added_at: now,
+        last_opened: None,
+        missing: false,
+        progress: None,
+        trusted: false,
+        citation: CitationFields::default(),
+    })
+}
+
+// function to register a file in the library and mark it as opened
+pub fn register_and_open(path: &str) -> Result<Book, ReaderError> {
+    let mut library = Library::load()?;
+    let id = library.register(path)?.id;
+    let book = library.touch(&id)?;
+    library.save()?;
+    Ok(book)
+}
+
+// library_books_list command: returns the books of library, filtered and sorted when invoked
+#[tauri::command]
+pub fn library_books_list(
+    filter: Option<LibraryFilter>,
+    sort_by: Option<LibrarySort>,
+    descending: Option<bool>,
+) -> Result<Vec<Book>, ReaderError> {
+    let library = Library::load()?;
+    let filter = filter.unwrap_or_default();
+    let query = filter.query.as_ref().map(|q| q.to_lowercase());
+
+    // ids of books on the requested shelf
+    let shelf_books = match &filter.shelf_id {
+        Some(id) => Some(
+            library
+                .shelves
+                .iter()
+                .find(|shelf| shelf.id == *id)
+                .map(|shelf| shelf.book_ids.clone())
+                .unwrap_or_default(),
+        ),
+        None => None,
+    };
+
+    let positions = load_positions()?;
+    let mut books: Vec<Book> = library
+        .books
+        .into_iter()
+        .map(|mut book| {
+            book.missing = !Path::new(&book.path).exists();
+            book.progress = positions.get(&book.id).map(|position| position.percentage);
+            book
+        })
+        .filter(|book| filter.format.is_none_or(|format| book.format == format))
+        .filter(|book| filter.missing.is_none_or(|missing| book.missing == missing))
+        .filter(|book| {
+            shelf_books
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&book.id))
+        })
+        .filter(|book| {
+            query.as_ref().is_none_or(|query| {
+                book.metadata.title.to_lowercase().contains(query)
+                    || book
+                        .metadata
+                        .authors
+                        .iter()
+                        .any(|author| author.to_lowercase().contains(query))
+            })
+        })
+        .collect();
+
+    // sort by the requested field (recently opened first by default)
+    match sort_by.unwrap_or(LibrarySort::LastOpened) {
+        LibrarySort::Title => books.sort_by_key(|book| book.metadata.title.to_lowercase()),
+        LibrarySort::Author => books.sort_by_key(|book| {
+            book.metadata
+                .authors
+                .first()
+                .map(|author| author.to_lowercase())
+                .unwrap_or_default()
+        }),
+        LibrarySort::DateAdded => books.sort_by_key(|book| book.added_at),
+        LibrarySort::LastOpened => {
+            books.sort_by_key(|book| std::cmp::Reverse(book.last_opened.unwrap_or(book.added_at)))
+        }
+        LibrarySort::Size => books.sort_by_key(|book| book.size),
+    }
+    if descending.unwrap_or(false) {
+        books.reverse();
+    }
+
+    Ok(books)
+}
+
+// library_book_open command: marks a library book as opened and returns its entry when invoked
+#[tauri::command]
+pub fn library_book_open(
+    state: tauri::State<Arc<Mutex<SearchIndex>>>,
+    id: &str,
+) -> Result<Book, ReaderError> {
+    let mut library = Library::load()?;
+    let path = library.book_mut(id)?.path.clone();
+
+    if !Path::new(&path).exists() {
+        return Err(ReaderError::NotFound(format!(
+            "{} was moved or deleted, please locate it again",
+            path
+        )));
+    }
+
+    // refresh the entry in case the file changed since last time, which can change its id
+    let id = library.register(&path)?.id;
+    let book = library.touch(&id)?;
+    library.save()?;
+
+    update_in_background(&state);
+    Ok(book)
+}
+
+// library_book_remove command: removes a book from the library and its shelves when invoked
+#[tauri::command]
+pub fn library_book_remove(
+    state: tauri::State<Arc<Mutex<SearchIndex>>>,
+    id: &str,
+) -> Result<(), ReaderError> {
+    let mut library = Library::load()?;
+    let removed = library.book(id).cloned();
+    if let Some(book) = &removed {
+        clear_document_cache(&book.hash);
+    }
+    library.books.retain(|book| book.id != id);
+    for shelf in library.shelves.iter_mut() {
+        shelf.book_ids.retain(|book_id| book_id != id);
+    }
+    library.save()?;
+
+    clear_cover_cache(id);
+    remove_position(id)?;
+    // highlights, bookmarks and the search segment go with the book, a book added again starts without them
+    if let Some(book) = &removed {
+        remove_highlights(highlights_key(book))?;
+        remove_bookmarks(&book.id)?;
+        state.lock().unwrap().remove(&book.id);
+    }
+
+    update_in_background(&state);
+    Ok(())
+}
+
+// library_book_trust_set command: allows or forbids scripts and remote content of a library book when invoked
+// trusted books are served as they are, other epubs are sanitized before reaching the webview
+#[tauri::command]
+pub fn library_book_trust_set(id: &str, trusted: bool) -> Result<Book, ReaderError> {
+    let mut library = Library::load()?;
+    let book = library.book_mut(id)?;
+    book.trusted = trusted;
+    let book = book.clone();
+    library.save()?;
+    Ok(book)
+}
+
+// library_book_relocate command: points a library book to its new path when invoked
+#[tauri::command]
+pub fn library_book_relocate(id: &str, path: &str) -> Result<Book, ReaderError> {
+    let mut library = Library::load()?;
+    let book = library.book_mut(id)?;
+
+    if !is_same_book(book, path)? {
+        return Err(ReaderError::InvalidData(format!(
+            "{} is not the same book as {}",
+            path, book.metadata.title
+        )));
+    }
+
+    book.path = path.to_string();
+    book.missing = false;
+    let book = library.register(path)?;
+    library.save()?;
+    Ok(book)
+}
+
+// library_books_relocate_in command: searches a folder for missing books and relocates the ones found when invoked
+#[tauri::command]
+pub fn library_books_relocate_in(dir: &str) -> Result<Vec<Book>, ReaderError> {
+    let mut library = Library::load()?;
+    let mut relocated = Vec::new();
+
+    let missing: Vec<Book> = library
+        .books
+        .iter()
+        .filter(|book| !Path::new(&book.path).exists())
+        .cloned()
+        .collect();
+    if missing.is_empty() {
+        return Ok(relocated);
+    }
+
+    for candidate in walk_files(Path::new(dir)) {
+        let Some(candidate) = candidate.to_str() else {
+            continue;
+        };
+        if BookFormat::from_path(candidate).is_none() {
+            continue;
+        }
+
+        // compare sizes first to avoid hashing every file of the folder
+        let size = fs::metadata(candidate).map(|meta| meta.len()).unwrap_or(0);
+        for book in missing.iter().filter(|book| book.size == size) {
+            if relocated.iter().any(|found: &Book| found.id == book.id) {
+                continue;
+            }
+            if is_same_book(book, candidate).unwrap_or(false) {
+                library.book_mut(&book.id)?.path = candidate.to_string();
+                relocated.push(library.register(candidate)?);
+                break;
+            }
+        }
+    }
+
+    library.save()?;
+    Ok(relocated)
+}
+
+// function to check if the file at path is the given library book (same content or same epub uid)
+fn is_same_book(book: &Book, path: &str) -> Result<bool, ReaderError> {
+    if hash_file(path)? == book.hash {
+        return Ok(true);
+    }
+
+    if book.format == BookFormat::Epub && BookFormat::from_path(path) == Some(BookFormat::Epub) {
+        let opf_data = read_opf_file(path)?;
+        return Ok(extract_uid(&opf_data).as_deref() == Some(book.id.as_str()));
+    }
+
+    Ok(false)
+}
+
+// function to list files of a directory recursively
+fn walk_files(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(walk_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+// library_shelves_list command: returns all shelves when invoked
+#[tauri::command]
+pub fn library_shelves_list() -> Result<Vec<Shelf>, ReaderError> {
+    Ok(Library::load()?.shelves)
+}
+
+// library_shelf_create command: creates a new empty shelf when invoked
+#[tauri::command]
+pub fn library_shelf_create(name: String) -> Result<Shelf, ReaderError> {
+    let mut library = Library::load()?;
+    let now = current_time_ms();
+
+    let shelf = Shelf {
+        id: now.to_string(),
+        name,
+        book_ids: Vec::new(),
+        created_at: now,
+    };
+    library.shelves.push(shelf.clone());
+    library.save()?;
+    Ok(shelf)
+}
+
+// library_shelf_rename command: renames a shelf when invoked
+#[tauri::command]
+pub fn library_shelf_rename(id: &str, name: String) -> Result<(), ReaderError> {
+    let mut library = Library::load()?;
+    library.shelf_mut(id)?.name = name;
+    library.save()
+}
+
+// library_shelf_delete command: deletes a shelf (books stay in the library) when invoked
+#[tauri::command]
+pub fn library_shelf_delete(id: &str) -> Result<(), ReaderError> {
+    let mut library = Library::load()?;
+    library.shelves.retain(|shelf| shelf.id != id);
+    library.save()
+}
+
+// library_shelf_book_add command: adds a library book to a shelf when invoked
+#[tauri::command]
+pub fn library_shelf_book_add(shelf_id: &str, book_id: String) -> Result<(), ReaderError> {
+    let mut library = Library::load()?;
+    if library.book(&book_id).is_none() {
+        return Err(ReaderError::NotFound(format!(
+            "book {} is not in library",
+            book_id
+        )));
+    }
+
+    let shelf = library.shelf_mut(shelf_id)?;
+    if !shelf.book_ids.contains(&book_id) {
+        shelf.book_ids.push(book_id);
+    }
+    library.save()
+}
+
+// library_shelf_book_remove command: removes a book from a shelf when invoked
+#[tauri::command]
+pub fn library_shelf_book_remove(shelf_id: &str, book_id: &str) -> Result<(), ReaderError> {
+    let mut library = Library::load()?;
+    library
+        .shelf_mut(shelf_id)?
+        .book_ids
+        .retain(|id| id != book_id);
+    library.save()
+}
@@ -0,0 +1,511 @@
+/*
+    reader module:
+    lua.rs reads and writes the lua tables koreader stores its book settings and annotations in
+*/
+
+// importing crates and modules
+use crate::reader::ReaderError;
+
+// first line of the files written by koreader
+const HEADER: &str = "-- we can read Lua syntax here!";
+
+// LuaValue enum: the values koreader serializes (functions and other lua types never appear)
+#[derive(Clone, Debug, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Table(Vec<(LuaValue, LuaValue)>), // key and value pairs in the order they were written
+}
+
+// implementations for LuaValue enum
+impl LuaValue {
+    // returns the value of a string key of a table
+    pub fn get(&self, key: &str) -> Option<&LuaValue> {
+        match self {
+            Self::Table(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| matches!(entry_key, Self::String(name) if name == key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    // returns the value of a string key of a table, or None if the key is missing or nil
+    pub fn field(&self, key: &str) -> Option<&LuaValue> {
+        self.get(key).filter(|value| **value != Self::Nil)
+    }
+
+    // sets the value of a string key of a table, adding the key if it is missing
+    pub fn set(&mut self, key: &str, value: LuaValue) {
+        if let Self::Table(entries) = self {
+            match entries
+                .iter_mut()
+                .find(|(entry_key, _)| matches!(entry_key, Self::String(name) if name == key))
+            {
+                Some((_, existing)) => *existing = value,
+                None => entries.push((Self::String(key.to_string()), value)),
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Self::Number(number) => Some(*number),
+            Self::String(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        matches!(self, Self::Bool(true))
+    }
+
+    // returns the values of a table, values with number keys come first sorted by their key (lua arrays)
+    pub fn values(&self) -> Vec<&LuaValue> {
+        let Self::Table(entries) = self else {
+            return Vec::new();
+        };
+        let mut numbered: Vec<(f64, &LuaValue)> = entries
+            .iter()
+            .filter_map(|(key, value)| match key {
+                Self::Number(number) => Some((*number, value)),
+                _ => None,
+            })
+            .collect();
+        numbered.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let named = entries
+            .iter()
+            .filter(|(key, _)| !matches!(key, Self::Number(_)))
+            .map(|(_, value)| value);
+        numbered
+            .into_iter()
+            .map(|(_, value)| value)
+            .chain(named)
+            .collect()
+    }
+
+    // builds an array table from values (lua arrays start from 1)
+    pub fn array(values: Vec<LuaValue>) -> Self {
+        Self::Table(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| (Self::Number(index as f64 + 1.0), value))
+                .collect(),
+        )
+    }
+
+    // builds a table from string keys and values, nil values are left out
+    pub fn table(fields: Vec<(&str, LuaValue)>) -> Self {
+        Self::Table(
+            fields
+                .into_iter()
+                .filter(|(_, value)| *value != Self::Nil)
+                .map(|(key, value)| (Self::String(key.to_string()), value))
+                .collect(),
+        )
+    }
+}
+
+// Parser struct: reads a lua value from source text
+struct Parser<'a> {
+    source: &'a [u8],
+    position: usize,
+}
+
+// implementations for Parser struct
+impl<'a> Parser<'a> {
+    // returns an error describing what was expected at the current position
+    fn error(&self, expected: &str) -> ReaderError {
+        let line = self.source[..self.position.min(self.source.len())]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count()
+            + 1;
+        ReaderError::InvalidData(format!(
+            "expected {} on line {} of lua table",
+            expected, line
+        ))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.position).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        self.source[self.position..].starts_with(text.as_bytes())
+    }
+
+    // skips whitespace, line comments and block comments
+    fn skip_space(&mut self) {
+        loop {
+            while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+                self.position += 1;
+            }
+            if !self.starts_with("--") {
+                return;
+            }
+            self.position += 2;
+            if let Some(level) = self.long_bracket_level() {
+                self.long_string(level);
+            } else {
+                while self.peek().is_some_and(|byte| byte != b'\n') {
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    // returns the level of a long bracket ([[ or [==[) starting at the current position
+    fn long_bracket_level(&self) -> Option<usize> {
+        let rest = self.source.get(self.position..)?;
+        if rest.first() != Some(&b'[') {
+            return None;
+        }
+        let level = rest[1..].iter().take_while(|&&byte| byte == b'=').count();
+        (rest.get(level + 1) == Some(&b'[')).then_some(level)
+    }
+
+    // reads a long string, the current position is at its opening bracket
+    fn long_string(&mut self, level: usize) -> String {
+        self.position += level + 2;
+        if self.peek() == Some(b'\r') {
+            self.position += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.position += 1; // a new line right after the opening bracket is skipped
+        }
+
+        let closing = format!("]{}]", "=".repeat(level));
+        let start = self.position;
+        while self.position < self.source.len() && !self.starts_with(&closing) {
+            self.position += 1;
+        }
+        let text = String::from_utf8_lossy(&self.source[start..self.position]).to_string();
+        self.position = (self.position + closing.len()).min(self.source.len());
+        text
+    }
+
+    // reads a quoted string with escape sequences, the current position is at its opening quote
+    fn quoted_string(&mut self) -> Result<String, ReaderError> {
+        let quote = self.source[self.position];
+        self.position += 1;
+        let mut bytes: Vec<u8> = Vec::new();
+
+        loop {
+            let byte = self.peek().ok_or_else(|| self.error("end of string"))?;
+            self.position += 1;
+            if byte == quote {
+                break;
+            }
+            if byte != b'\\' {
+                bytes.push(byte);
+                continue;
+            }
+
+            let escape = self.peek().ok_or_else(|| self.error("escape sequence"))?;
+            self.position += 1;
+            match escape {
+                b'n' => bytes.push(b'\n'),
+                b't' => bytes.push(b'\t'),
+                b'r' => bytes.push(b'\r'),
+                b'a' => bytes.push(0x07),
+                b'b' => bytes.push(0x08),
+                b'f' => bytes.push(0x0c),
+                b'v' => bytes.push(0x0b),
+                b'\n' => bytes.push(b'\n'),
+                b'z' => {
+                    while self.peek().is_some_and(|byte| byte.is_ascii_whitespace()) {
+                        self.position += 1;
+                    }
+                }
+                b'x' => {
+                    let hex = self
+                        .source
+                        .get(self.position..self.position + 2)
+                        .and_then(|hex| std::str::from_utf8(hex).ok())
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        .ok_or_else(|| self.error("two hex digits"))?;
+                    bytes.push(hex);
+                    self.position += 2;
+                }
+                b'u' => {
+                    let end = self.source[self.position..]
+                        .iter()
+                        .position(|&byte| byte == b'}')
+                        .ok_or_else(|| self.error("closing brace of unicode escape"))?;
+                    let code =
+                        std::str::from_utf8(&self.source[self.position + 1..self.position + end])
+                            .ok()
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("unicode code point"))?;
+                    bytes.extend_from_slice(code.to_string().as_bytes());
+                    self.position += end + 1;
+                }
+                digit if digit.is_ascii_digit() => {
+                    // up to three decimal digits give a byte, koreader writes non ascii text this way
+                    let mut value = u32::from(digit - b'0');
+                    for _ in 0..2 {
+                        match self.peek() {
+                            Some(next) if next.is_ascii_digit() => {
+                                value = value * 10 + u32::from(next - b'0');
+                                self.position += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    bytes.push(u8::try_from(value).map_err(|_| self.error("byte escape"))?);
+                }
+                other => bytes.push(other), // \\, \" and \'
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    // reads a number, including hexadecimal integers
+    fn number(&mut self) -> Result<f64, ReaderError> {
+        let start = self.position;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        while self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'+' | b'-'))
+        {
+            // exponent signs are only part of the number after e
+            if matches!(self.peek(), Some(b'+' | b'-'))
+                && !matches!(self.source[self.position - 1], b'e' | b'E')
+            {
+                break;
+            }
+            self.position += 1;
+        }
+
+        let text = std::str::from_utf8(&self.source[start..self.position]).unwrap_or_default();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        let value = match digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            Some(hex) => i64::from_str_radix(hex, 16).ok().map(|value| value as f64),
+            None => digits.parse::<f64>().ok(),
+        }
+        .ok_or_else(|| self.error("number"))?;
+
+        Ok(if negative { -value } else { value })
+    }
+
+    // reads a name (identifier or keyword)
+    fn name(&mut self) -> &'a str {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.source[start..self.position]).unwrap_or_default()
+    }
+
+    // reads a table, the current position is at its opening brace
+    fn table(&mut self, depth: usize) -> Result<LuaValue, ReaderError> {
+        self.position += 1;
+        let mut entries: Vec<(LuaValue, LuaValue)> = Vec::new();
+        let mut next_index = 1.0;
+
+        loop {
+            self.skip_space();
+            match self.peek() {
+                None => return Err(self.error("closing brace")),
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(LuaValue::Table(entries));
+                }
+                Some(b'[') if self.long_bracket_level().is_none() => {
+                    self.position += 1;
+                    let key = self.value(depth + 1)?;
+                    self.skip_space();
+                    self.expect(b']')?;
+                    self.skip_space();
+                    self.expect(b'=')?;
+                    let value = self.value(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Some(byte) if byte.is_ascii_alphabetic() || byte == b'_' => {
+                    let start = self.position;
+                    let name = self.name();
+                    self.skip_space();
+                    if self.peek() == Some(b'=')
+                        && self.source.get(self.position + 1) != Some(&b'=')
+                    {
+                        self.position += 1;
+                        let value = self.value(depth + 1)?;
+                        entries.push((LuaValue::String(name.to_string()), value));
+                    } else {
+                        // a keyword value like true, read again as a positional value
+                        self.position = start;
+                        let value = self.value(depth + 1)?;
+                        entries.push((LuaValue::Number(next_index), value));
+                        next_index += 1.0;
+                    }
+                }
+                Some(_) => {
+                    let value = self.value(depth + 1)?;
+                    entries.push((LuaValue::Number(next_index), value));
+                    next_index += 1.0;
+                }
+            }
+
+            self.skip_space();
+            match self.peek() {
+                Some(b',' | b';') => self.position += 1,
+                Some(b'}') => {}
+                _ => return Err(self.error("comma or closing brace")),
+            }
+        }
+    }
+
+    // checks that the next byte is the given one and skips it
+    fn expect(&mut self, byte: u8) -> Result<(), ReaderError> {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("\"{}\"", byte as char)))
+        }
+    }
+
+    // reads any value
+    fn value(&mut self, depth: usize) -> Result<LuaValue, ReaderError> {
+        if depth > 64 {
+            return Err(ReaderError::InvalidData(
+                "lua table is nested too deeply".to_string(),
+            ));
+        }
+
+        self.skip_space();
+        match self.peek() {
+            Some(b'{') => self.table(depth),
+            Some(b'"' | b'\'') => Ok(LuaValue::String(self.quoted_string()?)),
+            Some(b'[') => match self.long_bracket_level() {
+                Some(level) => Ok(LuaValue::String(self.long_string(level))),
+                None => Err(self.error("value")),
+            },
+            Some(byte) if byte.is_ascii_digit() || matches!(byte, b'-' | b'.') => {
+                Ok(LuaValue::Number(self.number()?))
+            }
+            Some(byte) if byte.is_ascii_alphabetic() => match self.name() {
+                "true" => Ok(LuaValue::Bool(true)),
+                "false" => Ok(LuaValue::Bool(false)),
+                "nil" => Ok(LuaValue::Nil),
+                _ => Err(self.error("value")),
+            },
+            _ => Err(self.error("value")),
+        }
+    }
+}
+
+// function to parse a lua file returning a table, like the metadata files of koreader
+pub fn parse_lua(source: &str) -> Result<LuaValue, ReaderError> {
+    let mut parser = Parser {
+        source: source.trim_start_matches('\u{feff}').as_bytes(),
+        position: 0,
+    };
+
+    parser.skip_space();
+    if parser.starts_with("return") {
+        parser.position += "return".len();
+    }
+    let value = parser.value(0)?;
+    parser.skip_space();
+    if parser.position < parser.source.len() {
+        return Err(parser.error("end of file"));
+    }
+    Ok(value)
+}
+
+// function to quote a string for lua source
+fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\0' => quoted.push_str("\\0"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// function to write a number, integers without a fraction
+fn number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else if value.is_finite() {
+        format!("{}", value)
+    } else {
+        "0".to_string() // lua source has no literal for infinity and nan
+    }
+}
+
+// function to write a value with the given indentation level
+fn write_value(value: &LuaValue, level: usize, output: &mut String) {
+    match value {
+        LuaValue::Nil => output.push_str("nil"),
+        LuaValue::Bool(value) => output.push_str(if *value { "true" } else { "false" }),
+        LuaValue::Number(value) => output.push_str(&number(*value)),
+        LuaValue::String(text) => output.push_str(&quote(text)),
+        LuaValue::Table(entries) => {
+            // keys are sorted like koreader does, numbers first
+            let mut sorted: Vec<&(LuaValue, LuaValue)> = entries.iter().collect();
+            sorted.sort_by(|(a, _), (b, _)| match (a, b) {
+                (LuaValue::Number(a), LuaValue::Number(b)) => a.total_cmp(b),
+                (LuaValue::Number(_), _) => std::cmp::Ordering::Less,
+                (_, LuaValue::Number(_)) => std::cmp::Ordering::Greater,
+                (LuaValue::String(a), LuaValue::String(b)) => a.cmp(b),
+                _ => std::cmp::Ordering::Equal,
+            });
+
+            output.push_str("{\n");
+            let indent = "    ".repeat(level + 1);
+            for (key, value) in sorted {
+                output.push_str(&indent);
+                output.push('[');
+                write_value(key, level + 1, output);
+                output.push_str("] = ");
+                write_value(value, level + 1, output);
+                output.push_str(",\n");
+            }
+            output.push_str(&"    ".repeat(level));
+            output.push('}');
+        }
+    }
+}
+
+// function to write a table as a lua file in the format koreader writes its metadata files
+pub fn write_lua(value: &LuaValue) -> String {
+    let mut output = format!("{}\nreturn ", HEADER);
+    write_value(value, 0, &mut output);
+    output.push('\n');
+    output
+}
@@ -0,0 +1,835 @@
+/*
+    reader module:
+    markdown.rs renders markdown notes to xhtml, raw html in the source is shown as text so the output is always safe
+*/
+
+// importing crates and modules
+use crate::reader::sanitize::{is_safe_link, is_safe_resource};
+use crate::reader::text::html_to_text;
+use crate::reader::xml::{decode_entities, escape_xml};
+
+// Heading struct: a heading of the rendered document, used for the table of contents
+#[derive(Clone, Debug)]
+pub struct Heading {
+    pub level: usize,
+    pub id: String,
+    pub text: String,
+}
+
+// ListMarker struct: the marker starting a list item
+struct ListMarker {
+    ordered: bool,
+    delimiter: char, // '-', '*' or '+' for bullets, '.' or ')' for ordered lists
+    start: u64,
+    content: usize, // column where the item content starts
+}
+
+// Renderer struct: output and headings collected while rendering
+struct Renderer {
+    output: String,
+    headings: Vec<Heading>,
+}
+
+// function to count the leading spaces of a line
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// function to check if a line is empty or only whitespace
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// function to remove up to the given number of leading spaces
+fn unindent(line: &str, width: usize) -> &str {
+    &line[indent(line).min(width)..]
+}
+
+// function to parse an atx heading (# Title), returns its level and text
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let line = line.trim();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+
+    // closing hashes are removed if they are separated by a space
+    let text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with(' ') {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+// function to check if a line is a thematic break (---, *** or ___)
+fn is_thematic_break(line: &str) -> bool {
+    if indent(line) > 3 {
+        return false;
+    }
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && ['-', '*', '_'].contains(&chars[0]) && chars.iter().all(|c| *c == chars[0])
+}
+
+// function to parse the underline of a setext heading, returns the heading level
+fn setext_level(line: &str) -> Option<usize> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let line = line.trim();
+    if !line.is_empty() && line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !line.is_empty() && line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+// function to parse a code fence (``` or ~~~), returns the fence character, its length and the info string
+fn code_fence(line: &str) -> Option<(char, usize, &str)> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let line = line.trim();
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length = line.chars().take_while(|ch| *ch == c).count();
+    let info = line[length..].trim();
+    if length < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((c, length, info))
+}
+
+// function to parse a list item marker (-, *, + or 1. and 1))
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let spaces = indent(line);
+    if spaces > 3 {
+        return None;
+    }
+    let rest = &line[spaces..];
+
+    let (ordered, delimiter, start, marker_width) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (false, c, 1, 1),
+        _ => {
+            let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+            let delimiter = rest[digits..].chars().next()?;
+            if !(1..=9).contains(&digits) || !['.', ')'].contains(&delimiter) {
+                return None;
+            }
+            (true, delimiter, rest[..digits].parse().ok()?, digits + 1)
+        }
+    };
+
+    // the marker is followed by a space, or ends the line for an empty item
+    let after = &rest[marker_width..];
+    if !after.is_empty() && !after.starts_with(' ') {
+        return None;
+    }
+    let gap = indent(after);
+    let gap = if gap == 0 || gap > 4 || is_blank(after) {
+        1
+    } else {
+        gap
+    };
+    Some(ListMarker {
+        ordered,
+        delimiter,
+        start,
+        content: spaces + marker_width + gap,
+    })
+}
+
+// function to check if a line is a blockquote line
+fn is_blockquote(line: &str) -> bool {
+    indent(line) <= 3 && line.trim_start().starts_with('>')
+}
+
+// function to remove the blockquote marker and the space after it
+fn strip_blockquote(line: &str) -> &str {
+    let rest = line.trim_start();
+    let rest = rest.strip_prefix('>').unwrap_or(rest);
+    rest.strip_prefix(' ').unwrap_or(rest)
+}
+
+// function to split a table row into its cells, pipes can be escaped as \|
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cells.last_mut().unwrap().push(chars.next().unwrap_or('|'))
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+// function to parse the delimiter row of a table (| --- | :-: |), returns the alignment of every column
+fn table_alignments(line: &str) -> Option<Vec<Option<&'static str>>> {
+    if !line.contains('-') {
+        return None;
+    }
+    table_cells(line)
+        .iter()
+        .map(|cell| {
+            let left = cell.starts_with(':');
+            let right = cell.ends_with(':');
+            let dashes = cell.trim_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => Some("center"),
+                (false, true) => Some("right"),
+                (true, false) => Some("left"),
+                (false, false) => None,
+            })
+        })
+        .collect()
+}
+
+// function to check if a line starts a block which interrupts a paragraph
+fn interrupts_paragraph(line: &str) -> bool {
+    atx_heading(line).is_some()
+        || is_thematic_break(line)
+        || code_fence(line).is_some()
+        || is_blockquote(line)
+        || list_marker(line).is_some_and(|marker| {
+            !is_blank(&line[marker.content.min(line.len())..])
+                && (!marker.ordered || marker.start == 1)
+        })
+}
+
+// function to turn heading text into an id, e.g. "Cell Biology!" -> "cell-biology"
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+// function to find the end of a link label starting after '[', brackets can be nested
+fn label_end(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = text[start..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => depth += 1,
+            ']' if depth == 0 => return Some(start + offset),
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// function to parse a link destination with optional title, e.g. (url "title")
+// returns the url, the title and the index after the closing parenthesis
+fn link_destination(text: &str, start: usize) -> Option<(String, Option<String>, usize)> {
+    let rest = text[start..].strip_prefix('(')?;
+    let inner_start = start + 1 + (rest.len() - rest.trim_start().len());
+    let rest = &text[inner_start..];
+
+    // <url with spaces> or a url with balanced parentheses
+    let (url, mut index) = if let Some(bracketed) = rest.strip_prefix('<') {
+        let end = bracketed.find(['>', '\n'])?;
+        (bracketed[..end].to_string(), inner_start + end + 2)
+    } else {
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| match c {
+                '(' => {
+                    depth += 1;
+                    false
+                }
+                ')' if depth == 0 => true,
+                ')' => {
+                    depth -= 1;
+                    false
+                }
+                c => c.is_whitespace(),
+            })
+            .map(|(end, _)| end)?;
+        (rest[..end].to_string(), inner_start + end)
+    };
+
+    let after = &text[index..];
+    index += after.len() - after.trim_start().len();
+    let mut title = None;
+    if let Some(quote) = text[index..]
+        .chars()
+        .next()
+        .filter(|c| ['"', '\''].contains(c))
+    {
+        let end = text[index + 1..].find(quote)?;
+        title = Some(text[index + 1..index + 1 + end].to_string());
+        index += end + 2;
+        let after = &text[index..];
+        index += after.len() - after.trim_start().len();
+    }
+
+    text[index..]
+        .starts_with(')')
+        .then(|| (url, title, index + 1))
+}
+
+// function to find the closing delimiter of emphasis, e.g. the second ** of **bold**
+// runs opened inside the emphasis are skipped together with their closers
+fn emphasis_end(text: &str, start: usize, delimiter: char, length: usize) -> Option<usize> {
+    let width = delimiter.len_utf8();
+    let mut nested: Vec<usize> = Vec::new(); // lengths of runs opened inside
+    let mut from = start;
+    while let Some(offset) = text[from..].find(delimiter) {
+        let index = from + offset;
+        let run = text[index..]
+            .chars()
+            .take_while(|c| *c == delimiter)
+            .count();
+        let before = text[..index].chars().next_back();
+        let after = text[index + run * width..].chars().next();
+
+        // _ can't open or close inside a word
+        let word = |c: Option<char>| delimiter == '_' && c.is_some_and(|c| c.is_alphanumeric());
+        let closes = index > start && before.is_some_and(|c| !c.is_whitespace()) && !word(after);
+        let opens = after.is_some_and(|c| !c.is_whitespace()) && !word(before);
+
+        let mut available = run;
+        if closes {
+            while let Some(&inner) = nested.last() {
+                if available < inner {
+                    break;
+                }
+                available -= inner;
+                nested.pop();
+            }
+            if nested.is_empty() && available >= length {
+                return Some(index + (run - available) * width);
+            }
+        }
+        if opens && available == run {
+            nested.push(run);
+        }
+        from = index + run * width;
+    }
+    None
+}
+
+// function to render inline markdown (emphasis, code, links, images) as xhtml
+fn render_inline(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut index = 0;
+
+    while let Some(c) = text[index..].chars().next() {
+        let rest = &text[index..];
+        let previous = text[..index].chars().next_back();
+
+        match c {
+            // escaped punctuation and hard line breaks written as a backslash
+            '\\' => match rest[1..].chars().next() {
+                Some('\n') => {
+                    output.push_str("<br/>\n");
+                    index += 2;
+                }
+                Some(next) if next.is_ascii_punctuation() => {
+                    output.push_str(&escape_xml(&next.to_string()));
+                    index += 2;
+                }
+                _ => {
+                    output.push('\\');
+                    index += 1;
+                }
+            },
+            // code spans end with a run of backticks of the same length
+            '`' => {
+                let length = rest.chars().take_while(|c| *c == '`').count();
+                let run = &rest[..length];
+                let end = rest[length..]
+                    .match_indices(run)
+                    .map(|(offset, _)| length + offset)
+                    .find(|end| !rest[end + length..].starts_with('`'));
+                match end {
+                    Some(end) => {
+                        let code = rest[length..end].replace('\n', " ");
+                        let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                            Some(trimmed) if !trimmed.trim().is_empty() => trimmed.to_string(),
+                            _ => code,
+                        };
+                        output.push_str(&format!("<code>{}</code>", escape_xml(&code)));
+                        index += end + length;
+                    }
+                    None => {
+                        output.push_str(run);
+                        index += length;
+                    }
+                }
+            }
+            // images and links: ![alt](src) and [text](href "title")
+            '!' | '[' => {
+                let image = c == '!';
+                let link = if image && !rest[1..].starts_with('[') {
+                    None
+                } else {
+                    let label_start = index + if image { 2 } else { 1 };
+                    label_end(text, label_start).and_then(|end| {
+                        link_destination(text, end + 1)
+                            .map(|(url, title, after)| (&text[label_start..end], url, title, after))
+                    })
+                };
+
+                let Some((label, url, title, after)) = link else {
+                    output.push(c);
+                    index += 1;
+                    continue;
+                };
+                let title = title
+                    .map(|title| format!(" title=\"{}\"", escape_xml(&title)))
+                    .unwrap_or_default();
+                if image {
+                    let alt = html_to_text(&format!("<p>{}</p>", render_inline(label)));
+                    if is_safe_resource(&url) {
+                        output.push_str(&format!(
+                            "<img src=\"{}\" alt=\"{}\"{}/>",
+                            escape_xml(&url),
+                            escape_xml(&alt),
+                            title
+                        ));
+                    } else {
+                        output.push_str(&escape_xml(&alt));
+                    }
+                } else if is_safe_link(&url) {
+                    output.push_str(&format!(
+                        "<a href=\"{}\"{}>{}</a>",
+                        escape_xml(&url),
+                        title,
+                        render_inline(label)
+                    ));
+                } else {
+                    output.push_str(&render_inline(label));
+                }
+                index = after;
+            }
+            // autolinks: <https://example.com> and <name@example.com>
+            '<' => {
+                let link = rest[1..]
+                    .find(['>', ' ', '\n', '<'])
+                    .filter(|end| rest[1 + end..].starts_with('>'))
+                    .map(|end| &rest[1..1 + end])
+                    .filter(|url| url.contains(':') || url.contains('@'));
+                match link {
+                    Some(url) => {
+                        let href = if url.contains(':') {
+                            url.to_string()
+                        } else {
+                            format!("mailto:{}", url)
+                        };
+                        if is_safe_link(&href) {
+                            output.push_str(&format!(
+                                "<a href=\"{}\">{}</a>",
+                                escape_xml(&href),
+                                escape_xml(url)
+                            ));
+                        } else {
+                            output.push_str(&escape_xml(url));
+                        }
+                        index += url.len() + 2;
+                    }
+                    None => {
+                        output.push_str("&lt;");
+                        index += 1;
+                    }
+                }
+            }
+            // bare web addresses, trailing punctuation is not part of them
+            'h' if (rest.starts_with("http://") || rest.starts_with("https://"))
+                && !previous.is_some_and(|c| c.is_alphanumeric()) =>
+            {
+                let end = rest.find([' ', '\n', '<', '\t']).unwrap_or(rest.len());
+                let url =
+                    rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+                output.push_str(&format!("<a href=\"{0}\">{0}</a>", escape_xml(url)));
+                index += url.len();
+            }
+            // emphasis, strong emphasis and strikethrough
+            '*' | '_' | '~' => {
+                let run = rest.chars().take_while(|ch| *ch == c).count();
+                let next = rest[run..].chars().next();
+                let opens = next.is_some_and(|ch| !ch.is_whitespace())
+                    && !(c == '_' && previous.is_some_and(|ch| ch.is_alphanumeric()))
+                    && (c != '~' || run == 2)
+                    && run <= 3;
+                let end = opens
+                    .then(|| emphasis_end(text, index + run, c, run))
+                    .flatten();
+
+                match end {
+                    Some(end) => {
+                        let inner = render_inline(&text[index + run..end]);
+                        let (open, close) = match (c, run) {
+                            ('~', _) => ("<del>", "</del>"),
+                            (_, 1) => ("<em>", "</em>"),
+                            (_, 2) => ("<strong>", "</strong>"),
+                            _ => ("<em><strong>", "</strong></em>"),
+                        };
+                        output.push_str(&format!("{}{}{}", open, inner, close));
+                        index = end + run;
+                    }
+                    None => {
+                        output.push_str(&rest[..run]);
+                        index += run;
+                    }
+                }
+            }
+            // entities are decoded, xhtml only knows the xml ones
+            '&' => {
+                let entity = rest
+                    .find(';')
+                    .filter(|end| *end <= 10)
+                    .map(|end| &rest[..=end])
+                    .filter(|entity| decode_entities(entity) != *entity);
+                match entity {
+                    Some(entity) => {
+                        output.push_str(&escape_xml(&decode_entities(entity)));
+                        index += entity.len();
+                    }
+                    None => {
+                        output.push_str("&amp;");
+                        index += 1;
+                    }
+                }
+            }
+            // two spaces at the end of a line are a hard line break
+            '\n' => {
+                if output.ends_with("  ") {
+                    output.truncate(output.trim_end_matches(' ').len());
+                    output.push_str("<br/>");
+                }
+                output.push('\n');
+                index += 1;
+            }
+            c => {
+                output.push_str(&escape_xml(&c.to_string()));
+                index += c.len_utf8();
+            }
+        }
+    }
+
+    output
+}
+
+// implementations for Renderer struct
+impl Renderer {
+    // adds a heading with a unique id
+    fn heading(&mut self, level: usize, text: &str) {
+        let html = render_inline(text);
+        let plain = html_to_text(&format!("<h{0}>{1}</h{0}>", level, html));
+
+        let base = slug(&plain);
+        let mut id = base.clone();
+        let mut count = 1;
+        while self.headings.iter().any(|heading| heading.id == id) {
+            id = format!("{}-{}", base, count);
+            count += 1;
+        }
+
+        self.output
+            .push_str(&format!("<h{0} id=\"{1}\">{2}</h{0}>\n", level, id, html));
+        self.headings.push(Heading {
+            level,
+            id,
+            text: plain,
+        });
+    }
+
+    // renders a paragraph, paragraphs of tight lists are written without <p>
+    fn paragraph(&mut self, lines: &[&str], tight: bool) {
+        let text: Vec<&str> = lines.iter().map(|line| line.trim_start()).collect();
+        let html = render_inline(text.join("\n").trim_end());
+        if tight {
+            self.output.push_str(&html);
+        } else {
+            self.output.push_str(&format!("<p>{}</p>\n", html));
+        }
+    }
+
+    // renders a code block with an optional language
+    fn code_block(&mut self, lines: &[&str], language: &str) {
+        let class = language
+            .split_whitespace()
+            .next()
+            .map(|language| format!(" class=\"language-{}\"", escape_xml(language)))
+            .unwrap_or_default();
+        let mut code = lines.join("\n");
+        if !code.is_empty() {
+            code.push('\n');
+        }
+        self.output.push_str(&format!(
+            "<pre><code{}>{}</code></pre>\n",
+            class,
+            escape_xml(&code)
+        ));
+    }
+
+    // renders a table from its header, alignments and body rows
+    fn table(&mut self, header: &str, alignments: &[Option<&str>], rows: &[&str]) {
+        let row = |line: &str, cell: &str| {
+            let cells = table_cells(line);
+            let mut html = String::from("<tr>");
+            for (column, alignment) in alignments.iter().enumerate() {
+                let style = alignment
+                    .map(|alignment| format!(" style=\"text-align: {}\"", alignment))
+                    .unwrap_or_default();
+                let content = cells.get(column).map(String::as_str).unwrap_or_default();
+                html.push_str(&format!(
+                    "<{0}{1}>{2}</{0}>",
+                    cell,
+                    style,
+                    render_inline(content)
+                ));
+            }
+            html.push_str("</tr>\n");
+            html
+        };
+
+        self.output.push_str("<table>\n<thead>\n");
+        self.output.push_str(&row(header, "th"));
+        self.output.push_str("</thead>\n");
+        if !rows.is_empty() {
+            self.output.push_str("<tbody>\n");
+            for line in rows {
+                self.output.push_str(&row(line, "td"));
+            }
+            self.output.push_str("</tbody>\n");
+        }
+        self.output.push_str("</table>\n");
+    }
+
+    // renders a list starting at lines[start], returns the index of the first line after it
+    fn list(&mut self, lines: &[&str], start: usize) -> usize {
+        let Some(first) = list_marker(lines[start]) else {
+            return start + 1;
+        };
+
+        // collect the lines of every item, without their indentation
+        let mut items: Vec<Vec<String>> = Vec::new();
+        let mut loose = false;
+        let mut blank_before = false;
+        let mut content = first.content;
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i];
+            let marker = list_marker(line).filter(|marker| {
+                marker.ordered == first.ordered
+                    && marker.delimiter == first.delimiter
+                    && indent(line) < content
+            });
+
+            if let Some(marker) = marker {
+                if blank_before && !items.is_empty() {
+                    loose = true;
+                }
+                let text = line.get(marker.content..).unwrap_or_default();
+                items.push(vec![text.to_string()]);
+                content = marker.content;
+                blank_before = false;
+            } else if is_blank(line) {
+                items.last_mut().unwrap().push(String::new());
+                blank_before = true;
+            } else if indent(line) >= content {
+                if blank_before {
+                    loose = true;
+                }
+                items.last_mut().unwrap().push(line[content..].to_string());
+                blank_before = false;
+            } else if !blank_before && !interrupts_paragraph(line) {
+                // lazy continuation of the last paragraph
+                items
+                    .last_mut()
+                    .unwrap()
+                    .push(line.trim_start().to_string());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+
+        // blank lines at the end belong to the document, not the list (the first line of an item is its marker line)
+        let last = items.last_mut().unwrap();
+        while last.len() > 1 && last.last().is_some_and(|line| line.is_empty()) {
+            last.pop();
+            i -= 1;
+        }
+        // a blank line between blocks of the same item also makes the list loose
+        loose = loose
+            || items.iter().any(|item| {
+                let end = item.iter().rposition(|line| !line.is_empty()).unwrap_or(0);
+                item[..end].iter().any(|line| line.is_empty())
+            });
+
+        let (open, close) = match (first.ordered, first.start) {
+            (true, 1) => ("<ol>".to_string(), "</ol>"),
+            (true, start) => (format!("<ol start=\"{}\">", start), "</ol>"),
+            (false, _) => ("<ul>".to_string(), "</ul>"),
+        };
+        self.output.push_str(&open);
+        self.output.push('\n');
+        for mut item in items {
+            // task list items: - [ ] todo and - [x] done
+            if let Some(first) = item.first_mut() {
+                for (marker, symbol) in [("[ ] ", "☐ "), ("[x] ", "☑ "), ("[X] ", "☑ ")] {
+                    if let Some(rest) = first.strip_prefix(marker) {
+                        *first = format!("{}{}", symbol, rest);
+                    }
+                }
+            }
+
+            self.output.push_str("<li>");
+            let lines: Vec<&str> = item.iter().map(String::as_str).collect();
+            self.blocks(&lines, !loose);
+            self.output.push_str("</li>\n");
+        }
+        self.output.push_str(close);
+        self.output.push('\n');
+        i
+    }
+
+    // renders block elements (headings, paragraphs, lists, quotes, code and tables)
+    fn blocks(&mut self, lines: &[&str], tight: bool) {
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+
+            if is_blank(line) {
+                i += 1;
+            } else if let Some((fence, length, info)) = code_fence(line) {
+                let fence_indent = indent(line);
+                let mut code = Vec::new();
+                i += 1;
+                while i < lines.len() {
+                    let closing = code_fence(lines[i])
+                        .is_some_and(|(c, l, info)| c == fence && l >= length && info.is_empty());
+                    if closing {
+                        i += 1;
+                        break;
+                    }
+                    code.push(unindent(lines[i], fence_indent));
+                    i += 1;
+                }
+                self.code_block(&code, info);
+            } else if indent(line) >= 4 {
+                let mut code = Vec::new();
+                while i < lines.len() && (is_blank(lines[i]) || indent(lines[i]) >= 4) {
+                    code.push(unindent(lines[i], 4));
+                    i += 1;
+                }
+                while code.last().is_some_and(|line| is_blank(line)) {
+                    code.pop();
+                }
+                self.code_block(&code, "");
+            } else if let Some((level, text)) = atx_heading(line) {
+                self.heading(level, text);
+                i += 1;
+            } else if is_thematic_break(line) {
+                self.output.push_str("<hr/>\n");
+                i += 1;
+            } else if is_blockquote(line) {
+                let mut quote = Vec::new();
+                while i < lines.len() && !is_blank(lines[i]) {
+                    if is_blockquote(lines[i]) {
+                        quote.push(strip_blockquote(lines[i]));
+                    } else if interrupts_paragraph(lines[i]) {
+                        break;
+                    } else {
+                        quote.push(lines[i]); // lazy continuation
+                    }
+                    i += 1;
+                }
+                self.output.push_str("<blockquote>\n");
+                self.blocks(&quote, false);
+                self.output.push_str("</blockquote>\n");
+            } else if list_marker(line).is_some() {
+                i = self.list(lines, i);
+            } else if let Some(alignments) = lines
+                .get(i + 1)
+                .filter(|_| line.contains('|'))
+                .and_then(|next| table_alignments(next))
+                .filter(|alignments| alignments.len() == table_cells(line).len())
+            {
+                let mut rows = Vec::new();
+                i += 2;
+                while i < lines.len() && !is_blank(lines[i]) && lines[i].contains('|') {
+                    rows.push(lines[i]);
+                    i += 1;
+                }
+                self.table(line, &alignments, &rows);
+            } else {
+                let mut paragraph = vec![line];
+                i += 1;
+                while i < lines.len() && !is_blank(lines[i]) {
+                    if let Some(level) = setext_level(lines[i]) {
+                        let text: Vec<&str> = paragraph.iter().map(|line| line.trim()).collect();
+                        self.heading(level, &text.join(" "));
+                        paragraph.clear();
+                        i += 1;
+                        break;
+                    }
+                    if interrupts_paragraph(lines[i]) {
+                        break;
+                    }
+                    paragraph.push(lines[i]);
+                    i += 1;
+                }
+                if !paragraph.is_empty() {
+                    self.paragraph(&paragraph, tight);
+                }
+            }
+        }
+    }
+}
+
+// function to render markdown to xhtml, returns the body content and the headings
+pub fn render_markdown(source: &str) -> (String, Vec<Heading>) {
+    // tabs are expanded so indentation can be counted in spaces
+    let source = source
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\t', "    ");
+    let lines: Vec<&str> = source.lines().collect();
+
+    let mut renderer = Renderer {
+        output: String::new(),
+        headings: Vec::new(),
+    };
+    renderer.blocks(&lines, false);
+    (renderer.output, renderer.headings)
+}
@@ -2,31 +2,147 @@
     reader module: contains functions related to pdf/epub reader
 */
 
+pub mod annotations; // annotation import from kindle, koreader and calibre, koreader export
+pub mod bookmarks; // named bookmarks of books
+pub mod cache; // cache of opened epubs
+pub mod citation; // apa, mla, chicago and bibtex citations
+pub mod comic; // comic book archives (cbz)
+pub mod cover; // cover extraction and thumbnail cache
+pub mod dictionary; // offline stardict dictionary lookup
+pub mod document; // plain text, markdown and html documents packed as epubs
+pub mod epub; // epub package (opf) parsing
+pub mod excerpt; // chapter and page range text for the study buddy
+pub mod export; // highlight export to markdown, html, csv and json
+pub mod highlights; // highlight model and storage
+pub mod index; // library-wide full-text search index
+pub mod library; // library catalog and shelves
+pub mod lua; // lua tables of koreader metadata files
+pub mod markdown; // markdown to xhtml rendering
+pub mod office; // docx and odt to xhtml conversion
+pub mod pdf; // pdf reading
+pub mod pdf_info; // pdf document info, outline and page labels
+pub mod pdf_text; // pdf text extraction
+pub mod position; // last reading position of books
+pub mod resources; // book: protocol serving library books to the webview
+pub mod sanitize; // removal of scripts and remote content from epub files
+pub mod search; // full-text search
+pub mod stats; // reading sessions and statistics
+pub mod text; // plain text of xhtml documents
+pub mod toc; // table of contents parsing
+pub mod validate; // epub structure checks
+pub mod xml; // xml/xhtml tokenizer
+
 // importing crates and modules
 use crate::functions::{read_data, save_data};
+use crate::reader::index::{update_in_background, SearchIndex};
 use font_loader::system_fonts;
 use regex::Regex;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Error as SerdeJsonError;
 use std::io::Read;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs::File, io::Write};
-use tauri::Error as TauriError;
+use tauri::ipc::InvokeError;
 use tauri::WebviewWindow;
 use tauri_plugin_dialog::{DialogExt, FileResponse};
+use thiserror::Error;
 use zip::{
+    result::ZipError,
     write::{ExtendedFileOptions, FileOptions},
-    CompressionMethod, ZipArchive, ZipWriter,
+    ZipWriter,
 };
 
+// Custom Error enum for reader commands
+#[derive(Debug, Error)]
+pub enum ReaderError {
+    #[error("Standard error: {0}")]
+    StdError(#[from] std::io::Error),
+
+    #[error("Zip error: {0}")]
+    ZipError(#[from] ZipError),
+
+    #[error("JSON parsing error: {0}")]
+    SerdeJsonError(#[from] SerdeJsonError),
+
+    #[error("PDF error: {0}")]
+    PdfError(#[from] lopdf::Error),
+
+    #[error("Image error: {0}")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+}
+
+// OpenedFile struct: the file picked in the dialog box with its library entry
+#[derive(Serialize, Clone, Debug)]
+pub struct OpenedFile {
+    #[serde(flatten)]
+    pub file: FileResponse,
+    pub book: library::Book,
+}
+
+// implementing InvokeError for ReaderError enum
+impl From<ReaderError> for InvokeError {
+    fn from(error: ReaderError) -> Self {
+        InvokeError::from(format!("{}", error))
+    }
+}
+
+// function to read a json data file, returns the default value if the file does not exist yet
+pub fn read_json<T: DeserializeOwned + Default>(pathname: &str) -> Result<T, ReaderError> {
+    match read_data(pathname) {
+        Ok(data) => Ok(serde_json::from_str(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// function to save a value as json data file
+pub fn save_json<T: Serialize + ?Sized>(pathname: &str, value: &T) -> Result<(), ReaderError> {
+    save_data(pathname, &serde_json::to_string(value)?)?;
+    Ok(())
+}
+
 // open_file_dialog command: opens a file selection dialog box and returns the response when invoked
+// the picked file is registered in the library, files which can't be read are reported as errors
+// books only enter the library through this dialog, so the webview can't have other files served to it
 #[tauri::command]
-pub fn open_file_dialog(window: WebviewWindow) -> Option<FileResponse> {
+pub fn open_file_dialog(
+    window: WebviewWindow,
+    state: tauri::State<Arc<Mutex<SearchIndex>>>,
+) -> Result<Option<OpenedFile>, ReaderError> {
     let dialog = window
         .dialog()
         .file()
-        .add_filter("Readables", &vec!["pdf", "epub"])
+        .add_filter(
+            "Readables",
+            &[
+                "pdf", "epub", "txt", "md", "markdown", "html", "htm", "xhtml", "cbz", "cbr",
+                "docx", "odt",
+            ],
+        )
         .blocking_pick_file();
 
-    return dialog;
+    let Some(file) = dialog else {
+        return Ok(None); // dialog was closed without picking a file
+    };
+    let path = file.path.to_str().ok_or_else(|| {
+        ReaderError::InvalidData(format!("{} is not a valid path", file.path.display()))
+    })?;
+
+    // register the picked file in the library
+    let book = library::register_and_open(path)?;
+    update_in_background(&state);
+
+    Ok(Some(OpenedFile { file, book }))
 }
 
 // load_installed_fonts command: returns name of all the fonts installed on system
@@ -38,82 +154,56 @@ pub fn load_installed_fonts() -> Vec<String> {
 
 // e_pub_data_get command: reads an epub file and return certain data when invoked
 #[tauri::command]
-pub fn e_pub_data_get(path: &str) -> Result<Vec<String>, TauriError> {
-    let opf_data = read_opf_file(path).map_err(TauriError::from)?; // read content.opf
+pub fn e_pub_data_get(path: &str) -> Result<Vec<String>, ReaderError> {
+    let opf_data = read_opf_file(path)?; // read content.opf
 
     let title = extract_title(&opf_data).unwrap_or_default(); // extract title
 
     // extract uid (unique id to load highlights) or generate and save a new one if not found
-    let uid = extract_uid(&opf_data).unwrap_or_else(|| {
-        let new_uid = generate_uid();
-        let updated_opf_data = update_opf_with_uid(&opf_data, &new_uid);
+    let uid = ensure_uid(path, &opf_data).unwrap_or_else(generate_uid);
 
-        save_opf_file(path, updated_opf_data).unwrap_or_default(); // Save the updated OPF data
-        new_uid
-    });
-
-    // read hidhlights file based on the uid
-    let highlights =
-        read_data(format!("highlights/{}.json", uid).as_str()).unwrap_or_else(|_| "[]".to_string());
+    // read highlights based on the uid
+    let highlights = serde_json::to_string(&highlights::load_highlights(&uid)?)?;
 
     Ok(vec![uid, title, highlights])
 }
 
-// e_pub_highlights_save command: saves the highlight data as json file when invoked
-#[tauri::command]
-pub fn e_pub_highlight_save(uid: String, data: String) {
-    println!("saving data: {}", data);
-    save_data(format!("highlights/{}.json", uid).as_str(), &data).unwrap_or_default();
-}
-
 // function to read content.opf data from an epub file
-fn read_opf_file(path: &str) -> Result<String, std::io::Error> {
-    let file = File::open(path)?; // read the epub file
-    let mut archive = ZipArchive::new(file)?; // unzips the epub file
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        if file.name().ends_with("content.opf") {
-            // return the file data with name "content.opf"
-            let mut opf_data = String::new();
-            file.read_to_string(&mut opf_data)?;
-            return Ok(opf_data);
-        }
-    }
+fn read_opf_file(path: &str) -> Result<String, ReaderError> {
+    let mut archive = cache::open_cached_archive(path)?; // the central directory is parsed once per book
 
-    Err(std::io::Error::new(
-        std::io::ErrorKind::NotFound,
-        "content.opf not found",
-    ))
+    // the package document is found through META-INF/container.xml, like the viewers do
+    let name = epub::find_opf_path(&mut archive)?;
+    epub::read_entry_string(&mut archive, &name)
 }
 
 // function to save content.opf data to an epub file
 fn save_opf_file(path: &str, updated_opf_data: String) -> Result<(), Box<dyn std::error::Error>> {
-    // Open the existing epub file
-    let file = File::open(path)?;
-    let mut archive = ZipArchive::new(file)?;
+    // documents are only read as epubs, they must never be overwritten with one
+    if library::BookFormat::from_path(path) != Some(library::BookFormat::Epub) {
+        return Err(format!("{} is not an epub file", path).into());
+    }
+
+    // Open the existing epub file and find the package document it was read from
+    let mut archive = cache::open_cached_archive(path)?;
+    let opf_path = epub::find_opf_path(&mut archive)?;
 
     // Create a temporary file to write the new epub contents
     let temp_file_path = format!("{}.tmp", path);
     let temp_file = File::create(&temp_file_path)?;
     let mut zip_writer = ZipWriter::new(temp_file);
 
-    // Write the updated content.opf to the epub archive
-    zip_writer.start_file::<_, ExtendedFileOptions>(
-        "content.opf",
-        FileOptions::default().compression_method(CompressionMethod::Stored),
-    )?;
-    zip_writer.write_all(updated_opf_data.as_bytes())?;
-
-    // Copy the remaining files from the existing ZIP archive
+    // Copy the files in their order (mimetype must stay first), with the updated package document
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
         let name = file.name().to_string();
-        if name != "content.opf" {
-            zip_writer.start_file::<_, ExtendedFileOptions>(
-                name,
-                FileOptions::default().compression_method(file.compression()),
-            )?;
+        zip_writer.start_file::<_, ExtendedFileOptions>(
+            name.as_str(),
+            FileOptions::default().compression_method(file.compression()),
+        )?;
+        if name == opf_path {
+            zip_writer.write_all(updated_opf_data.as_bytes())?;
+        } else {
             let mut buffer = Vec::new();
             file.read_to_end(&mut buffer)?;
             zip_writer.write_all(&buffer)?;
@@ -123,6 +213,10 @@ fn save_opf_file(path: &str, updated_opf_data: String) -> Result<(), Box<dyn std
     // Finalize the ZIP file
     zip_writer.finish()?;
 
+    // Close the cached archive, the file can't be replaced while it is open on windows
+    drop(archive);
+    cache::evict(path);
+
     // Replace the original file with the temporary file
     std::fs::remove_file(path)?;
     std::fs::rename(temp_file_path, path)?;
@@ -149,6 +243,16 @@ fn extract_uid(opf_data: &str) -> Option<String> {
         .map(|m| m.as_str().to_string())
 }
 
+// function to extract uid from content.opf data, or generate and save a new one if not found (None if it can't be saved)
+fn ensure_uid(path: &str, opf_data: &str) -> Option<String> {
+    extract_uid(opf_data).or_else(|| {
+        let new_uid = generate_uid();
+        let updated_opf_data = update_opf_with_uid(opf_data, &new_uid);
+
+        save_opf_file(path, updated_opf_data).ok().map(|_| new_uid) // Save the updated OPF data
+    })
+}
+
 // function to generate new uid (based on current timestamp)
 fn generate_uid() -> String {
     SystemTime::now()
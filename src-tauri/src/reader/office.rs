@@ -0,0 +1,798 @@
+/*
+    reader module:
+    office.rs converts word (docx) and opendocument (odt) files to xhtml,
+    only headings, paragraphs, lists, tables, links and images are written so the output is always safe
+*/
+
+// importing crates and modules
+use crate::reader::epub::{open_archive, read_entry_bytes, read_entry_string};
+use crate::reader::markdown::{slug, Heading};
+use crate::reader::sanitize::is_safe_link;
+use crate::reader::text::html_to_text;
+use crate::reader::xml::{
+    decode_entities, escape_xml, resolve_path, tokenize, Tag, TagKind, Token,
+};
+use crate::reader::ReaderError;
+use std::collections::HashMap;
+use std::fs::File;
+use zip::ZipArchive;
+
+// deepest heading level of xhtml, deeper outline levels are written as h6
+const MAX_HEADING_LEVEL: usize = 6;
+
+// deepest list level of word documents (w:ilvl is 0 to 8), deeper levels are written at this level
+const MAX_LIST_LEVEL: usize = 8;
+
+// odt elements whose content is not part of the text, e.g. comments and footnotes
+const ODT_SKIPPED_ELEMENTS: [&str; 7] = [
+    "annotation",
+    "note",
+    "tracked-changes",
+    "sequence-decls",
+    "title",
+    "desc",
+    "notes",
+];
+
+// OfficeDocument struct: a converted document with the images it shows
+pub struct OfficeDocument {
+    pub title: Option<String>,
+    pub body: String,
+    pub headings: Vec<Heading>,
+    pub images: Vec<(String, Vec<u8>)>, // path and data, images keep their path of the source archive
+}
+
+// RunFormat struct: character formatting of a run of text
+#[derive(Clone, Copy, Default, PartialEq)]
+struct RunFormat {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strike: bool,
+    superscript: bool,
+    subscript: bool,
+}
+
+// implementations for RunFormat struct
+impl RunFormat {
+    // wraps html in the elements of the format
+    fn wrap(self, html: &str) -> String {
+        let elements = [
+            (self.bold, "strong"),
+            (self.italic, "em"),
+            (self.underline, "u"),
+            (self.strike, "s"),
+            (self.superscript, "sup"),
+            (self.subscript, "sub"),
+        ];
+
+        let mut wrapped = html.to_string();
+        for (_, name) in elements.iter().filter(|(set, _)| *set) {
+            wrapped = format!("<{0}>{1}</{0}>", name, wrapped);
+        }
+        wrapped
+    }
+}
+
+// Writer struct: output collected while converting
+#[derive(Default)]
+struct Writer {
+    body: String,
+    headings: Vec<Heading>,
+    images: Vec<(String, Vec<u8>)>,
+    lists: Vec<bool>, // open lists, true for ordered ones, the last item of every open list is still open
+}
+
+// implementations for Writer struct
+impl Writer {
+    // closes open lists until the given depth is left
+    fn close_lists(&mut self, depth: usize) {
+        while self.lists.len() > depth {
+            let ordered = self.lists.pop().unwrap_or_default();
+            self.body.push_str(if ordered {
+                "</li></ol>\n"
+            } else {
+                "</li></ul>\n"
+            });
+        }
+    }
+
+    // writes a paragraph, empty paragraphs used as spacing are dropped
+    fn paragraph(&mut self, html: &str) {
+        self.close_lists(0);
+        if !html.trim().is_empty() {
+            self.body.push_str(&format!("<p>{}</p>\n", html.trim()));
+        }
+    }
+
+    // writes a heading with a unique id, empty headings are dropped
+    fn heading(&mut self, level: usize, html: &str) {
+        let level = level.clamp(1, MAX_HEADING_LEVEL);
+        let text = html_to_text(&format!("<h{0}>{1}</h{0}>", level, html));
+        let text = text.trim();
+        if text.is_empty() {
+            return self.paragraph(html);
+        }
+        self.close_lists(0);
+
+        let base = slug(text);
+        let mut id = base.clone();
+        let mut count = 1;
+        while self.headings.iter().any(|heading| heading.id == id) {
+            id = format!("{}-{}", base, count);
+            count += 1;
+        }
+
+        self.body.push_str(&format!(
+            "<h{0} id=\"{1}\">{2}</h{0}>\n",
+            level,
+            id,
+            html.trim()
+        ));
+        self.headings.push(Heading {
+            level,
+            id,
+            text: text.to_string(),
+        });
+    }
+
+    // writes an item of a list at the given depth (1 for top level lists)
+    fn list_item(&mut self, depth: usize, ordered: bool, html: &str) {
+        let depth = depth.clamp(1, MAX_LIST_LEVEL + 1);
+        self.close_lists(depth);
+        if self.lists.len() == depth {
+            if self.lists[depth - 1] == ordered {
+                self.body.push_str("</li>\n");
+            } else {
+                self.close_lists(depth - 1);
+            }
+        }
+        while self.lists.len() < depth {
+            self.body.push_str(if ordered { "<ol>" } else { "<ul>" });
+            self.lists.push(ordered);
+            // skipped levels get an empty item holding the deeper list
+            if self.lists.len() < depth {
+                self.body.push_str("<li>");
+            }
+        }
+        self.body.push_str(&format!("<li>{}", html.trim()));
+    }
+
+    // reads an image of the source archive and returns the element showing it
+    fn image(&mut self, archive: &mut ZipArchive<File>, path: &str, alt: &str) -> String {
+        if !self.images.iter().any(|(name, _)| name == path) {
+            match read_entry_bytes(archive, path) {
+                Ok(data) => self.images.push((path.to_string(), data)),
+                Err(_) => return String::new(), // broken images are left out
+            }
+        }
+        format!(
+            "<img src=\"{}\" alt=\"{}\"/>",
+            escape_xml(path),
+            escape_xml(alt)
+        )
+    }
+
+    // finishes the document
+    fn finish(mut self, title: Option<String>) -> OfficeDocument {
+        self.close_lists(0);
+        OfficeDocument {
+            title,
+            body: self.body,
+            headings: self.headings,
+            images: self.images,
+        }
+    }
+}
+
+// function to check if a docx on/off property like <w:b/> or <w:b w:val="0"/> is on
+fn docx_toggle(tag: &Tag) -> bool {
+    !matches!(
+        tag.attr("w:val").as_deref(),
+        Some("0" | "false" | "off" | "none")
+    )
+}
+
+// function to get the text of the first element with the given name, e.g. the title of docProps/core.xml
+fn element_text(data: &str, name: &str) -> Option<String> {
+    let mut text: Option<String> = None;
+    for token in tokenize(data) {
+        match (token, text.as_mut()) {
+            (Token::Tag(tag), None) if tag.name == name && tag.is_open() => {
+                text = Some(String::new())
+            }
+            (Token::Tag(tag), Some(_)) if tag.name == name && tag.is_close() => break,
+            (Token::Text(raw), Some(text)) => text.push_str(&decode_entities(raw)),
+            _ => {}
+        }
+    }
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+// function to read the relationships of a docx part (id -> target and whether it is outside the archive)
+fn docx_relationships(
+    archive: &mut ZipArchive<File>,
+    path: &str,
+) -> HashMap<String, (String, bool)> {
+    let Ok(data) = read_entry_string(archive, path) else {
+        return HashMap::new();
+    };
+
+    tokenize(&data)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Tag(tag) if tag.name == "relationship" && !tag.is_close() => {
+                let external = tag.attr("targetmode").as_deref() == Some("External");
+                let target = tag.attr("target")?;
+                let target = if external {
+                    target
+                } else {
+                    resolve_path("word", &target)
+                };
+                Some((tag.attr("id")?, (target, external)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// function to read the heading level of docx paragraph styles, from their name or outline level
+fn docx_heading_styles(data: &str) -> HashMap<String, usize> {
+    let mut levels = HashMap::new();
+    let mut style: Option<String> = None;
+
+    for token in tokenize(data) {
+        let Token::Tag(tag) = token else {
+            continue;
+        };
+        match (tag.name.as_str(), tag.kind) {
+            ("style", TagKind::Open) => style = tag.attr("w:styleid"),
+            ("style", _) => style = None,
+            ("name", _) => {
+                let name = tag.attr("w:val").unwrap_or_default().to_ascii_lowercase();
+                let level = match name.strip_prefix("heading ") {
+                    Some(level) => level.trim().parse().ok(),
+                    None => (name == "title").then_some(1),
+                };
+                if let (Some(style), Some(level)) = (style.clone(), level) {
+                    levels.insert(style, level);
+                }
+            }
+            ("outlinelvl", _) => {
+                let level = tag
+                    .attr("w:val")
+                    .and_then(|level| level.parse::<usize>().ok());
+                if let (Some(style), Some(level)) = (style.clone(), level.filter(|l| *l < 9)) {
+                    levels.entry(style).or_insert(level + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    levels
+}
+
+// function to read which docx lists are numbered (num id and level -> ordered)
+fn docx_numbering(data: &str) -> HashMap<(String, usize), bool> {
+    let mut abstract_levels: HashMap<(String, usize), bool> = HashMap::new();
+    let mut nums: Vec<(String, String)> = Vec::new(); // num id and abstract num id
+    let (mut abstract_id, mut level, mut num_id) = (None, 0, None);
+
+    for token in tokenize(data) {
+        let Token::Tag(tag) = token else {
+            continue;
+        };
+        match (tag.name.as_str(), tag.kind) {
+            ("abstractnum", TagKind::Open) => abstract_id = tag.attr("w:abstractnumid"),
+            ("lvl", TagKind::Open) => {
+                level = tag
+                    .attr("w:ilvl")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(0)
+            }
+            ("numfmt", _) => {
+                if let Some(abstract_id) = abstract_id.clone() {
+                    let format = tag.attr("w:val").unwrap_or_default();
+                    abstract_levels.insert(
+                        (abstract_id, level),
+                        !matches!(format.as_str(), "bullet" | "none"),
+                    );
+                }
+            }
+            ("num", TagKind::Open) => num_id = tag.attr("w:numid"),
+            ("abstractnumid", _) => {
+                if let (Some(num_id), Some(abstract_id)) = (num_id.clone(), tag.attr("w:val")) {
+                    nums.push((num_id, abstract_id));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut ordered = HashMap::new();
+    for (num_id, abstract_id) in nums {
+        for ((id, level), numbered) in abstract_levels.iter() {
+            if *id == abstract_id {
+                ordered.insert((num_id.clone(), *level), *numbered);
+            }
+        }
+    }
+    ordered
+}
+
+// DocxParagraph struct: a docx paragraph being read
+#[derive(Default)]
+struct DocxParagraph {
+    style: Option<String>,
+    outline_level: Option<usize>,
+    num_id: Option<String>,
+    list_level: usize,
+    html: String,
+}
+
+// function to convert a word document (docx) to xhtml
+pub fn convert_docx(path: &str) -> Result<OfficeDocument, ReaderError> {
+    let mut archive = open_archive(path)?;
+    let document = read_entry_string(&mut archive, "word/document.xml")?;
+    let relationships = docx_relationships(&mut archive, "word/_rels/document.xml.rels");
+    let heading_styles = read_entry_string(&mut archive, "word/styles.xml")
+        .map(|data| docx_heading_styles(&data))
+        .unwrap_or_default();
+    let numbering = read_entry_string(&mut archive, "word/numbering.xml")
+        .map(|data| docx_numbering(&data))
+        .unwrap_or_default();
+    let title = read_entry_string(&mut archive, "docProps/core.xml")
+        .ok()
+        .and_then(|data| element_text(&data, "title"));
+
+    let mut writer = Writer::default();
+    let mut paragraph: Option<DocxParagraph> = None;
+    let mut depth: usize = 0; // paragraphs of text boxes are nested in the paragraph holding the box
+    let mut format = RunFormat::default();
+    let (mut in_paragraph_properties, mut in_run_properties, mut in_text) = (false, false, false);
+    let mut links: Vec<bool> = Vec::new(); // open hyperlinks, true if an <a> was written for them
+    let mut alt = String::new();
+
+    for token in tokenize(&document) {
+        let tag = match token {
+            Token::Text(raw) => {
+                if let (true, Some(paragraph)) = (in_text, paragraph.as_mut()) {
+                    paragraph
+                        .html
+                        .push_str(&format.wrap(&escape_xml(&decode_entities(raw))));
+                }
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        match (tag.name.as_str(), tag.kind) {
+            ("p", TagKind::Open) => {
+                depth += 1;
+                if depth == 1 {
+                    paragraph = Some(DocxParagraph::default());
+                }
+            }
+            ("p", TagKind::Close) => {
+                depth = depth.saturating_sub(1); // a stray closing tag does not open a paragraph
+                if depth > 0 {
+                    continue;
+                }
+                let Some(paragraph) = paragraph.take() else {
+                    continue;
+                };
+                let level = paragraph.outline_level.or_else(|| {
+                    let style = paragraph.style.as_deref()?;
+                    heading_styles.get(style).copied().or_else(|| {
+                        // documents without styles.xml still use the built-in style ids
+                        style
+                            .to_ascii_lowercase()
+                            .strip_prefix("heading")?
+                            .parse()
+                            .ok()
+                    })
+                });
+                match (level, paragraph.num_id.as_deref()) {
+                    (Some(level), _) => writer.heading(level, &paragraph.html),
+                    (None, Some(num_id)) if num_id != "0" => {
+                        let key = (num_id.to_string(), paragraph.list_level);
+                        let ordered = numbering.get(&key).copied().unwrap_or(false);
+                        writer.list_item(paragraph.list_level + 1, ordered, &paragraph.html);
+                    }
+                    _ => writer.paragraph(&paragraph.html),
+                }
+            }
+            ("ppr", TagKind::Open) => in_paragraph_properties = true,
+            ("ppr", TagKind::Close) => in_paragraph_properties = false,
+            ("rpr", TagKind::Open) => in_run_properties = true,
+            ("rpr", TagKind::Close) => in_run_properties = false,
+            ("pstyle" | "outlinelvl" | "ilvl" | "numid", _) if in_paragraph_properties => {
+                let (Some(paragraph), Some(value)) = (paragraph.as_mut(), tag.attr("w:val")) else {
+                    continue;
+                };
+                match tag.name.as_str() {
+                    "pstyle" => paragraph.style = Some(value),
+                    "outlinelvl" => {
+                        paragraph.outline_level = value
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|l| *l < 9)
+                            .map(|l| l + 1)
+                    }
+                    "ilvl" => paragraph.list_level = value.parse().unwrap_or(0).min(MAX_LIST_LEVEL),
+                    _ => paragraph.num_id = Some(value),
+                }
+            }
+            ("r", TagKind::Open) => format = RunFormat::default(),
+            // formatting of the paragraph mark is inside <w:pPr>, it does not apply to the text
+            (name, _) if in_run_properties && !in_paragraph_properties => match name {
+                "b" => format.bold = docx_toggle(&tag),
+                "i" => format.italic = docx_toggle(&tag),
+                "u" => format.underline = docx_toggle(&tag),
+                "strike" | "dstrike" => format.strike = docx_toggle(&tag),
+                "vertalign" => {
+                    let align = tag.attr("w:val").unwrap_or_default();
+                    format.superscript = align == "superscript";
+                    format.subscript = align == "subscript";
+                }
+                _ => {}
+            },
+            ("t", TagKind::Open) => in_text = true,
+            ("t", _) => in_text = false,
+            ("tab", _) | ("br" | "cr", _) if !in_paragraph_properties => {
+                let Some(paragraph) = paragraph.as_mut() else {
+                    continue;
+                };
+                match tag.name.as_str() {
+                    "tab" => paragraph.html.push(' '),
+                    _ if tag.attr("w:type").as_deref() == Some("page") => {}
+                    _ => paragraph.html.push_str("<br/>"),
+                }
+            }
+            ("hyperlink", TagKind::Open) => {
+                let href = match (tag.attr("r:id"), tag.attr("w:anchor")) {
+                    (Some(id), _) => relationships
+                        .get(&id)
+                        .filter(|(_, external)| *external)
+                        .map(|(target, _)| target.clone()),
+                    (None, Some(anchor)) => Some(format!("#{}", anchor)),
+                    _ => None,
+                };
+                let href = href.filter(|href| is_safe_link(href));
+                if let (Some(paragraph), Some(href)) = (paragraph.as_mut(), href.as_ref()) {
+                    paragraph
+                        .html
+                        .push_str(&format!("<a href=\"{}\">", escape_xml(href)));
+                }
+                links.push(href.is_some() && paragraph.is_some());
+            }
+            ("hyperlink", TagKind::Close) => {
+                if let (Some(true), Some(paragraph)) = (links.pop(), paragraph.as_mut()) {
+                    paragraph.html.push_str("</a>");
+                }
+            }
+            // targets of internal links, e.g. the entries of a generated table of contents
+            ("bookmarkstart", _) => {
+                let name = tag.attr("w:name").unwrap_or_default();
+                if let (Some(paragraph), false) =
+                    (paragraph.as_mut(), name.is_empty() || name == "_GoBack")
+                {
+                    paragraph
+                        .html
+                        .push_str(&format!("<span id=\"{}\"></span>", escape_xml(&name)));
+                }
+            }
+            ("docpr", _) => alt = tag.attr("descr").unwrap_or_default(),
+            ("blip", _) => {
+                let target = tag
+                    .attr("r:embed")
+                    .and_then(|id| relationships.get(&id))
+                    .filter(|(_, external)| !external)
+                    .map(|(target, _)| target.clone());
+                if let (Some(target), Some(paragraph)) = (target, paragraph.as_mut()) {
+                    let image = writer.image(&mut archive, &target, &alt);
+                    paragraph.html.push_str(&image);
+                }
+            }
+            ("tbl", TagKind::Open) => {
+                writer.close_lists(0);
+                writer.body.push_str("<table>\n");
+            }
+            ("tbl", TagKind::Close) => writer.body.push_str("</table>\n"),
+            ("tr", TagKind::Open) => writer.body.push_str("<tr>"),
+            ("tr", TagKind::Close) => writer.body.push_str("</tr>\n"),
+            ("tc", TagKind::Open) => writer.body.push_str("<td>"),
+            ("tc", TagKind::Close) => {
+                writer.close_lists(0);
+                writer.body.push_str("</td>");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(writer.finish(title))
+}
+
+// function to read the formatting of odt automatic styles and which list styles are numbered
+fn odt_styles(data: &str) -> (HashMap<String, RunFormat>, HashMap<(String, usize), bool>) {
+    let mut formats = HashMap::new();
+    let mut lists = HashMap::new();
+    let (mut style, mut list_style): (Option<String>, Option<String>) = (None, None);
+
+    for token in tokenize(data) {
+        let Token::Tag(tag) = token else {
+            continue;
+        };
+        match (tag.name.as_str(), tag.kind) {
+            ("style", TagKind::Open) => style = tag.attr("style:name"),
+            ("style", _) => style = None,
+            ("text-properties", _) => {
+                let Some(style) = style.clone() else {
+                    continue;
+                };
+                let set = |name: &str| tag.attr(name).is_some_and(|value| value != "none");
+                let position = tag.attr("style:text-position").unwrap_or_default();
+                let format = RunFormat {
+                    bold: tag.attr("fo:font-weight").as_deref() == Some("bold"),
+                    italic: tag.attr("fo:font-style").as_deref() == Some("italic"),
+                    underline: set("style:text-underline-style"),
+                    strike: set("style:text-line-through-style"),
+                    superscript: position.starts_with("super"),
+                    subscript: position.starts_with("sub"),
+                };
+                if format != RunFormat::default() {
+                    formats.insert(style, format);
+                }
+            }
+            ("list-style", TagKind::Open) => list_style = tag.attr("style:name"),
+            ("list-style", _) => list_style = None,
+            ("list-level-style-number" | "list-level-style-bullet", _) => {
+                let level = tag
+                    .attr("text:level")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(1);
+                if let Some(list_style) = list_style.clone() {
+                    lists.insert((list_style, level), tag.name == "list-level-style-number");
+                }
+            }
+            _ => {}
+        }
+    }
+    (formats, lists)
+}
+
+// OdtParagraph struct: an odt paragraph or heading being read
+struct OdtParagraph {
+    heading: Option<usize>,
+    format: RunFormat,
+    html: String,
+}
+
+// OdtInline enum: an open span with its format and start in the paragraph html, or a link and whether it was written
+enum OdtInline {
+    Span(RunFormat, usize),
+    Link(bool),
+}
+
+// function to convert an opendocument text (odt) to xhtml
+pub fn convert_odt(path: &str) -> Result<OfficeDocument, ReaderError> {
+    let mut archive = open_archive(path)?;
+    let content = read_entry_string(&mut archive, "content.xml")?;
+    let title = read_entry_string(&mut archive, "meta.xml")
+        .ok()
+        .and_then(|data| element_text(&data, "title"));
+
+    // list styles can also be named styles of styles.xml
+    let (formats, mut list_styles) = odt_styles(&content);
+    if let Ok(styles) = read_entry_string(&mut archive, "styles.xml") {
+        for (key, ordered) in odt_styles(&styles).1 {
+            list_styles.entry(key).or_insert(ordered);
+        }
+    }
+
+    let mut writer = Writer::default();
+    let mut in_body = false;
+    let mut skipped: Option<(String, usize)> = None; // element being skipped and its nesting
+    let mut paragraph: Option<OdtParagraph> = None;
+    let mut depth: usize = 0; // paragraphs of frames are nested in the paragraph holding the frame
+    let mut inlines: Vec<OdtInline> = Vec::new(); // open spans and links of the paragraph
+    let mut lists: Vec<Option<String>> = Vec::new(); // style names of open lists
+    let mut item_started = false; // set once the current list item has its first paragraph
+
+    for token in tokenize(&content) {
+        let tag = match token {
+            Token::Text(raw) => {
+                if let (None, Some(paragraph)) = (&skipped, paragraph.as_mut()) {
+                    paragraph.html.push_str(&escape_xml(&decode_entities(raw)));
+                }
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        if let Some((name, nesting)) = skipped.as_mut() {
+            if *name == tag.name {
+                match tag.kind {
+                    TagKind::Open => *nesting += 1,
+                    TagKind::Close => *nesting -= 1,
+                    TagKind::SelfClosing => {}
+                }
+                if *nesting == 0 {
+                    skipped = None;
+                }
+            }
+            continue;
+        }
+        if !in_body {
+            in_body = tag.name == "body" && tag.is_open();
+            continue;
+        }
+
+        match (tag.name.as_str(), tag.kind) {
+            (name, TagKind::Open) if ODT_SKIPPED_ELEMENTS.contains(&name) => {
+                skipped = Some((tag.name.clone(), 1));
+            }
+            ("p" | "h", TagKind::Open) => {
+                depth += 1;
+                if depth == 1 {
+                    let heading = (tag.name == "h").then(|| {
+                        tag.attr("text:outline-level")
+                            .and_then(|level| level.parse().ok())
+                            .unwrap_or(1)
+                    });
+                    let format = tag
+                        .attr("text:style-name")
+                        .and_then(|style| formats.get(&style).copied())
+                        .unwrap_or_default();
+                    paragraph = Some(OdtParagraph {
+                        heading,
+                        format,
+                        html: String::new(),
+                    });
+                }
+            }
+            ("p" | "h", TagKind::Close) => {
+                depth = depth.saturating_sub(1); // a stray closing tag does not open a paragraph
+                if depth > 0 {
+                    continue;
+                }
+                let Some(paragraph) = paragraph.take() else {
+                    continue;
+                };
+                inlines.clear();
+                let html = match paragraph.html.trim() {
+                    "" => String::new(),
+                    html => paragraph.format.wrap(html),
+                };
+                match (paragraph.heading, lists.len()) {
+                    (Some(level), _) => writer.heading(level, &html),
+                    (None, 0) => writer.paragraph(&html),
+                    (None, _) if item_started => {
+                        if !html.is_empty() {
+                            writer.body.push_str(&format!("<p>{}</p>", html));
+                        }
+                    }
+                    (None, depth) => {
+                        let style = lists.last().cloned().flatten().unwrap_or_default();
+                        let ordered = list_styles.get(&(style, depth)).copied().unwrap_or(false);
+                        writer.list_item(depth, ordered, &html);
+                        item_started = true;
+                    }
+                }
+            }
+            ("span", TagKind::Open) => {
+                let format = tag
+                    .attr("text:style-name")
+                    .and_then(|style| formats.get(&style).copied())
+                    .unwrap_or_default();
+                if let Some(paragraph) = paragraph.as_ref() {
+                    inlines.push(OdtInline::Span(format, paragraph.html.len()));
+                }
+            }
+            ("a", TagKind::Open) => {
+                let href = tag.attr("xlink:href").filter(|href| is_safe_link(href));
+                if let Some(paragraph) = paragraph.as_mut() {
+                    if let Some(href) = href.as_ref() {
+                        paragraph
+                            .html
+                            .push_str(&format!("<a href=\"{}\">", escape_xml(href)));
+                    }
+                    inlines.push(OdtInline::Link(href.is_some()));
+                }
+            }
+            ("span" | "a", TagKind::Close) => {
+                let (Some(inline), Some(paragraph)) = (inlines.pop(), paragraph.as_mut()) else {
+                    continue;
+                };
+                match inline {
+                    // the format is written around the content once the span is complete
+                    OdtInline::Span(format, start) => {
+                        let content = paragraph.html.split_off(start);
+                        paragraph.html.push_str(&format.wrap(&content));
+                    }
+                    OdtInline::Link(true) => paragraph.html.push_str("</a>"),
+                    OdtInline::Link(false) => {}
+                }
+            }
+            ("s", _) => {
+                let count = tag
+                    .attr("text:c")
+                    .and_then(|count| count.parse().ok())
+                    .unwrap_or(1usize)
+                    .min(100);
+                if let Some(paragraph) = paragraph.as_mut() {
+                    paragraph.html.push_str(&"\u{a0}".repeat(count));
+                }
+            }
+            ("tab", _) => {
+                if let Some(paragraph) = paragraph.as_mut() {
+                    paragraph.html.push(' ');
+                }
+            }
+            ("line-break", _) => {
+                if let Some(paragraph) = paragraph.as_mut() {
+                    paragraph.html.push_str("<br/>");
+                }
+            }
+            ("bookmark" | "bookmark-start", _) => {
+                if let (Some(paragraph), Some(name)) = (paragraph.as_mut(), tag.attr("text:name")) {
+                    paragraph
+                        .html
+                        .push_str(&format!("<span id=\"{}\"></span>", escape_xml(&name)));
+                }
+            }
+            ("image", _) => {
+                let Some(href) = tag.attr("xlink:href") else {
+                    continue;
+                };
+                if href.contains(':') {
+                    continue; // linked images outside the file are not loaded
+                }
+                let image = writer.image(&mut archive, &resolve_path("", &href), "");
+                match paragraph.as_mut() {
+                    Some(paragraph) => paragraph.html.push_str(&image),
+                    None => writer.paragraph(&image),
+                }
+            }
+            ("list", TagKind::Open) => {
+                let style = tag
+                    .attr("text:style-name")
+                    .or_else(|| lists.last().cloned().flatten());
+                lists.push(style);
+            }
+            ("list", TagKind::Close) => {
+                lists.pop();
+                writer.close_lists(lists.len());
+            }
+            ("list-item" | "list-header", TagKind::Open) => item_started = false,
+            ("table", TagKind::Open) => {
+                writer.close_lists(0);
+                writer.body.push_str("<table>\n");
+            }
+            ("table", TagKind::Close) => writer.body.push_str("</table>\n"),
+            ("table-row", TagKind::Open) => writer.body.push_str("<tr>"),
+            ("table-row", TagKind::Close) => writer.body.push_str("</tr>\n"),
+            ("table-cell", TagKind::Open | TagKind::SelfClosing) => {
+                let span = tag
+                    .attr("table:number-columns-spanned")
+                    .and_then(|span| span.parse::<u32>().ok())
+                    .filter(|span| *span > 1);
+                match span {
+                    Some(span) => writer.body.push_str(&format!("<td colspan=\"{}\">", span)),
+                    None => writer.body.push_str("<td>"),
+                }
+                if tag.kind == TagKind::SelfClosing {
+                    writer.body.push_str("</td>");
+                }
+            }
+            ("table-cell", TagKind::Close) => {
+                writer.close_lists(0);
+                writer.body.push_str("</td>");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(writer.finish(title))
+}
@@ -0,0 +1,130 @@
+/*
+    reader module:
+    pdf.rs contains functions to read pdf files on the backend
+*/
+
+// importing crates and modules
+use crate::reader::ReaderError;
+use flate2::read::ZlibDecoder;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use lopdf::{Document, Object, ObjectId};
+use std::io::{Cursor, Read};
+
+// size of an a4 page, used when a page has no media box
+const DEFAULT_PAGE_SIZE: (f64, f64) = (595.0, 842.0);
+
+// function to load a pdf document, decrypting it if it opens with an empty password
+pub fn load_document(path: &str) -> Result<Document, ReaderError> {
+    let mut document = Document::load(path)?;
+
+    if document.is_encrypted() {
+        document
+            .decrypt("")
+            .map_err(|_| ReaderError::InvalidData(format!("{} is password protected", path)))?;
+    }
+
+    Ok(document)
+}
+
+// function to get the largest image drawn on the first page as encoded (jpeg/png) bytes
+// scanned books and most textbooks have the cover as a single full page image
+pub fn first_page_image(path: &str) -> Result<Option<Vec<u8>>, ReaderError> {
+    let document = load_document(path)?;
+    let Some(page_id) = document.get_pages().values().next().copied() else {
+        return Ok(None);
+    };
+
+    let images = document.get_page_images(page_id).unwrap_or_default();
+    let Some(image) = images
+        .iter()
+        .filter(|image| image.width >= 200 && image.height >= 200) // skip logos and icons
+        .max_by_key(|image| image.width * image.height)
+    else {
+        return Ok(None);
+    };
+
+    let filters = image.filters.clone().unwrap_or_default();
+    match filters.last().map(|filter| filter.as_str()) {
+        // jpeg data can be used as it is
+        Some("DCTDecode") if filters.len() == 1 => Ok(Some(image.content.to_vec())),
+        // raw pixels are only supported for 8 bit gray and rgb images
+        None | Some("FlateDecode") if filters.len() <= 1 => {
+            if image.bits_per_component != Some(8) {
+                return Ok(None);
+            }
+
+            // images with predictors need un-filtering which is not done here
+            if image.origin_dict.get(b"DecodeParms").is_ok() {
+                return Ok(None);
+            }
+
+            let content = if filters.is_empty() {
+                image.content.to_vec()
+            } else {
+                let mut content = Vec::new();
+                ZlibDecoder::new(image.content).read_to_end(&mut content)?;
+                content
+            };
+            let (width, height) = (image.width as u32, image.height as u32);
+            let decoded = match image.color_space.as_deref() {
+                Some("DeviceRGB") => {
+                    RgbImage::from_raw(width, height, content).map(DynamicImage::from)
+                }
+                Some("DeviceGray") => {
+                    GrayImage::from_raw(width, height, content).map(DynamicImage::from)
+                }
+                _ => None,
+            };
+
+            match decoded {
+                Some(decoded) => {
+                    let mut png = Vec::new();
+                    decoded.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+                    Ok(Some(png))
+                }
+                None => Ok(None),
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+// function to get the media box of a page as [left, bottom, right, top]
+// the origin of the box is not always 0 0, positions on the page are relative to its bottom left corner
+pub fn page_box(document: &Document, page_id: ObjectId) -> [f64; 4] {
+    let mut node = document.get_dictionary(page_id).ok();
+    let mut depth = 0;
+
+    // media box is inheritable, so walk up the page tree until found
+    while let Some(dict) = node {
+        if let Ok(media_box) = dict
+            .get_deref(b"MediaBox", document)
+            .and_then(Object::as_array)
+        {
+            let values: Vec<f64> = media_box
+                .iter()
+                .filter_map(|o| o.as_float().ok())
+                .map(|n| n as f64)
+                .collect();
+            if let [x1, y1, x2, y2] = values[..] {
+                return [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)];
+            }
+        }
+        depth += 1;
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .ok()
+            .filter(|_| depth < 32);
+    }
+
+    let (width, height) = DEFAULT_PAGE_SIZE;
+    [0.0, 0.0, width, height]
+}
+
+// function to get the media box of a page as (width, height)
+pub fn page_size(document: &Document, page_id: ObjectId) -> (f64, f64) {
+    let [left, bottom, right, top] = page_box(document, page_id);
+    (right - left, top - bottom)
+}
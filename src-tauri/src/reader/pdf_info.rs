@@ -0,0 +1,496 @@
+/*
+    reader module:
+    pdf_info.rs reads the document info, page sizes, outline and page labels of pdf files
+*/
+
+// importing crates and modules
+use crate::reader::pdf::{load_document, page_size};
+use crate::reader::ReaderError;
+use lopdf::{decode_text_string, Dictionary, Document, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// maximum depth of nested outline items, name trees and number trees
+const MAX_TREE_DEPTH: usize = 32;
+
+// page labels above this number are written in digits, roman numerals and letters would get too long
+const MAX_STYLED_NUMBER: u32 = 3999;
+
+// PdfInfo struct: entries of the document information dictionary
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PdfInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<String>, // iso 8601, e.g. 2024-01-31T12:00:00+01:00
+    pub modification_date: Option<String>,
+}
+
+// PageInfo struct: size and label of a page
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PageInfo {
+    pub page: u32,
+    pub label: Option<String>, // e.g. "iv" for roman numbered front matter
+    pub width: f64,
+    pub height: f64,
+    pub rotation: i64, // clockwise rotation in degrees, a multiple of 90
+}
+
+// OutlineItem struct: an entry of the outline (bookmarks) tree
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OutlineItem {
+    pub title: String,
+    pub page: Option<u32>,   // target page, starting from 1
+    pub uri: Option<String>, // set for links to web pages
+    pub children: Vec<OutlineItem>,
+}
+
+// PdfDetails struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PdfDetails {
+    pub info: PdfInfo,
+    pub page_count: u32,
+    pub pages: Vec<PageInfo>,
+    pub outline: Vec<OutlineItem>,
+}
+
+// function to decode a text string, ignoring empty ones
+fn text(object: Option<&Object>) -> Option<String> {
+    let decoded = decode_text_string(object?).ok()?;
+    let trimmed = decoded.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+// function to follow a reference, returning the object itself for direct objects
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> &'a Object {
+    document
+        .dereference(object)
+        .map(|(_, object)| object)
+        .unwrap_or(object)
+}
+
+// function to convert a pdf date (D:YYYYMMDDHHmmSSOHH'mm') to iso 8601
+// missing parts default as described in the pdf specification, unreadable dates are returned as they are
+pub fn pdf_date(raw: &str) -> String {
+    let value = raw.trim().trim_start_matches("D:");
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return raw.trim().to_string();
+    }
+
+    let part = |start: usize, default: &str| -> String {
+        digits.get(start..start + 2).unwrap_or(default).to_string()
+    };
+    let mut date = format!(
+        "{}-{}-{}T{}:{}:{}",
+        &digits[..4],
+        part(4, "01"),
+        part(6, "01"),
+        part(8, "00"),
+        part(10, "00"),
+        part(12, "00")
+    );
+
+    // time zone: Z, or +/- followed by HH'mm'
+    let zone = &value[digits.len()..];
+    if zone.starts_with('Z') {
+        date.push('Z');
+    } else if zone.starts_with(['+', '-']) {
+        let zone_digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+        if zone_digits.len() >= 2 {
+            date.push_str(&format!(
+                "{}{}:{}",
+                &zone[..1],
+                &zone_digits[..2],
+                zone_digits.get(2..4).unwrap_or("00")
+            ));
+        }
+    }
+
+    date
+}
+
+// function to read the document information dictionary
+pub fn read_info(document: &Document) -> PdfInfo {
+    let Ok(info) = document
+        .trailer
+        .get_deref(b"Info", document)
+        .and_then(Object::as_dict)
+    else {
+        return PdfInfo::default();
+    };
+
+    let get = |key: &[u8]| text(info.get_deref(key, document).ok());
+    PdfInfo {
+        title: get(b"Title"),
+        author: get(b"Author"),
+        subject: get(b"Subject"),
+        keywords: get(b"Keywords"),
+        creator: get(b"Creator"),
+        producer: get(b"Producer"),
+        creation_date: get(b"CreationDate").map(|date| pdf_date(&date)),
+        modification_date: get(b"ModDate").map(|date| pdf_date(&date)),
+    }
+}
+
+// function to find a key in a name tree (used for named destinations)
+fn name_tree_lookup<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    key: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > MAX_TREE_DEPTH {
+        return None;
+    }
+
+    if let Ok(names) = node
+        .get_deref(b"Names", document)
+        .and_then(Object::as_array)
+    {
+        for pair in names.chunks(2) {
+            if let [name, value] = pair {
+                if resolve(document, name).as_str().ok() == Some(key) {
+                    return Some(resolve(document, value));
+                }
+            }
+        }
+    }
+
+    let kids = node.get_deref(b"Kids", document).and_then(Object::as_array);
+    for kid in kids.into_iter().flatten() {
+        let Ok(kid) = resolve(document, kid).as_dict() else {
+            continue;
+        };
+
+        // limits are [first last] keys of the subtree, skip subtrees which can't contain the key
+        if let Ok(limits) = kid
+            .get_deref(b"Limits", document)
+            .and_then(Object::as_array)
+        {
+            let limits: Vec<&[u8]> = limits
+                .iter()
+                .filter_map(|limit| resolve(document, limit).as_str().ok())
+                .collect();
+            if let [first, last] = limits[..] {
+                if key < first || key > last {
+                    continue;
+                }
+            }
+        }
+        if let Some(found) = name_tree_lookup(document, kid, key, depth + 1) {
+            return Some(found);
+        }
+    }
+
+    None
+}
+
+// function to find a named destination in the catalog
+fn named_destination<'a>(document: &'a Document, name: &[u8]) -> Option<&'a Object> {
+    let catalog = document.catalog().ok()?;
+
+    // pdf 1.2+ name tree
+    let tree = catalog
+        .get_deref(b"Names", document)
+        .and_then(Object::as_dict)
+        .and_then(|names| names.get_deref(b"Dests", document))
+        .and_then(Object::as_dict);
+    if let Ok(tree) = tree {
+        if let Some(found) = name_tree_lookup(document, tree, name, 0) {
+            return Some(found);
+        }
+    }
+
+    // pdf 1.1 dictionary of names
+    catalog
+        .get_deref(b"Dests", document)
+        .and_then(Object::as_dict)
+        .and_then(|dests| dests.get_deref(name, document))
+        .ok()
+}
+
+// function to get the page number (starting from 1) a destination points to
+fn destination_page(
+    document: &Document,
+    destination: &Object,
+    pages: &HashMap<ObjectId, u32>,
+    depth: usize,
+) -> Option<u32> {
+    if depth > 4 {
+        return None; // named destinations pointing to each other
+    }
+
+    match resolve(document, destination) {
+        // explicit destination: [page /XYZ left top zoom] and similar
+        Object::Array(array) => match array.first()? {
+            Object::Reference(id) => pages.get(id).copied(),
+            Object::Integer(index) => u32::try_from(*index).ok()?.checked_add(1),
+            _ => None,
+        },
+        Object::Name(name) | Object::String(name, _) => {
+            let target = named_destination(document, name)?;
+            destination_page(document, target, pages, depth + 1)
+        }
+        // named destinations may be dictionaries with the destination in /D
+        Object::Dictionary(dict) => {
+            destination_page(document, dict.get(b"D").ok()?, pages, depth + 1)
+        }
+        _ => None,
+    }
+}
+
+// function to read the outline items starting from the first child of an outline node
+fn read_outline_items(
+    document: &Document,
+    node: &Dictionary,
+    pages: &HashMap<ObjectId, u32>,
+    visited: &mut HashSet<ObjectId>,
+    depth: usize,
+) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    if depth > MAX_TREE_DEPTH {
+        return items;
+    }
+
+    let mut next = node.get(b"First").and_then(Object::as_reference).ok();
+    while let Some(id) = next {
+        // broken files can have cycles in the sibling or child links
+        if !visited.insert(id) {
+            break;
+        }
+        let Ok(item) = document.get_dictionary(id) else {
+            break;
+        };
+        next = item.get(b"Next").and_then(Object::as_reference).ok();
+
+        let mut page = item
+            .get(b"Dest")
+            .ok()
+            .and_then(|dest| destination_page(document, dest, pages, 0));
+        let mut uri = None;
+
+        // actions: GoTo jumps inside the document, URI opens a web page
+        if let Ok(action) = item.get_deref(b"A", document).and_then(Object::as_dict) {
+            match action.get(b"S").and_then(Object::as_name) {
+                Ok(b"GoTo") => {
+                    page = action
+                        .get(b"D")
+                        .ok()
+                        .and_then(|dest| destination_page(document, dest, pages, 0));
+                }
+                Ok(b"URI") => {
+                    uri = action
+                        .get_deref(b"URI", document)
+                        .and_then(Object::as_str)
+                        .ok()
+                        .map(|uri| String::from_utf8_lossy(uri).to_string());
+                }
+                _ => {}
+            }
+        }
+
+        items.push(OutlineItem {
+            title: text(item.get_deref(b"Title", document).ok()).unwrap_or_default(),
+            page,
+            uri,
+            children: read_outline_items(document, item, pages, visited, depth + 1),
+        });
+    }
+
+    items
+}
+
+// function to read the outline (bookmarks) tree of a document
+pub fn read_outline(document: &Document) -> Vec<OutlineItem> {
+    let Ok(root) = document
+        .catalog()
+        .and_then(|catalog| catalog.get_deref(b"Outlines", document))
+        .and_then(Object::as_dict)
+    else {
+        return Vec::new();
+    };
+
+    let pages: HashMap<ObjectId, u32> = document
+        .get_pages()
+        .into_iter()
+        .map(|(number, id)| (id, number))
+        .collect();
+    read_outline_items(document, root, &pages, &mut HashSet::new(), 0)
+}
+
+// function to collect the (first page index, label dictionary) entries of a number tree
+fn number_tree_entries<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    entries: &mut Vec<(u32, &'a Dictionary)>,
+    depth: usize,
+) {
+    if depth > MAX_TREE_DEPTH {
+        return;
+    }
+
+    if let Ok(nums) = node.get_deref(b"Nums", document).and_then(Object::as_array) {
+        for pair in nums.chunks(2) {
+            if let [index, value] = pair {
+                let index = resolve(document, index).as_i64().ok();
+                let value = resolve(document, value).as_dict().ok();
+                if let (Some(index), Some(value)) = (index, value) {
+                    if let Ok(index) = u32::try_from(index) {
+                        entries.push((index, value));
+                    }
+                }
+            }
+        }
+    }
+
+    let kids = node.get_deref(b"Kids", document).and_then(Object::as_array);
+    for kid in kids.into_iter().flatten() {
+        if let Ok(kid) = resolve(document, kid).as_dict() {
+            number_tree_entries(document, kid, entries, depth + 1);
+        }
+    }
+}
+
+// function to write a number in roman numerals
+fn roman(mut number: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+
+    let mut result = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            result.push_str(numeral);
+            number -= value;
+        }
+    }
+    result
+}
+
+// function to write a number in letters: a to z, then aa to zz, then aaa...
+fn letters(number: u32) -> String {
+    if number == 0 {
+        return String::new();
+    }
+    let letter = (b'a' + ((number - 1) % 26) as u8) as char;
+    letter.to_string().repeat(((number - 1) / 26 + 1) as usize)
+}
+
+// function to get the labels of all pages, None for pages without a label
+pub fn page_labels(document: &Document, page_count: u32) -> Vec<Option<String>> {
+    let mut labels = vec![None; page_count as usize];
+    let Ok(tree) = document
+        .catalog()
+        .and_then(|catalog| catalog.get_deref(b"PageLabels", document))
+        .and_then(Object::as_dict)
+    else {
+        return labels;
+    };
+
+    let mut entries = Vec::new();
+    number_tree_entries(document, tree, &mut entries, 0);
+    entries.sort_by_key(|(index, _)| *index);
+
+    // every entry starts a range which lasts until the next entry
+    for (position, (first, label)) in entries.iter().enumerate() {
+        let end = entries
+            .get(position + 1)
+            .map_or(page_count, |(next, _)| (*next).min(page_count));
+        let style = label.get(b"S").and_then(Object::as_name).ok();
+        let prefix = text(label.get_deref(b"P", document).ok()).unwrap_or_default();
+        let start = label
+            .get(b"St")
+            .and_then(Object::as_i64)
+            .map_or(1, |start| start.clamp(1, u32::MAX as i64) as u32);
+
+        for index in *first..end {
+            let Some(number) = start.checked_add(index - first) else {
+                break; // hostile start numbers, the remaining pages keep no label
+            };
+            let value = match style {
+                Some(b"R" | b"r" | b"A" | b"a") if number > MAX_STYLED_NUMBER => number.to_string(),
+                Some(b"D") => number.to_string(),
+                Some(b"R") => roman(number).to_uppercase(),
+                Some(b"r") => roman(number),
+                Some(b"A") => letters(number).to_uppercase(),
+                Some(b"a") => letters(number),
+                _ => String::new(), // no style: the label is only the prefix
+            };
+            labels[index as usize] = Some(format!("{}{}", prefix, value));
+        }
+    }
+
+    labels
+}
+
+// function to get the rotation of a page, which is inheritable from the page tree
+fn page_rotation(document: &Document, page_id: ObjectId) -> i64 {
+    let mut node = document.get_dictionary(page_id).ok();
+    let mut depth = 0;
+
+    while let Some(dict) = node {
+        if let Ok(rotation) = dict.get_deref(b"Rotate", document).and_then(Object::as_i64) {
+            return rotation.rem_euclid(360);
+        }
+        depth += 1;
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .ok()
+            .filter(|_| depth < MAX_TREE_DEPTH);
+    }
+
+    0
+}
+
+// function to read the info, pages and outline of a loaded document
+pub fn read_details(document: &Document) -> PdfDetails {
+    let page_ids = document.get_pages();
+    let page_count = page_ids.len() as u32;
+    let labels = page_labels(document, page_count);
+
+    let pages = page_ids
+        .iter()
+        .map(|(&page, &page_id)| {
+            let (width, height) = page_size(document, page_id);
+            PageInfo {
+                page,
+                label: labels.get(page as usize - 1).cloned().flatten(),
+                width,
+                height,
+                rotation: page_rotation(document, page_id),
+            }
+        })
+        .collect();
+
+    PdfDetails {
+        info: read_info(document),
+        page_count,
+        pages,
+        outline: read_outline(document),
+    }
+}
+
+// pdf_details_get command: returns the info, page sizes and labels and the outline of a pdf when invoked
+#[tauri::command]
+pub fn pdf_details_get(path: &str) -> Result<PdfDetails, ReaderError> {
+    let document = load_document(path)?;
+    Ok(read_details(&document))
+}
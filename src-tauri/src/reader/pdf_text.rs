@@ -0,0 +1,684 @@
+/*
+    reader module:
+    pdf_text.rs extracts the text of pdf pages in reading order
+*/
+
+// importing crates and modules
+use crate::reader::pdf::{load_document, page_box};
+use crate::reader::ReaderError;
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object, ObjectId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// maximum depth of nested form xobjects
+const MAX_FORM_DEPTH: usize = 8;
+
+// number of bins the page width is split into while looking for a gutter, the media box is not trusted as a size
+const GUTTER_GRID: usize = 1000;
+
+// narrowest gap between two columns, in points
+const MIN_GUTTER_WIDTH: f64 = 8.0;
+
+// a 2d transformation matrix [a b c d e f], points are row vectors: p' = p x m
+type Matrix = [f64; 6];
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// TextLine struct: a line of text, position is in points from the top left corner of the page
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TextLine {
+    pub text: String,
+    pub x: f64,
+    pub y: f64, // top of the line
+    pub width: f64,
+    pub font_size: f64,
+}
+
+// PageText struct: text of a page, lines are in reading order
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PageText {
+    pub page: u32, // 1 based page number
+    pub width: f64,
+    pub height: f64,
+    pub lines: Vec<TextLine>,
+    pub text: String, // lines joined with new lines, blocks separated by empty lines
+}
+
+// Span struct: text drawn by a single text showing operator, in page space (origin at the bottom left)
+#[derive(Clone, Debug)]
+struct Span {
+    text: String,
+    x: f64,
+    y: f64, // baseline
+    width: f64,
+    font_size: f64,
+}
+
+// FontInfo struct: what is needed to decode text and measure glyphs of a font
+struct FontInfo<'a> {
+    encoding: Option<Encoding<'a>>,
+    two_byte: bool, // composite (Type0) fonts use two byte codes
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+}
+
+// implementations for FontInfo struct
+impl<'a> FontInfo<'a> {
+    // reads encoding and glyph widths of a font dictionary
+    fn new(document: &'a Document, font: &'a Dictionary) -> Self {
+        let subtype = font
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or_default();
+        let two_byte = subtype == b"Type0";
+        let mut widths = HashMap::new();
+        let mut default_width = 500.0;
+
+        if two_byte {
+            // widths of composite fonts are in the descendant font: W [first [w1 w2 ...] first last w ...]
+            let descendant = font
+                .get_deref(b"DescendantFonts", document)
+                .and_then(Object::as_array)
+                .ok()
+                .and_then(|fonts| fonts.first())
+                .and_then(|font| document.dereference(font).ok())
+                .and_then(|(_, font)| font.as_dict().ok());
+
+            if let Some(descendant) = descendant {
+                default_width = number(descendant.get(b"DW").ok()).unwrap_or(1000.0);
+                let w = descendant
+                    .get_deref(b"W", document)
+                    .and_then(Object::as_array)
+                    .map(|w| w.as_slice())
+                    .unwrap_or_default();
+
+                let mut index = 0;
+                while index + 1 < w.len() {
+                    let first = number(Some(&w[index])).unwrap_or_default() as u32;
+                    match document.dereference(&w[index + 1]).map(|(_, o)| o) {
+                        Ok(Object::Array(list)) => {
+                            for (offset, width) in list.iter().enumerate() {
+                                let code = u32::try_from(offset)
+                                    .ok()
+                                    .and_then(|offset| first.checked_add(offset));
+                                let Some(code) = code else { break };
+                                if let Some(width) = number(Some(width)) {
+                                    widths.insert(code, width);
+                                }
+                            }
+                            index += 2;
+                        }
+                        _ => {
+                            let last = number(w.get(index + 1)).unwrap_or_default() as u32;
+                            let width = number(w.get(index + 2)).unwrap_or(default_width);
+                            for code in first..=last.min(first.saturating_add(0xFFFF)) {
+                                widths.insert(code, width);
+                            }
+                            index += 3;
+                        }
+                    }
+                }
+            }
+        } else {
+            let first = number(font.get(b"FirstChar").ok()).unwrap_or_default() as u32;
+            if let Ok(list) = font
+                .get_deref(b"Widths", document)
+                .and_then(Object::as_array)
+            {
+                for (offset, width) in list.iter().enumerate() {
+                    let code = u32::try_from(offset)
+                        .ok()
+                        .and_then(|offset| first.checked_add(offset));
+                    let Some(code) = code else { break };
+                    if let Some(width) = number(Some(width)) {
+                        widths.insert(code, width);
+                    }
+                }
+            }
+            if let Some(width) = font
+                .get_deref(b"FontDescriptor", document)
+                .and_then(Object::as_dict)
+                .ok()
+                .and_then(|descriptor| number(descriptor.get(b"MissingWidth").ok()))
+                .filter(|width| *width > 0.0)
+            {
+                default_width = width;
+            }
+        }
+
+        Self {
+            encoding: font.get_font_encoding(document).ok(),
+            two_byte,
+            widths,
+            default_width,
+        }
+    }
+
+    // splits a string into character codes
+    fn codes(&self, bytes: &[u8]) -> Vec<u32> {
+        if self.two_byte {
+            bytes
+                .chunks(2)
+                .map(|pair| pair.iter().fold(0, |code, byte| code << 8 | *byte as u32))
+                .collect()
+        } else {
+            bytes.iter().map(|byte| *byte as u32).collect()
+        }
+    }
+
+    // width of a glyph in thousandths of text space units
+    fn width(&self, code: u32) -> f64 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+
+    // decodes a string to unicode, falling back to latin-1 if the encoding is not supported
+    fn decode(&self, bytes: &[u8]) -> String {
+        if let Some(text) = self
+            .encoding
+            .as_ref()
+            .and_then(|encoding| Document::decode_text(encoding, bytes).ok())
+        {
+            return text;
+        }
+        if self.two_byte {
+            return String::new(); // cids without a unicode map can not be decoded
+        }
+        bytes.iter().map(|byte| *byte as char).collect()
+    }
+}
+
+// TextState struct: graphics and text state needed to position text
+#[derive(Clone)]
+struct TextState {
+    ctm: Matrix,
+    font: Option<usize>, // key of the current font in the font cache
+    font_size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    horizontal_scaling: f64,
+    leading: f64,
+    rise: f64,
+}
+
+// Interpreter struct: walks content streams and collects spans
+struct Interpreter<'a> {
+    document: &'a Document,
+    fonts: HashMap<usize, FontInfo<'a>>, // fonts by address of their dictionary, names differ per resources
+    spans: Vec<Span>,
+}
+
+// function to read a number operand
+fn number(object: Option<&Object>) -> Option<f64> {
+    object.and_then(|o| o.as_float().ok()).map(|n| n as f64)
+}
+
+// function to multiply two matrices (m1 is applied first)
+fn multiply(m1: &Matrix, m2: &Matrix) -> Matrix {
+    [
+        m1[0] * m2[0] + m1[1] * m2[2],
+        m1[0] * m2[1] + m1[1] * m2[3],
+        m1[2] * m2[0] + m1[3] * m2[2],
+        m1[2] * m2[1] + m1[3] * m2[3],
+        m1[4] * m2[0] + m1[5] * m2[2] + m2[4],
+        m1[4] * m2[1] + m1[5] * m2[3] + m2[5],
+    ]
+}
+
+// function to read six number operands as a matrix
+fn matrix(operands: &[Object]) -> Option<Matrix> {
+    let values: Vec<f64> = operands.iter().filter_map(|o| number(Some(o))).collect();
+    values.try_into().ok()
+}
+
+// function to collect resource dictionaries of a page (own and inherited)
+fn page_resources(document: &Document, page_id: ObjectId) -> Vec<&Dictionary> {
+    let Ok((own, inherited)) = document.get_page_resources(page_id) else {
+        return Vec::new();
+    };
+    own.into_iter()
+        .chain(
+            inherited
+                .into_iter()
+                .filter_map(|id| document.get_dictionary(id).ok()),
+        )
+        .collect()
+}
+
+// function to get a named entry (font or xobject) from resource dictionaries
+fn resource<'a>(
+    document: &'a Document,
+    resources: &[&'a Dictionary],
+    kind: &[u8],
+    name: &[u8],
+) -> Option<&'a Object> {
+    resources.iter().find_map(|dict| {
+        let entries = dict
+            .get_deref(kind, document)
+            .and_then(Object::as_dict)
+            .ok()?;
+        let object = entries.get(name).ok()?;
+        document.dereference(object).ok().map(|(_, object)| object)
+    })
+}
+
+// implementations for Interpreter struct
+impl<'a> Interpreter<'a> {
+    // runs the operations of a content stream, forms are run recursively
+    fn run(&mut self, content: &[u8], resources: &[&'a Dictionary], ctm: Matrix, depth: usize) {
+        let Ok(content) = Content::decode(content) else {
+            return;
+        };
+
+        let mut state = TextState {
+            ctm,
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+        };
+        let mut saved: Vec<TextState> = Vec::new();
+        let mut text_matrix = IDENTITY;
+        let mut line_matrix = IDENTITY;
+
+        for operation in &content.operations {
+            let operands = &operation.operands;
+            let operand = |index: usize| number(operands.get(index)).unwrap_or_default();
+
+            match operation.operator.as_str() {
+                "q" => saved.push(state.clone()),
+                "Q" => state = saved.pop().unwrap_or(state),
+                "cm" => {
+                    if let Some(m) = matrix(operands) {
+                        state.ctm = multiply(&m, &state.ctm);
+                    }
+                }
+                "BT" => {
+                    text_matrix = IDENTITY;
+                    line_matrix = IDENTITY;
+                }
+                "Tf" => {
+                    state.font = operands
+                        .first()
+                        .and_then(|o| o.as_name().ok())
+                        .and_then(|name| self.load_font(resources, name));
+                    state.font_size = operand(1);
+                }
+                "Tc" => state.char_spacing = operand(0),
+                "Tw" => state.word_spacing = operand(0),
+                "Tz" => state.horizontal_scaling = operand(0) / 100.0,
+                "TL" => state.leading = operand(0),
+                "Ts" => state.rise = operand(0),
+                "Td" | "TD" => {
+                    if operation.operator == "TD" {
+                        state.leading = -operand(1);
+                    }
+                    line_matrix =
+                        multiply(&[1.0, 0.0, 0.0, 1.0, operand(0), operand(1)], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tm" => {
+                    if let Some(m) = matrix(operands) {
+                        line_matrix = m;
+                        text_matrix = m;
+                    }
+                }
+                "T*" => {
+                    line_matrix =
+                        multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                    text_matrix = line_matrix;
+                }
+                "Tj" | "'" | "\"" | "TJ" => {
+                    // ' and " move to the next line first, " also sets spacing
+                    if operation.operator == "\"" {
+                        state.word_spacing = operand(0);
+                        state.char_spacing = operand(1);
+                    }
+                    if operation.operator == "'" || operation.operator == "\"" {
+                        line_matrix =
+                            multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, -state.leading], &line_matrix);
+                        text_matrix = line_matrix;
+                    }
+
+                    let parts: &[Object] = match operands.last() {
+                        Some(Object::Array(parts)) => parts,
+                        Some(string) => std::slice::from_ref(string),
+                        None => &[],
+                    };
+                    for part in parts {
+                        match part {
+                            Object::String(bytes, _) => {
+                                self.show(bytes, &state, &mut text_matrix);
+                            }
+                            // numbers in TJ arrays move the next glyph left, in thousandths of an em
+                            other => {
+                                let adjust = number(Some(other)).unwrap_or_default();
+                                let tx =
+                                    -adjust / 1000.0 * state.font_size * state.horizontal_scaling;
+                                text_matrix =
+                                    multiply(&[1.0, 0.0, 0.0, 1.0, tx, 0.0], &text_matrix);
+                            }
+                        }
+                    }
+                }
+                "Do" if depth < MAX_FORM_DEPTH => {
+                    let Some(name) = operands.first().and_then(|o| o.as_name().ok()) else {
+                        continue;
+                    };
+                    let Some(Object::Stream(form)) =
+                        resource(self.document, resources, b"XObject", name)
+                    else {
+                        continue;
+                    };
+                    if form.dict.get(b"Subtype").and_then(Object::as_name).ok() != Some(b"Form") {
+                        continue; // images have no text
+                    }
+
+                    let form_matrix = form
+                        .dict
+                        .get(b"Matrix")
+                        .and_then(Object::as_array)
+                        .ok()
+                        .and_then(|m| matrix(m))
+                        .unwrap_or(IDENTITY);
+
+                    // forms use their own resources if they have them
+                    let mut form_resources: Vec<&'a Dictionary> = form
+                        .dict
+                        .get_deref(b"Resources", self.document)
+                        .and_then(Object::as_dict)
+                        .into_iter()
+                        .collect();
+                    form_resources.extend_from_slice(resources);
+
+                    if let Ok(data) = form.get_plain_content() {
+                        self.run(
+                            &data,
+                            &form_resources,
+                            multiply(&form_matrix, &state.ctm),
+                            depth + 1,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // reads a font the first time it is used and returns its key in the font cache
+    fn load_font(&mut self, resources: &[&'a Dictionary], name: &[u8]) -> Option<usize> {
+        let Some(Object::Dictionary(font)) = resource(self.document, resources, b"Font", name)
+        else {
+            return None;
+        };
+
+        let key = font as *const Dictionary as usize;
+        self.fonts
+            .entry(key)
+            .or_insert_with(|| FontInfo::new(self.document, font));
+        Some(key)
+    }
+
+    // adds a span for a shown string and moves the text matrix past it
+    fn show(&mut self, bytes: &[u8], state: &TextState, text_matrix: &mut Matrix) {
+        let Some(font) = state.font.and_then(|key| self.fonts.get(&key)) else {
+            return;
+        };
+
+        // advance of the string in unscaled text space
+        let mut advance = 0.0;
+        for code in font.codes(bytes) {
+            advance += font.width(code) / 1000.0 * state.font_size + state.char_spacing;
+            if !font.two_byte && code == 32 {
+                advance += state.word_spacing;
+            }
+        }
+        advance *= state.horizontal_scaling;
+
+        let rendering = multiply(text_matrix, &state.ctm);
+        let origin = multiply(&[1.0, 0.0, 0.0, 1.0, 0.0, state.rise], &rendering);
+        let scale_x = (rendering[0].powi(2) + rendering[1].powi(2)).sqrt();
+        let scale_y = (rendering[2].powi(2) + rendering[3].powi(2)).sqrt();
+
+        let text = font.decode(bytes);
+        if !text.trim().is_empty() {
+            self.spans.push(Span {
+                text,
+                x: origin[4],
+                y: origin[5],
+                width: advance * scale_x,
+                font_size: (state.font_size * scale_y).abs().max(1.0),
+            });
+        }
+
+        *text_matrix = multiply(&[1.0, 0.0, 0.0, 1.0, advance, 0.0], text_matrix);
+    }
+}
+
+// function to group spans into lines, top to bottom and left to right
+fn build_lines(mut spans: Vec<Span>, page_height: f64) -> Vec<TextLine> {
+    spans.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let mut groups: Vec<Vec<Span>> = Vec::new();
+    for span in spans {
+        match groups.last_mut() {
+            // spans whose baselines are close enough are on the same line
+            Some(group) if (group[0].y - span.y).abs() < group[0].font_size * 0.5 => {
+                group.push(span)
+            }
+            _ => groups.push(vec![span]),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|mut group| {
+            group.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+            let mut text = String::new();
+            let mut end: Option<f64> = None;
+            for span in &group {
+                // insert a space where there is a visible gap between spans
+                if let Some(end) = end {
+                    let gap = span.x - end;
+                    if gap > span.font_size * 0.15
+                        && !text.ends_with(char::is_whitespace)
+                        && !span.text.starts_with(char::is_whitespace)
+                    {
+                        text.push(' ');
+                    }
+                }
+                text.push_str(&span.text);
+                end = Some(end.unwrap_or(f64::MIN).max(span.x + span.width));
+            }
+
+            let x = group[0].x;
+            let font_size = group.iter().map(|s| s.font_size).fold(0.0, f64::max);
+            let baseline = group.iter().map(|s| s.y).fold(f64::MIN, f64::max);
+            TextLine {
+                text: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+                x,
+                y: page_height - baseline - font_size,
+                width: end.unwrap_or(x) - x,
+                font_size,
+            }
+        })
+        .filter(|line| !line.text.is_empty())
+        .collect()
+}
+
+// function to find the gap between two text columns, if the page has them
+// returns the x coordinate of the middle of the gap
+fn find_gutter(spans: &[Span], page_width: f64) -> Option<f64> {
+    if spans.len() < 10 || !(page_width > 0.0 && page_width.is_finite()) {
+        return None;
+    }
+
+    // count spans covering every bin of the page width
+    let bin = |x: f64| ((x / page_width).clamp(0.0, 1.0) * GUTTER_GRID as f64) as usize;
+    let mut coverage = vec![0usize; GUTTER_GRID + 1];
+    for span in spans {
+        let (start, end) = (bin(span.x), bin(span.x + span.width));
+        for count in coverage.iter_mut().take(end + 1).skip(start) {
+            *count += 1;
+        }
+    }
+
+    // widest (almost) empty run in the middle of the page, a few titles may cross it
+    let allowed = (spans.len() / 20).max(2);
+    let (from, to) = (GUTTER_GRID * 3 / 10, GUTTER_GRID * 7 / 10);
+    let mut best: Option<(usize, usize)> = None;
+    let mut run_start: Option<usize> = None;
+    for (x, count) in coverage.iter().enumerate().take(to + 1).skip(from) {
+        if *count <= allowed {
+            let start = *run_start.get_or_insert(x);
+            if best.is_none_or(|(best_start, best_end)| x - start > best_end - best_start) {
+                best = Some((start, x));
+            }
+        } else {
+            run_start = None;
+        }
+    }
+
+    let to_points = |bins: usize| bins as f64 * page_width / GUTTER_GRID as f64;
+    let (start, end) = best.filter(|(start, end)| to_points(end - start) >= MIN_GUTTER_WIDTH)?;
+    let gutter = to_points(start + end) / 2.0;
+
+    // both columns need a fair share of the text
+    let left = spans.iter().filter(|s| s.x + s.width <= gutter).count();
+    let right = spans.iter().filter(|s| s.x >= gutter).count();
+    (left * 5 >= spans.len() && right * 5 >= spans.len()).then_some(gutter)
+}
+
+// function to put the spans of a page in reading order
+// two column pages are read column by column, lines crossing the gutter (like titles) split the columns
+fn order_lines(spans: Vec<Span>, page_width: f64, page_height: f64) -> Vec<Vec<TextLine>> {
+    let Some(gutter) = find_gutter(&spans, page_width) else {
+        return vec![build_lines(spans, page_height)];
+    };
+
+    let (mut left, mut right, mut full) = (Vec::new(), Vec::new(), Vec::new());
+    for span in spans {
+        if span.x + span.width <= gutter {
+            left.push(span);
+        } else if span.x >= gutter {
+            right.push(span);
+        } else {
+            full.push(span);
+        }
+    }
+    let left = build_lines(left, page_height);
+    let right = build_lines(right, page_height);
+    let full = build_lines(full, page_height);
+
+    // every full width line ends a band, each band is read left column first
+    let mut blocks = Vec::new();
+    let mut top = f64::MIN;
+    for bottom in full
+        .iter()
+        .map(|line| line.y)
+        .chain([f64::MAX])
+        .collect::<Vec<f64>>()
+    {
+        for column in [&left, &right] {
+            let block: Vec<TextLine> = column
+                .iter()
+                .filter(|line| line.y >= top && line.y < bottom)
+                .cloned()
+                .collect();
+            if !block.is_empty() {
+                blocks.push(block);
+            }
+        }
+        if let Some(line) = full.iter().find(|line| line.y == bottom) {
+            blocks.push(vec![line.clone()]);
+        }
+        top = bottom;
+    }
+
+    blocks
+}
+
+// function to extract the text of a page
+fn page_text(document: &Document, page: u32, page_id: ObjectId) -> PageText {
+    let [left, bottom, right, top] = page_box(document, page_id);
+    let (width, height) = (right - left, top - bottom);
+    let mut interpreter = Interpreter {
+        document,
+        fonts: HashMap::new(),
+        spans: Vec::new(),
+    };
+
+    if let Ok(content) = document.get_page_content(page_id) {
+        let resources = page_resources(document, page_id);
+        interpreter.run(&content, &resources, IDENTITY, 0);
+    }
+
+    // positions are measured from the corner of the media box, which is not always at the origin
+    for span in &mut interpreter.spans {
+        span.x -= left;
+        span.y -= bottom;
+    }
+
+    let blocks = order_lines(interpreter.spans, width, height);
+    let text = blocks
+        .iter()
+        .map(|block| {
+            block
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    PageText {
+        page,
+        width,
+        height,
+        lines: blocks.into_iter().flatten().collect(),
+        text,
+    }
+}
+
+// function to extract text of the pages of a pdf in the given range (1 based, inclusive)
+pub fn extract_pages(
+    path: &str,
+    first_page: Option<u32>,
+    last_page: Option<u32>,
+) -> Result<Vec<PageText>, ReaderError> {
+    let document = load_document(path)?;
+    let first = first_page.unwrap_or(1);
+    let last = last_page.unwrap_or(u32::MAX);
+
+    Ok(document
+        .get_pages()
+        .into_iter()
+        .filter(|(page, _)| (first..=last).contains(page))
+        .map(|(page, page_id)| page_text(&document, page, page_id))
+        .collect())
+}
+
+// function to extract the text of every page of a pdf (page 1 is at index 0)
+pub fn page_texts(path: &str) -> Result<Vec<String>, ReaderError> {
+    Ok(extract_pages(path, None, None)?
+        .into_iter()
+        .map(|page| page.text)
+        .collect())
+}
+
+// pdf_text_get command: returns the text of pdf pages in the given range when invoked
+#[tauri::command]
+pub fn pdf_text_get(
+    path: &str,
+    first_page: Option<u32>,
+    last_page: Option<u32>,
+) -> Result<Vec<PageText>, ReaderError> {
+    extract_pages(path, first_page, last_page)
+}
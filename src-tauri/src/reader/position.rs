@@ -0,0 +1,175 @@
+/*
+    reader module:
+    position.rs remembers the last reading location and progress of every library book
+*/
+
+// importing crates and modules
+use crate::functions::current_time_ms;
+use crate::reader::stats::record_progress;
+use crate::reader::{read_json, save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// path of the positions file inside the app data directory
+const POSITIONS_FILE: &str = "library/positions.json";
+
+// Locator enum: a location inside a book, depending on its format
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "format")]
+pub enum Locator {
+    Epub {
+        cfi: String,
+        href: Option<String>, // spine document containing the cfi, if known
+    },
+    Pdf {
+        page: u32, // 1 based page number
+        page_count: Option<u32>,
+        scroll_offset: f64,
+        zoom: f64,
+        rotation: i32,
+    },
+    Comic {
+        page: u32, // 1 based page number
+        page_count: Option<u32>,
+    },
+}
+
+// ReadingPosition struct: last location of a book with progress in percent (0 - 100)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReadingPosition {
+    pub book_id: String,
+    pub locator: Locator,
+    pub percentage: f32,
+    pub updated_at: u128,
+}
+
+// function to load saved positions of all books (book id -> position)
+pub fn load_positions() -> Result<HashMap<String, ReadingPosition>, ReaderError> {
+    read_json(POSITIONS_FILE)
+}
+
+// function to save positions of all books
+fn save_positions(positions: &HashMap<String, ReadingPosition>) -> Result<(), ReaderError> {
+    save_json(POSITIONS_FILE, positions)
+}
+
+// function to get the spine position from an epub cfi, e.g. epubcfi(/6/4[chap01]!/4/2) -> 1
+pub fn cfi_spine_index(cfi: &str) -> Option<usize> {
+    let path = cfi.strip_prefix("epubcfi(")?.strip_prefix("/6/")?;
+    let step: String = path.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let step: usize = step.parse().ok()?;
+
+    // children of <spine> are counted in steps of 2, starting from 2
+    (step >= 2).then(|| step / 2 - 1)
+}
+
+// function to check the locator and work out the progress
+pub fn validate_locator(locator: &Locator, percentage: Option<f32>) -> Result<f32, ReaderError> {
+    let computed = match locator {
+        Locator::Epub { cfi, .. } => {
+            if !cfi.starts_with("epubcfi(") {
+                return Err(ReaderError::InvalidData(format!(
+                    "{} is not an epub cfi",
+                    cfi
+                )));
+            }
+            None
+        }
+        Locator::Pdf {
+            page,
+            page_count,
+            zoom,
+            rotation,
+            ..
+        } => {
+            if *page == 0 || page_count.is_some_and(|count| *page > count) {
+                return Err(ReaderError::InvalidData(format!(
+                    "page {} is out of range",
+                    page
+                )));
+            }
+            if !zoom.is_finite() || *zoom <= 0.0 {
+                return Err(ReaderError::InvalidData(format!(
+                    "{} is not a valid zoom",
+                    zoom
+                )));
+            }
+            if rotation % 90 != 0 {
+                return Err(ReaderError::InvalidData(format!(
+                    "{} is not a valid rotation",
+                    rotation
+                )));
+            }
+            page_count.map(|count| *page as f32 / count as f32 * 100.0)
+        }
+        Locator::Comic { page, page_count } => {
+            if *page == 0 || page_count.is_some_and(|count| *page > count) {
+                return Err(ReaderError::InvalidData(format!(
+                    "page {} is out of range",
+                    page
+                )));
+            }
+            page_count.map(|count| *page as f32 / count as f32 * 100.0)
+        }
+    };
+
+    // progress sent by the ui is preferred, as it knows the actual layout
+    let percentage = percentage.or(computed).unwrap_or(0.0);
+    if !percentage.is_finite() {
+        return Err(ReaderError::InvalidData(
+            "progress is not a number".to_string(),
+        ));
+    }
+    Ok(percentage.clamp(0.0, 100.0))
+}
+
+// reading_position_save command: saves the current location of a book when invoked
+#[tauri::command]
+pub fn reading_position_save(
+    book_id: String,
+    locator: Locator,
+    percentage: Option<f32>,
+) -> Result<ReadingPosition, ReaderError> {
+    let percentage = validate_locator(&locator, percentage)?;
+    let position = ReadingPosition {
+        book_id: book_id.clone(),
+        locator,
+        percentage,
+        updated_at: current_time_ms(),
+    };
+
+    let mut positions = load_positions()?;
+    positions.insert(book_id.clone(), position.clone());
+    save_positions(&positions)?;
+
+    // the position is saved first, so a failing statistics file can't lose it
+    record_progress(&book_id, &position.locator, percentage)?;
+
+    Ok(position)
+}
+
+// reading_position_get command: returns the last saved location of a book when invoked
+#[tauri::command]
+pub fn reading_position_get(book_id: &str) -> Result<Option<ReadingPosition>, ReaderError> {
+    Ok(load_positions()?.remove(book_id))
+}
+
+// function to move the saved location of a book to its new id
+pub fn move_position(old_id: &str, new_id: &str) -> Result<(), ReaderError> {
+    let mut positions = load_positions()?;
+    if let Some(mut position) = positions.remove(old_id) {
+        position.book_id = new_id.to_string();
+        positions.insert(new_id.to_string(), position);
+        save_positions(&positions)?;
+    }
+    Ok(())
+}
+
+// function to remove the saved location of a book
+pub fn remove_position(book_id: &str) -> Result<(), ReaderError> {
+    let mut positions = load_positions()?;
+    if positions.remove(book_id).is_some() {
+        save_positions(&positions)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,280 @@
+/*
+    reader module:
+    resources.rs serves library books and the files inside epubs to the webview through the book: protocol
+*/
+
+// importing crates and modules
+use crate::reader::cache::{open_book, open_cached_archive};
+use crate::reader::comic::{find_page, page_preview, read_page};
+use crate::reader::cover::error_response;
+use crate::reader::document::read_document_resource;
+use crate::reader::epub::read_entry_bytes;
+use crate::reader::library::{Book, BookFormat, Library};
+use crate::reader::sanitize::sanitize_resource;
+use crate::reader::xml::percent_decode;
+use crate::reader::ReaderError;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Runtime, UriSchemeContext};
+
+// largest part of a book file sent in one response, larger requests get a partial response
+const FILE_CHUNK_LENGTH: u64 = 4 * 1024 * 1024;
+
+// function to get the mime type of a file inside an epub from its extension
+fn mime_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "xhtml" | "xht" => "application/xhtml+xml",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "xml" => "application/xml",
+        "opf" => "application/oebps-package+xml",
+        "ncx" => "application/x-dtbncx+xml",
+        "smil" => "application/smil+xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "txt" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "pdf" => "application/pdf",
+        "epub" => "application/epub+zip",
+        "cbz" => "application/vnd.comicbook+zip",
+        "cbr" => "application/vnd.comicbook-rar",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        _ => "application/octet-stream",
+    }
+}
+
+// function to parse a single range of a Range header (bytes=start-end, bytes=start- or bytes=-suffix)
+// returns None if the header is not a range this protocol understands, so the whole body is sent
+fn parse_range(value: &str, length: u64) -> Option<Result<Range<u64>, ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None; // multiple ranges are not supported, send everything
+    }
+    let (start, end) = spec.split_once('-')?;
+
+    let range = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            length.saturating_sub(suffix)..length
+        }
+        (start, "") => start.parse().ok()?..length,
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            start.parse().ok()?..end.saturating_add(1).min(length)
+        }
+    };
+
+    if range.start >= length || range.start >= range.end {
+        return Some(Err(())); // not satisfiable
+    }
+    Some(Ok(range))
+}
+
+// function to build a response for data of the given total length, honouring the range header
+// responses are cut to max_length bytes if given, read is called with the byte range to send
+fn ranged_response(
+    request: &Request<Vec<u8>>,
+    content_type: &str,
+    length: u64,
+    max_length: Option<u64>,
+    read: impl FnOnce(Range<u64>) -> Result<Vec<u8>, ReaderError>,
+) -> Response<Vec<u8>> {
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, length));
+
+    let (status, range) = match range {
+        Some(Ok(range)) => (StatusCode::PARTIAL_CONTENT, range),
+        Some(Err(())) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", length))
+                .body(Vec::new())
+                .unwrap_or_default()
+        }
+        None => (StatusCode::OK, 0..length),
+    };
+    let (status, range) = match max_length {
+        Some(max_length) if range.end - range.start > max_length => (
+            StatusCode::PARTIAL_CONTENT,
+            range.start..range.start + max_length,
+        ),
+        _ => (status, range),
+    };
+
+    let body = match read(range.clone()) {
+        Ok(body) => body,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::ACCESS_CONTROL_EXPOSE_HEADERS, "Content-Range");
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", range.start, range.end - 1, length),
+        );
+    }
+    response.body(body).unwrap_or_default()
+}
+
+// function to serve the whole book file, reading only the requested range from disk
+// books can be hundreds of MBs, so at most one chunk is read per request and viewers ask for the rest by range
+fn serve_book_file(request: &Request<Vec<u8>>, book: &Book) -> Response<Vec<u8>> {
+    let mut file = match File::open(&book.path) {
+        Ok(file) => file,
+        Err(e) => return error_response(StatusCode::NOT_FOUND, &e.to_string()),
+    };
+    let length = match file.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+
+    let content_type = match book.format {
+        BookFormat::Pdf => "application/pdf",
+        BookFormat::Epub => "application/epub+zip",
+        BookFormat::Text
+        | BookFormat::Markdown
+        | BookFormat::Html
+        | BookFormat::Comic
+        | BookFormat::Docx
+        | BookFormat::Odt => mime_type(&book.path),
+    };
+    ranged_response(
+        request,
+        content_type,
+        length,
+        Some(FILE_CHUNK_LENGTH),
+        |range| {
+            let mut body = vec![0; (range.end - range.start) as usize];
+            file.seek(SeekFrom::Start(range.start))?;
+            file.read_exact(&mut body)?;
+            Ok(body)
+        },
+    )
+}
+
+// function to serve a file inside an epub archive, or inside the epub made from a document
+fn serve_archive_entry(request: &Request<Vec<u8>>, book: &Book, path: &str) -> Response<Vec<u8>> {
+    let entry = open_cached_archive(&book.path)
+        .and_then(|mut archive| read_entry_bytes(&mut archive, path));
+    // files linked from documents, such as images of markdown notes, are read from their folder
+    let entry = match entry {
+        Err(ReaderError::NotFound(_)) if book.format.is_document() => {
+            read_document_resource(&book.path, path)
+        }
+        entry => entry,
+    };
+    let data = match entry {
+        Ok(data) => data,
+        Err(ReaderError::NotFound(message)) => {
+            return error_response(StatusCode::NOT_FOUND, &message)
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+    let data = if book.trusted {
+        data
+    } else {
+        // the manifest says what the reader renders the file as, files linked from documents have no manifest item
+        let media_type = open_book(&book.path).ok().and_then(|(_, package)| {
+            package
+                .item_by_href(path)
+                .map(|item| item.media_type.clone())
+        });
+        sanitize_resource(path, media_type.as_deref(), data)
+    };
+
+    // entries are compressed, so they are read whole and the range is cut from memory
+    ranged_response(request, mime_type(path), data.len() as u64, None, |range| {
+        Ok(data[range.start as usize..range.end as usize].to_vec())
+    })
+}
+
+// function to serve a page of a comic, pages/{page} is the original image and previews/{page} a downscaled one
+fn serve_comic_page(request: &Request<Vec<u8>>, book: &Book, entry: &str) -> Response<Vec<u8>> {
+    let (kind, page) = entry.split_once('/').unwrap_or((entry, ""));
+    let Ok(page) = page.parse::<u32>() else {
+        return error_response(StatusCode::NOT_FOUND, "comics only have numbered pages");
+    };
+
+    let image = match kind {
+        "pages" => find_page(&book.path, page)
+            .and_then(|page| Ok((read_page(&book.path, &page)?, page.name))),
+        "previews" => page_preview(&book.id, &book.path, page),
+        _ => return error_response(StatusCode::NOT_FOUND, "comics only have pages and previews"),
+    };
+    let (data, name) = match image {
+        Ok(image) => image,
+        Err(ReaderError::NotFound(message)) => {
+            return error_response(StatusCode::NOT_FOUND, &message)
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+
+    ranged_response(
+        request,
+        mime_type(&name),
+        data.len() as u64,
+        None,
+        |range| Ok(data[range.start as usize..range.end as usize].to_vec()),
+    )
+}
+
+// book_protocol: handles book://localhost/{book_id}/{path in archive} requests from the webview
+// text documents are served as the epub made from them, like epubs they are opened as a directory
+// pages of comics are served by number, book://localhost/{book_id}/pages/{page}
+// without a path the book file itself is sent, only books registered in the library are served
+// documents are only served as the epub made from them, their raw file could run scripts in the webview
+pub fn book_protocol<R: Runtime>(
+    _ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let path = percent_decode(request.uri().path().trim_start_matches('/'));
+    let (book_id, entry) = path.split_once('/').unwrap_or((&path, ""));
+
+    let library = match Library::load() {
+        Ok(library) => library,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    };
+    let Some(book) = library.book(book_id) else {
+        return error_response(StatusCode::NOT_FOUND, "book is not in library");
+    };
+
+    match (entry, book.format) {
+        ("", format) if format.is_document() => error_response(
+            StatusCode::NOT_FOUND,
+            "documents are only served as the epub made from them",
+        ),
+        ("", _) => serve_book_file(&request, book),
+        (_, BookFormat::Pdf) => error_response(StatusCode::NOT_FOUND, "pdfs have no inner files"),
+        (entry, BookFormat::Comic) => serve_comic_page(&request, book, entry),
+        (entry, _) => serve_archive_entry(&request, book, entry),
+    }
+}
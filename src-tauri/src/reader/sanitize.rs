@@ -0,0 +1,399 @@
+/*
+    reader module:
+    sanitize.rs removes scripts, event handlers and remote resources from epub content before it reaches the webview
+*/
+
+// importing crates and modules
+use crate::reader::xml::{decode_entities, escape_xml, tokenize, Tag, Token};
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+// elements removed together with their content
+const REMOVED_ELEMENTS: [&str; 9] = [
+    "script", "iframe", "frame", "frameset", "object", "embed", "applet", "base", "portal",
+];
+
+// removed elements which never have content
+const REMOVED_VOID_ELEMENTS: [&str; 5] = ["embed", "base", "frame", "link", "meta"];
+
+// elements an html parser reads as text up to their closing tag, what looks like markup inside them is text
+const RAW_TEXT_ELEMENTS: [&str; 9] = [
+    "style",
+    "noscript",
+    "textarea",
+    "title",
+    "xmp",
+    "noembed",
+    "noframes",
+    "iframe",
+    "plaintext",
+];
+
+// svg animation elements, which can change attributes like href after sanitizing
+const ANIMATION_ELEMENTS: [&str; 4] = ["animate", "set", "animatemotion", "animatetransform"];
+
+// attributes which make the webview fetch a resource
+const FETCH_ATTRIBUTES: [&str; 6] = ["src", "srcset", "poster", "background", "data", "lowsrc"];
+
+// elements whose href attribute fetches a resource instead of being a link
+const FETCH_HREF_ELEMENTS: [&str; 5] = ["link", "image", "use", "feimage", "script"];
+
+// attributes which hold a link the user can follow
+const LINK_ATTRIBUTES: [&str; 4] = ["href", "xlink:href", "action", "formaction"];
+
+// regex matching attributes inside a tag, with the same rules as the xml tokenizer
+// no space is required before a name, as browsers also accept attributes written right after a quote
+fn attr_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"([A-Za-z_][\w:.-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>/]+))"#).unwrap()
+    })
+}
+
+// regex matching url() values in css
+fn css_url_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)]*))\s*\)"#).unwrap())
+}
+
+// regex matching @import rules in css
+fn css_import_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"(?i)@import\s+(?:url\(\s*)?(?:"([^"]*)"|'([^']*)'|([^\s;)]+))[^;]*;?"#)
+            .unwrap()
+    })
+}
+
+// regex matching comments and cdata sections in the text between tags, with the same rules as the xml tokenizer
+fn hidden_text_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?s)<!--((?:-?>|.*?--!?>|.*))|<!\[CDATA\[(.*?)\]\]>"#).unwrap())
+}
+
+// function to normalize a url for checking its scheme: entities decoded, whitespace and control characters removed
+fn normalize_url(url: &str) -> String {
+    decode_entities(url)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+// function to get the scheme of a url, None for relative urls
+fn url_scheme(url: &str) -> Option<&str> {
+    let end = url.find([':', '/', '?', '#'])?;
+    (url[end..].starts_with(':') && end > 0).then(|| &url[..end])
+}
+
+// function to check if a url can be loaded as a resource (relative files of the book and images in data urls)
+pub fn is_safe_resource(url: &str) -> bool {
+    let url = normalize_url(url);
+    if url.starts_with("//") || url.starts_with("\\\\") {
+        return false; // protocol relative urls are remote
+    }
+    match url_scheme(&url) {
+        None => true,
+        Some("data") => !url.starts_with("data:text/html") && !url.starts_with("data:application/"),
+        Some(_) => false,
+    }
+}
+
+// function to check if a url can be used as a link (relative, web pages and mail)
+pub fn is_safe_link(url: &str) -> bool {
+    let url = normalize_url(url);
+    match url_scheme(&url) {
+        None => true,
+        Some(scheme) => ["http", "https", "mailto"].contains(&scheme),
+    }
+}
+
+// function to remove remote resources from css
+pub fn sanitize_css(css: &str) -> String {
+    let imported = |caps: &Captures| {
+        let url = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .or_else(|| caps.get(3))
+            .map_or("", |m| m.as_str());
+        if is_safe_resource(url) {
+            caps[0].to_string()
+        } else {
+            String::new()
+        }
+    };
+    let css = css_import_regex().replace_all(css, imported);
+
+    let url = |caps: &Captures| {
+        let url = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .or_else(|| caps.get(3))
+            .map_or("", |m| m.as_str().trim());
+        if is_safe_resource(url) {
+            caps[0].to_string()
+        } else {
+            "none".to_string()
+        }
+    };
+    css_url_regex().replace_all(&css, url).into_owned()
+}
+
+// function to escape an attribute value written in double quotes
+fn escape_attr(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+// function to check if an attribute of an element is safe to keep, or return its cleaned up value
+// returns None to drop the attribute
+fn clean_attr(element: &str, name: &str, value: &str) -> Option<Option<String>> {
+    let name = name.to_ascii_lowercase();
+    if name.starts_with("on") {
+        return None; // event handlers
+    }
+    if name == "style" {
+        let css = decode_entities(value);
+        let cleaned = sanitize_css(&css);
+        return Some((cleaned != css).then(|| escape_attr(&cleaned)));
+    }
+    if name == "srcset" {
+        // candidates are separated by commas: "a.png 1x, b.png 2x"
+        let safe = value.split(',').all(|candidate| {
+            is_safe_resource(candidate.split_whitespace().next().unwrap_or_default())
+        });
+        return safe.then_some(None);
+    }
+
+    let fetches = FETCH_ATTRIBUTES.contains(&name.as_str())
+        || (matches!(name.as_str(), "href" | "xlink:href")
+            && FETCH_HREF_ELEMENTS.contains(&element));
+    if fetches {
+        return is_safe_resource(value).then_some(None);
+    }
+    if LINK_ATTRIBUTES.contains(&name.as_str()) {
+        return is_safe_link(value).then_some(None);
+    }
+    Some(None)
+}
+
+// function to check if an element is removed even though it has no removed name
+fn is_unsafe_element(tag: &Tag) -> bool {
+    match tag.name.as_str() {
+        // meta refresh redirects the viewer
+        "meta" => tag
+            .attr("http-equiv")
+            .is_some_and(|value| value.eq_ignore_ascii_case("refresh")),
+        // only stylesheets from the book itself are allowed
+        "link" => tag
+            .attr("href")
+            .is_some_and(|href| !is_safe_resource(&href)),
+        // animations could set a javascript: url or an event handler
+        name if ANIMATION_ELEMENTS.contains(&name) => {
+            tag.attr("attributename").is_some_and(|target| {
+                let target = normalize_url(&target);
+                target.starts_with("on") || target.ends_with("href")
+            })
+        }
+        _ => false,
+    }
+}
+
+// function to rewrite a tag without its unsafe attributes, keeping the source as it is otherwise
+fn clean_tag(source: &str, name: &str) -> String {
+    attr_regex()
+        .replace_all(source, |caps: &Captures| {
+            let value = caps
+                .get(2)
+                .or_else(|| caps.get(3))
+                .or_else(|| caps.get(4))
+                .map_or("", |m| m.as_str());
+            match clean_attr(name, &caps[1], value) {
+                Some(None) => caps[0].to_string(),
+                Some(Some(cleaned)) => format!("{}=\"{}\"", &caps[1], cleaned),
+                None => String::new(),
+            }
+        })
+        .into_owned()
+}
+
+// function to clean the content of a raw text element, which must not contain '<' as it could close the element
+// for an html parser while an xml parser reads it as a tag or an attribute value: css escapes it, other text drops it
+fn clean_raw_text(text: &str, element: &str) -> String {
+    if element == "style" {
+        sanitize_css(text).replace('<', "\\3c ")
+    } else {
+        text.replace('<', "")
+    }
+}
+
+// function to clean the text between two tags, inside the given raw text element if any
+// comments and cdata sections are not copied, an html parser may end them elsewhere and read what follows as markup:
+// their content is kept as escaped text, or as css inside style elements, and other comments are dropped
+fn clean_text(text: &str, raw_text: Option<&str>) -> String {
+    let in_style = raw_text == Some("style");
+    let clean = |text: &str| match raw_text {
+        Some(element) => clean_raw_text(text, element),
+        None => text.to_string(),
+    };
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in hidden_text_regex().captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        output.push_str(&clean(&text[last..whole.start()]));
+        last = whole.end();
+
+        match (caps.get(1), caps.get(2)) {
+            // old stylesheets are wrapped in a comment for browsers without css
+            (Some(comment), _) if in_style => {
+                let css = comment.as_str();
+                let css = css.strip_suffix("-->").or_else(|| css.strip_suffix("--!>"));
+                output.push_str(&clean(css.unwrap_or_default()));
+            }
+            (_, Some(cdata)) if in_style => output.push_str(&clean(cdata.as_str())),
+            (_, Some(cdata)) => output.push_str(&escape_xml(cdata.as_str())),
+            _ => {}
+        }
+    }
+    output.push_str(&clean(&text[last..]));
+    output
+}
+
+// function to remove scripts, event handlers, javascript urls and remote resources from xhtml, html or svg
+pub fn sanitize_markup(data: &str) -> String {
+    let mut output = String::with_capacity(data.len());
+    let mut last = 0; // end of the source copied so far
+    let mut removed: Option<(String, usize)> = None; // element being removed with its nesting depth
+    let mut raw_text: Option<String> = None; // raw text element the tokens are inside of
+
+    for token in tokenize(data) {
+        let Token::Tag(tag) = token else {
+            continue; // text, comments and declarations are cleaned with the source between tags
+        };
+
+        // text before the tag
+        if removed.is_none() {
+            output.push_str(&clean_text(&data[last..tag.start], raw_text.as_deref()));
+        }
+        last = tag.end;
+
+        if let Some((name, depth)) = removed.as_mut() {
+            if *name == tag.name {
+                if tag.is_open() {
+                    *depth += 1;
+                } else if tag.is_close() {
+                    *depth -= 1;
+                }
+            }
+            if *depth == 0 {
+                removed = None;
+            }
+            continue;
+        }
+
+        // tags inside a raw text element are text for an html parser, only its closing tag ends it
+        if let Some(element) = &raw_text {
+            if !(tag.is_close() && tag.name == *element) {
+                output.push_str(&clean_raw_text(&data[tag.start..tag.end], element));
+                continue;
+            }
+            raw_text = None;
+        }
+
+        if REMOVED_ELEMENTS.contains(&tag.name.as_str()) || is_unsafe_element(&tag) {
+            if tag.is_open() && !REMOVED_VOID_ELEMENTS.contains(&tag.name.as_str()) {
+                removed = Some((tag.name.clone(), 1));
+            }
+            continue;
+        }
+
+        if tag.is_open() && RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+            raw_text = Some(tag.name.clone());
+        }
+        output.push_str(&clean_tag(&data[tag.start..tag.end], &tag.name));
+    }
+
+    if removed.is_none() {
+        output.push_str(&clean_text(&data[last..], raw_text.as_deref()));
+    }
+    output
+}
+
+// ResourceKind enum: how a file of an epub is sanitized
+#[derive(Clone, Copy, PartialEq)]
+enum ResourceKind {
+    Markup,
+    Css,
+    Binary, // images, fonts and media, which the webview never runs
+}
+
+// function to get the kind of a file from its extension, None when the extension is unknown
+fn extension_kind(path: &str) -> Option<ResourceKind> {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "xhtml" | "xht" | "html" | "htm" | "svg" | "xml" | "opf" | "ncx" | "smil" => {
+            Some(ResourceKind::Markup)
+        }
+        "css" => Some(ResourceKind::Css),
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "avif" | "tif" | "tiff" | "ico"
+        | "ttf" | "otf" | "woff" | "woff2" | "mp3" | "m4a" | "mp4" | "ogg" | "oga" | "opus"
+        | "wav" | "webm" => Some(ResourceKind::Binary),
+        _ => None,
+    }
+}
+
+// function to get the kind of a file from its manifest media type, None when the media type is unknown
+fn media_type_kind(media_type: &str) -> Option<ResourceKind> {
+    let media_type = media_type.split(';').next().unwrap_or_default();
+    let media_type = media_type.trim().to_ascii_lowercase();
+
+    if media_type == "text/html" || media_type.ends_with("/xml") || media_type.ends_with("+xml") {
+        Some(ResourceKind::Markup)
+    } else if media_type == "text/css" {
+        Some(ResourceKind::Css)
+    } else if [
+        "image/",
+        "font/",
+        "audio/",
+        "video/",
+        "application/font-",
+        "application/x-font-",
+    ]
+    .iter()
+    .any(|prefix| media_type.starts_with(prefix))
+        || media_type == "application/vnd.ms-opentype"
+    {
+        Some(ResourceKind::Binary)
+    } else {
+        None
+    }
+}
+
+// function to sanitize a file of an epub depending on its type, taken from its manifest media type and its extension
+// the webview renders a file by its extension while the reader renders spine documents by their media type,
+// so a file is only returned as it is when neither says it is markup or css, and unknown files are sanitized as markup
+pub fn sanitize_resource(path: &str, media_type: Option<&str>, data: Vec<u8>) -> Vec<u8> {
+    let kinds = [extension_kind(path), media_type.and_then(media_type_kind)];
+    let kind = if kinds.contains(&Some(ResourceKind::Markup)) {
+        ResourceKind::Markup
+    } else if kinds.contains(&Some(ResourceKind::Css)) {
+        ResourceKind::Css
+    } else if kinds.contains(&Some(ResourceKind::Binary)) {
+        ResourceKind::Binary
+    } else {
+        ResourceKind::Markup
+    };
+
+    match kind {
+        ResourceKind::Markup => sanitize_markup(&String::from_utf8_lossy(&data)).into_bytes(),
+        ResourceKind::Css => sanitize_css(&String::from_utf8_lossy(&data)).into_bytes(),
+        ResourceKind::Binary => data,
+    }
+}
@@ -0,0 +1,177 @@
+/*
+    reader module:
+    search.rs searches the text of a book for a query
+*/
+
+// importing crates and modules
+use crate::reader::cache::open_book;
+use crate::reader::epub::read_entry_string;
+use crate::reader::position::Locator;
+use crate::reader::text::DocumentText;
+use crate::reader::toc::{read_toc, spine_chapters};
+use crate::reader::ReaderError;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+// number of results returned if the ui does not set a limit
+const DEFAULT_LIMIT: usize = 500;
+
+// number of characters shown before and after a match
+const CONTEXT_LENGTH: usize = 60;
+
+// SearchOptions struct: how the query is matched
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SearchOptions {
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub whole_word: bool,
+    #[serde(default)]
+    pub regex: bool, // query is a regular expression instead of plain text
+    pub limit: Option<usize>,
+}
+
+// SearchMatch struct: a match with the text around it, the ui shows before + matched + after
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchMatch {
+    pub spine_index: usize,
+    pub chapter: Option<String>,
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+    pub locator: Locator,
+}
+
+// SearchResults struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    pub truncated: bool, // set when more matches were found than the limit
+}
+
+// function to build the regex for a query
+pub fn build_query(query: &str, options: &SearchOptions) -> Result<Regex, ReaderError> {
+    if query.trim().is_empty() {
+        return Err(ReaderError::ValidationError(
+            "search query is empty".to_string(),
+        ));
+    }
+
+    let mut pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query.trim())
+    };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|e| ReaderError::ValidationError(format!("invalid search query: {}", e)))
+}
+
+// function to cut the text around a match at character boundaries
+pub fn snippet(text: &str, start: usize, end: usize) -> (String, String) {
+    let before_start = text[..start]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_LENGTH - 1)
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    let after_end = text[end..]
+        .char_indices()
+        .nth(CONTEXT_LENGTH)
+        .map(|(index, _)| end + index)
+        .unwrap_or(text.len());
+
+    (
+        text[before_start..start].replace('\n', " "),
+        text[end..after_end].replace('\n', " "),
+    )
+}
+
+// function to escape characters with special meaning in cfi assertions
+fn escape_cfi(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '^' | '[' | ']' | '(' | ')' | ',' | ';' | '=') {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// function to build the full cfi of a range inside a spine document
+pub fn spine_cfi(index: usize, idref: &str, range: &str) -> String {
+    format!(
+        "epubcfi(/6/{}[{}]!{})",
+        (index + 1) * 2,
+        escape_cfi(idref),
+        range
+    )
+}
+
+// e_pub_search command: searches the text of every spine document of an epub when invoked
+#[tauri::command]
+pub fn e_pub_search(
+    path: &str,
+    query: &str,
+    options: Option<SearchOptions>,
+) -> Result<SearchResults, ReaderError> {
+    let options = options.unwrap_or_default();
+    let regex = build_query(query, &options)?;
+    let limit = options.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let (mut archive, package) = open_book(path)?;
+    let chapters = read_toc(&mut archive, &package)
+        .map(|toc| spine_chapters(&package, &toc))
+        .unwrap_or_default();
+
+    let mut matches = Vec::new();
+    for (index, spine_item) in package.spine.iter().enumerate() {
+        let Some(item) = package.item_by_id(&spine_item.idref) else {
+            continue;
+        };
+        let Ok(data) = read_entry_string(&mut archive, &item.href) else {
+            continue; // missing documents should not break the search
+        };
+        let document = DocumentText::new(&data);
+
+        for found in regex.find_iter(&document.text) {
+            if found.is_empty() {
+                continue;
+            }
+            if matches.len() == limit {
+                return Ok(SearchResults {
+                    matches,
+                    truncated: true,
+                });
+            }
+            let Some(range) = document.cfi_range(found.start(), found.end()) else {
+                continue;
+            };
+
+            let (before, after) = snippet(&document.text, found.start(), found.end());
+            matches.push(SearchMatch {
+                spine_index: index,
+                chapter: chapters.get(index).cloned().flatten(),
+                before,
+                matched: found.as_str().replace('\n', " "),
+                after,
+                locator: Locator::Epub {
+                    cfi: spine_cfi(index, &spine_item.idref, &range),
+                    href: Some(item.href.clone()),
+                },
+            });
+        }
+    }
+
+    Ok(SearchResults {
+        matches,
+        truncated: false,
+    })
+}
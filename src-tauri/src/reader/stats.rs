@@ -0,0 +1,492 @@
+/*
+    reader module:
+    stats.rs records reading sessions and works out reading time, speed and streaks
+*/
+
+// importing crates and modules
+use crate::functions::current_time_ms;
+use crate::reader::cache::open_book;
+use crate::reader::comic::list_pages;
+use crate::reader::library::{BookFormat, Library};
+use crate::reader::pdf::load_document;
+use crate::reader::pdf_info::{read_outline, OutlineItem};
+use crate::reader::position::{cfi_spine_index, load_positions, Locator};
+use crate::reader::toc::{chapter_span, read_toc, spine_chapters};
+use crate::reader::{read_json, save_json, ReaderError};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+// path of the sessions file inside the app data directory
+const SESSIONS_FILE: &str = "library/sessions.json";
+
+// a session ends when the reading position was not saved for this long
+const IDLE_GAP_MS: u128 = 5 * 60 * 1000;
+
+// shortest time a page can be read in, faster page changes are counted as skipping
+const MIN_PAGE_MS: u128 = 2000;
+
+// larger forward moves are counted as jumps (e.g. from the toc) instead of reading
+const MAX_PERCENT_STEP: f32 = 2.0;
+
+// pages and progress needed before the reading speed of a book is trusted
+const MIN_SPEED_PAGES: u32 = 10;
+const MIN_SPEED_PERCENT: f32 = 1.0;
+
+// number of days shown in the daily stats if the ui does not set it
+const DEFAULT_DAYS: u32 = 30;
+
+// length of a day in milliseconds
+const DAY_MS: i128 = 24 * 60 * 60 * 1000;
+
+// sessions older than this many days are merged into one session per book and day
+const COMPACT_AFTER_DAYS: i128 = 60;
+
+// ReadingSession struct: continuous reading of a book
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReadingSession {
+    pub book_id: String,
+    pub started_at: u128,
+    pub ended_at: u128,
+    pub start_percentage: f32,
+    pub end_percentage: f32,
+    pub start_page: Option<u32>, // only set for pdfs and comics
+    pub end_page: Option<u32>,
+    pub pages: u32,    // pages read, without skipped pages
+    pub progress: f32, // percentage advanced while reading, without jumps
+}
+
+// implementations for ReadingSession struct
+impl ReadingSession {
+    pub fn duration(&self) -> u128 {
+        self.ended_at.saturating_sub(self.started_at)
+    }
+}
+
+// BookStats struct: reading totals of a book
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BookStats {
+    pub book_id: String,
+    pub title: Option<String>, // None if the book was removed from the library
+    pub time_ms: u128,
+    pub sessions: usize,
+    pub pages: u32,
+    pub progress: f32,
+    pub last_read_at: u128,
+}
+
+// DayStats struct: reading totals of a day in local time
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DayStats {
+    pub date: String, // YYYY-MM-DD
+    pub time_ms: u128,
+    pub pages: u32,
+}
+
+// ReadingStats struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReadingStats {
+    pub total_time_ms: u128,
+    pub books: Vec<BookStats>, // most recently read first
+    pub days: Vec<DayStats>,   // oldest first, including days without reading
+    pub pages_per_day: f32,    // average over the shown days
+    pub current_streak: u32,   // days in a row with reading, up to today (or yesterday)
+    pub longest_streak: u32,
+}
+
+// ReadingEstimate struct: estimated time left at the saved position of a book
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReadingEstimate {
+    pub chapter: Option<String>,
+    pub chapter_ms_left: Option<u128>,
+    pub book_ms_left: Option<u128>,
+    pub ms_per_page: Option<f64>, // measured speed, set for pdfs and comics
+    pub ms_per_percent: Option<f64>, // measured speed, set for epubs
+}
+
+// function to load all recorded sessions, oldest first
+pub fn load_sessions() -> Result<Vec<ReadingSession>, ReaderError> {
+    read_json(SESSIONS_FILE)
+}
+
+// function to save all sessions
+fn save_sessions(sessions: &[ReadingSession]) -> Result<(), ReaderError> {
+    save_json(SESSIONS_FILE, sessions)
+}
+
+// function to move recorded sessions of a book to its new id
+pub fn move_sessions(old_id: &str, new_id: &str) -> Result<(), ReaderError> {
+    let mut sessions = load_sessions()?;
+    let mut moved = false;
+    for session in sessions
+        .iter_mut()
+        .filter(|session| session.book_id == old_id)
+    {
+        session.book_id = new_id.to_string();
+        moved = true;
+    }
+    if moved {
+        save_sessions(&sessions)?;
+    }
+    Ok(())
+}
+
+// function to merge old sessions into one session per book and day, so the sessions file stays small
+// totals of books and days are kept, only the boundaries of old sessions are lost
+fn compact_sessions(sessions: Vec<ReadingSession>, now: u128) -> Vec<ReadingSession> {
+    let cutoff = local_day(now, 0) - COMPACT_AFTER_DAYS;
+    let mut compacted: Vec<ReadingSession> = Vec::with_capacity(sessions.len());
+
+    for session in sessions {
+        let day = local_day(session.started_at, 0);
+        let target = compacted
+            .iter_mut()
+            .rev()
+            .take_while(|kept| local_day(kept.started_at, 0) == day)
+            .find(|kept| day < cutoff && kept.book_id == session.book_id);
+
+        match target {
+            Some(kept) => {
+                kept.ended_at = kept.started_at + kept.duration() + session.duration();
+                kept.end_percentage = session.end_percentage;
+                kept.end_page = session.end_page;
+                kept.pages += session.pages;
+                kept.progress += session.progress;
+            }
+            None => compacted.push(session),
+        }
+    }
+    compacted
+}
+
+// function to record a saved reading position, extending the current session or starting a new one
+pub fn record_progress(
+    book_id: &str,
+    locator: &Locator,
+    percentage: f32,
+) -> Result<(), ReaderError> {
+    let now = current_time_ms();
+    let page = match locator {
+        Locator::Pdf { page, .. } | Locator::Comic { page, .. } => Some(*page),
+        Locator::Epub { .. } => None,
+    };
+    let mut sessions = load_sessions()?;
+
+    // only the latest session can continue, reading another book in between ends it
+    let current = sessions.last_mut().filter(|session| {
+        session.book_id == book_id && now.saturating_sub(session.ended_at) <= IDLE_GAP_MS
+    });
+
+    match current {
+        Some(session) => {
+            let elapsed = now.saturating_sub(session.ended_at);
+            if let (Some(previous), Some(page)) = (session.end_page, page) {
+                if page > previous {
+                    let readable = (elapsed / MIN_PAGE_MS).min(u32::MAX as u128) as u32;
+                    session.pages += (page - previous).min(readable);
+                }
+            }
+            let step = percentage - session.end_percentage;
+            if step > 0.0 && step <= MAX_PERCENT_STEP {
+                session.progress += step;
+            }
+
+            session.ended_at = now;
+            session.end_percentage = percentage;
+            session.end_page = page;
+        }
+        None => {
+            sessions = compact_sessions(sessions, now);
+            sessions.push(ReadingSession {
+                book_id: book_id.to_string(),
+                started_at: now,
+                ended_at: now,
+                start_percentage: percentage,
+                end_percentage: percentage,
+                start_page: page,
+                end_page: page,
+                pages: 0,
+                progress: 0.0,
+            });
+        }
+    }
+
+    save_sessions(&sessions)
+}
+
+// function to convert days since 1970-01-01 to a YYYY-MM-DD date
+// uses the days to civil algorithm for the proleptic gregorian calendar
+pub fn civil_date(days: i128) -> String {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i128::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// function to get the local day (days since 1970-01-01) of a time
+fn local_day(time_ms: u128, utc_offset_minutes: i32) -> i128 {
+    (time_ms as i128 + utc_offset_minutes as i128 * 60_000).div_euclid(DAY_MS)
+}
+
+// function to get the current and longest streak from the days with reading
+fn streaks(days: &[i128], today: i128) -> (u32, u32) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<i128> = None;
+    for &day in days {
+        run = match previous {
+            Some(previous) if day == previous + 1 => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    // a streak is still alive if the user has not read yet today
+    let current = match previous {
+        Some(last) if last >= today - 1 => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+// function to measure the reading speed of a book in ms per page (pdfs and comics) and ms per percent (epubs)
+// pdfs fall back to the speed over all pdfs, as pages are comparable between books
+fn reading_speed(sessions: &[ReadingSession], book_id: &str) -> (Option<f64>, Option<f64>) {
+    let (mut book_ms, mut book_pages, mut book_progress) = (0, 0, 0.0);
+    let (mut all_ms, mut all_pages) = (0, 0);
+    let mut book_progress_ms = 0;
+
+    for session in sessions {
+        if session.start_page.is_some() {
+            all_ms += session.duration();
+            all_pages += session.pages;
+        }
+        if session.book_id == book_id {
+            if session.start_page.is_some() {
+                book_ms += session.duration();
+                book_pages += session.pages;
+            } else {
+                book_progress_ms += session.duration();
+                book_progress += session.progress;
+            }
+        }
+    }
+
+    let ms_per_page = if book_pages >= MIN_SPEED_PAGES {
+        Some(book_ms as f64 / book_pages as f64)
+    } else if all_pages >= MIN_SPEED_PAGES {
+        Some(all_ms as f64 / all_pages as f64)
+    } else {
+        None
+    };
+    let ms_per_percent = (book_progress >= MIN_SPEED_PERCENT)
+        .then(|| book_progress_ms as f64 / book_progress as f64);
+
+    (ms_per_page, ms_per_percent)
+}
+
+// function to get the start page and title of all outline items
+fn outline_pages(items: &[OutlineItem], pages: &mut Vec<(u32, String)>) {
+    for item in items {
+        if let Some(page) = item.page {
+            pages.push((page, item.title.clone()));
+        }
+        outline_pages(&item.children, pages);
+    }
+}
+
+// function to find the title and end percentage of the epub chapter containing a cfi
+// chapter length is estimated from the size of its documents, like the viewer's locations
+fn e_pub_chapter_end(path: &str, cfi: &str) -> Result<(Option<String>, f32), ReaderError> {
+    let index = cfi_spine_index(cfi)
+        .ok_or_else(|| ReaderError::InvalidData(format!("{} has no spine step", cfi)))?;
+
+    let (mut archive, package) = open_book(path)?;
+    let toc = read_toc(&mut archive, &package)?;
+
+    let sizes: Vec<u64> = package
+        .spine
+        .iter()
+        .map(|spine_item| {
+            package
+                .item_by_id(&spine_item.idref)
+                .and_then(|item| archive.by_name(&item.href).ok().map(|entry| entry.size()))
+                .unwrap_or(0)
+        })
+        .collect();
+    let total: u64 = sizes.iter().sum();
+    if total == 0 {
+        return Err(ReaderError::InvalidData(format!("{} has no content", path)));
+    }
+
+    let span = chapter_span(&package, &toc, index);
+    let before_end: u64 = sizes.iter().take(span.end).sum();
+    let chapter = spine_chapters(&package, &toc).get(index).cloned().flatten();
+    Ok((chapter, before_end as f32 / total as f32 * 100.0))
+}
+
+// reading_stats_get command: returns reading time per book and day and reading streaks when invoked
+// days are counted in the local time of the ui, given as offset from utc (e.g. 60 for utc+1)
+#[tauri::command]
+pub fn reading_stats_get(
+    utc_offset_minutes: Option<i32>,
+    days: Option<u32>,
+) -> Result<ReadingStats, ReaderError> {
+    let sessions = load_sessions()?;
+    let library = Library::load()?;
+    let offset = utc_offset_minutes.unwrap_or(0);
+    let shown_days = days.unwrap_or(DEFAULT_DAYS).max(1);
+    let today = local_day(current_time_ms(), offset);
+
+    let mut books: HashMap<&str, BookStats> = HashMap::new();
+    let mut totals: BTreeMap<i128, (u128, u32)> = BTreeMap::new(); // day -> (time, pages)
+    for session in &sessions {
+        let stats = books
+            .entry(session.book_id.as_str())
+            .or_insert_with(|| BookStats {
+                book_id: session.book_id.clone(),
+                title: library
+                    .book(&session.book_id)
+                    .map(|book| book.metadata.title.clone()),
+                time_ms: 0,
+                sessions: 0,
+                pages: 0,
+                progress: 0.0,
+                last_read_at: 0,
+            });
+        stats.time_ms += session.duration();
+        stats.sessions += 1;
+        stats.pages += session.pages;
+        stats.progress += session.progress;
+        stats.last_read_at = stats.last_read_at.max(session.ended_at);
+
+        // sessions are counted on the day they started
+        let day = totals
+            .entry(local_day(session.started_at, offset))
+            .or_default();
+        day.0 += session.duration();
+        day.1 += session.pages;
+    }
+
+    let mut books: Vec<BookStats> = books.into_values().collect();
+    books.sort_by_key(|book| Reverse(book.last_read_at));
+
+    let first_day = today - shown_days as i128 + 1;
+    let days: Vec<DayStats> = (first_day..=today)
+        .map(|day| {
+            let (time_ms, pages) = totals.get(&day).copied().unwrap_or_default();
+            DayStats {
+                date: civil_date(day),
+                time_ms,
+                pages,
+            }
+        })
+        .collect();
+    let pages: u32 = days.iter().map(|day| day.pages).sum();
+
+    // a day only counts for streaks if some time was actually spent reading
+    let reading_days: Vec<i128> = totals
+        .iter()
+        .filter(|(_, (time_ms, _))| *time_ms > 0)
+        .map(|(day, _)| *day)
+        .collect();
+    let (current_streak, longest_streak) = streaks(&reading_days, today);
+
+    Ok(ReadingStats {
+        total_time_ms: books.iter().map(|book| book.time_ms).sum(),
+        books,
+        pages_per_day: pages as f32 / shown_days as f32,
+        days,
+        current_streak,
+        longest_streak,
+    })
+}
+
+// reading_estimate_get command: returns the estimated time left in the current chapter and book when invoked
+#[tauri::command]
+pub fn reading_estimate_get(book_id: &str) -> Result<ReadingEstimate, ReaderError> {
+    let library = Library::load()?;
+    let book = library
+        .book(book_id)
+        .ok_or_else(|| ReaderError::NotFound(format!("book {} is not in library", book_id)))?;
+    let Some(position) = load_positions()?.remove(book_id) else {
+        return Ok(ReadingEstimate::default()); // the book was never opened
+    };
+
+    let (ms_per_page, ms_per_percent) = reading_speed(&load_sessions()?, book_id);
+    let mut estimate = ReadingEstimate {
+        ms_per_page,
+        ms_per_percent,
+        ..Default::default()
+    };
+
+    match (&position.locator, book.format) {
+        (
+            Locator::Pdf {
+                page, page_count, ..
+            },
+            BookFormat::Pdf,
+        ) => {
+            let document = load_document(&book.path).ok();
+            let page_count = page_count
+                .or_else(|| document.as_ref().map(|d| d.get_pages().len() as u32))
+                .unwrap_or(*page);
+
+            // the chapter is the last outline item starting before the page, it ends where the next one starts
+            let outline = document.as_ref().map(read_outline).unwrap_or_default();
+            let mut starts = Vec::new();
+            outline_pages(&outline, &mut starts);
+            estimate.chapter = starts
+                .iter()
+                .filter(|(start, _)| start <= page)
+                .max_by_key(|(start, _)| *start)
+                .map(|(_, title)| title.clone());
+            let chapter_end = starts
+                .iter()
+                .map(|(start, _)| *start)
+                .filter(|start| start > page)
+                .min()
+                .unwrap_or(page_count.saturating_add(1));
+
+            if let Some(ms_per_page) = ms_per_page {
+                let left = |pages: u32| (pages as f64 * ms_per_page) as u128;
+                estimate.book_ms_left = Some(left(page_count.saturating_sub(*page) + 1));
+                estimate.chapter_ms_left = Some(left(chapter_end.saturating_sub(*page)));
+            }
+        }
+        (Locator::Comic { page, page_count }, BookFormat::Comic) => {
+            // comics have no chapters, only the pages left in the book are estimated
+            let page_count = page_count
+                .or_else(|| list_pages(&book.path).ok().map(|pages| pages.len() as u32))
+                .unwrap_or(*page);
+            if let Some(ms_per_page) = ms_per_page {
+                let left = page_count.saturating_sub(*page) + 1;
+                estimate.book_ms_left = Some((left as f64 * ms_per_page) as u128);
+            }
+        }
+        (Locator::Epub { cfi, .. }, format)
+            if format.is_document() || format == BookFormat::Epub =>
+        {
+            let chapter = e_pub_chapter_end(&book.path, cfi).ok();
+            estimate.chapter = chapter.as_ref().and_then(|(title, _)| title.clone());
+
+            if let Some(ms_per_percent) = ms_per_percent {
+                let left = |percent: f32| (percent.max(0.0) as f64 * ms_per_percent) as u128;
+                estimate.book_ms_left = Some(left(100.0 - position.percentage));
+                estimate.chapter_ms_left = chapter.map(|(_, end)| left(end - position.percentage));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(estimate)
+}
@@ -0,0 +1,250 @@
+/*
+    reader module:
+    text.rs converts xhtml documents of books to plain text, remembering where every character came from
+*/
+
+// importing crates and modules
+use crate::reader::xml::{decode_entities, tokenize, Token};
+
+// elements whose text is not part of the content
+const SKIPPED_ELEMENTS: [&str; 5] = ["head", "script", "style", "template", "noscript"];
+
+// elements which start a new line in the plain text
+const BLOCK_ELEMENTS: [&str; 30] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+];
+
+// html elements which never have content, even if they are not written as self closing
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+// TextPosition struct: a character inside a text node of the document
+#[derive(Debug, Clone, Copy)]
+struct TextPosition {
+    node: usize,   // index into the cfi paths of text nodes
+    offset: usize, // utf-16 offset inside the text node, as used by the dom and cfis
+    width: usize,  // utf-16 length of the source character
+}
+
+// Element struct: an open element while walking the document
+struct Element {
+    name: String,
+    path: String,                  // cfi path from the root element, e.g. /4/2
+    children: usize,               // number of child elements seen so far
+    chunk: Option<(usize, usize)>, // text node currently being read: (node, utf-16 offset)
+}
+
+// DocumentText struct: plain text of a document with the source position of every character
+pub struct DocumentText {
+    pub text: String,
+    positions: Vec<Option<TextPosition>>, // indexed by byte, set on the first byte of mapped characters
+    nodes: Vec<String>,                   // cfi paths of text nodes
+}
+
+// implementations for DocumentText struct
+impl DocumentText {
+    // creates the plain text of an xhtml document
+    // whitespace is collapsed and block elements are separated by new lines
+    pub fn new(data: &str) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            positions: Vec::new(),
+            nodes: Vec::new(),
+        };
+        let mut stack: Vec<Element> = Vec::new();
+        let mut skipped = 0; // depth inside skipped elements
+
+        for token in tokenize(data) {
+            match token {
+                Token::Tag(tag) => {
+                    let void = VOID_ELEMENTS.contains(&tag.name.as_str());
+                    if BLOCK_ELEMENTS.contains(&tag.name.as_str()) {
+                        document.push_newline();
+                    }
+
+                    if tag.is_close() {
+                        if void {
+                            continue; // already handled as self closing
+                        }
+                        if let Some(index) = stack.iter().rposition(|e| e.name == tag.name) {
+                            if SKIPPED_ELEMENTS.contains(&tag.name.as_str()) {
+                                skipped -= 1;
+                            }
+                            stack.truncate(index);
+                        }
+                        if let Some(parent) = stack.last_mut() {
+                            parent.chunk = None; // text after an element is a new text node
+                        }
+                        continue;
+                    }
+
+                    // the root element has an empty path, its children start from /2
+                    let path = match stack.last_mut() {
+                        Some(parent) => {
+                            parent.children += 1;
+                            parent.chunk = None;
+                            format!("{}/{}", parent.path, parent.children * 2)
+                        }
+                        None => String::new(),
+                    };
+
+                    if tag.is_open() && !void {
+                        if SKIPPED_ELEMENTS.contains(&tag.name.as_str()) {
+                            skipped += 1;
+                        }
+                        stack.push(Element {
+                            name: tag.name,
+                            path,
+                            children: 0,
+                            chunk: None,
+                        });
+                    }
+                }
+                Token::Text(raw) => {
+                    let Some(parent) = stack.last_mut() else {
+                        continue; // text outside the root element
+                    };
+
+                    // consecutive text tokens (e.g. around a comment) belong to the same text node
+                    let (node, mut offset) = match parent.chunk {
+                        Some(chunk) => chunk,
+                        None => {
+                            document.nodes.push(format!(
+                                "{}/{}",
+                                parent.path,
+                                parent.children * 2 + 1
+                            ));
+                            (document.nodes.len() - 1, 0)
+                        }
+                    };
+
+                    for c in decode_entities(raw).chars() {
+                        if skipped == 0 {
+                            document.push_char(
+                                c,
+                                TextPosition {
+                                    node,
+                                    offset,
+                                    width: c.len_utf16(),
+                                },
+                            );
+                        }
+                        offset += c.len_utf16();
+                    }
+                    parent.chunk = Some((node, offset));
+                }
+            }
+        }
+
+        document.push_newline();
+        document.text.pop(); // trailing new line
+        document.positions.truncate(document.text.len());
+        document
+    }
+
+    // appends a character, collapsing whitespace
+    fn push_char(&mut self, c: char, position: TextPosition) {
+        if c.is_whitespace() {
+            if self.text.is_empty() || self.text.ends_with([' ', '\n']) {
+                return;
+            }
+            self.text.push(' ');
+            self.positions.push(Some(position));
+            return;
+        }
+
+        self.text.push(c);
+        self.positions.push(Some(position));
+        self.positions.resize(self.text.len(), None);
+    }
+
+    // starts a new line, unless the text is empty or already ends with one
+    fn push_newline(&mut self) {
+        if self.text.ends_with(' ') {
+            self.text.pop();
+            self.positions.pop();
+        }
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+            self.positions.push(None);
+        }
+    }
+
+    // returns the cfi range (without the spine part) of text[start..end], e.g. /4/2,/1:10,/3:5
+    pub fn cfi_range(&self, start: usize, end: usize) -> Option<String> {
+        let first = self.positions.get(start..end)?.iter().flatten().next()?;
+        let last = self.positions.get(start..end)?.iter().flatten().last()?;
+
+        let start_path = format!("{}:{}", self.nodes[first.node], first.offset);
+        let end_path = format!("{}:{}", self.nodes[last.node], last.offset + last.width);
+
+        // the common parent is written once, followed by the two relative paths
+        let start_steps: Vec<&str> = start_path.split('/').collect();
+        let end_steps: Vec<&str> = end_path.split('/').collect();
+        let common = start_steps
+            .iter()
+            .zip(end_steps.iter())
+            .take(start_steps.len().min(end_steps.len()) - 1)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        Some(format!(
+            "{},/{},/{}",
+            start_steps[..common].join("/"),
+            start_steps[common..].join("/"),
+            end_steps[common..].join("/")
+        ))
+    }
+
+    // returns the byte index in the text of a cfi location (without the spine part), e.g. /4/2/1:10
+    // locations pointing to an element give the start of its first text
+    pub fn text_index(&self, path: &str, offset: usize) -> Option<usize> {
+        let element = format!("{}/", path);
+        self.positions
+            .iter()
+            .enumerate()
+            .find_map(|(index, position)| {
+                let position = position.as_ref()?;
+                let node = &self.nodes[position.node];
+                let found = (node == path && position.offset + position.width > offset)
+                    || node.starts_with(&element);
+                found.then_some(index)
+            })
+    }
+}
+
+// function to get the plain text of an xhtml document
+pub fn html_to_text(data: &str) -> String {
+    DocumentText::new(data).text
+}
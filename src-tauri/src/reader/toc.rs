@@ -0,0 +1,465 @@
+/*
+    reader module:
+    toc.rs parses table of contents of an epub (epub 2 toc.ncx or epub 3 nav.xhtml)
+*/
+
+// importing crates and modules
+use crate::reader::cache::open_book;
+use crate::reader::epub::{read_entry_string, Package};
+use crate::reader::xml::{
+    collapse_whitespace, decode_entities, parent_dir, resolve_href, tokenize, Tag, TagKind, Token,
+};
+use crate::reader::ReaderError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::ops::Range;
+use zip::ZipArchive;
+
+// format the toc was parsed from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TocSource {
+    Nav,
+    Ncx,
+    None,
+}
+
+// TocEntry struct: a node of the nested toc tree (href is a path inside the archive, with fragment if any)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TocEntry {
+    pub id: Option<String>,
+    pub label: String,
+    pub href: Option<String>,
+    pub play_order: u32,
+    pub children: Vec<TocEntry>,
+}
+
+// PageTarget struct: an entry of the page list (print page numbers)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PageTarget {
+    pub label: String,
+    pub href: String,
+    pub play_order: u32,
+}
+
+// Landmark struct: structural points of the book like cover, toc or bodymatter
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Landmark {
+    pub kind: String,
+    pub label: String,
+    pub href: String,
+}
+
+// TableOfContents struct
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TableOfContents {
+    pub source: TocSource,
+    pub title: Option<String>,
+    pub entries: Vec<TocEntry>,
+    pub page_list: Vec<PageTarget>,
+    pub landmarks: Vec<Landmark>,
+}
+
+// implementations for TableOfContents struct
+impl TableOfContents {
+    pub fn empty() -> Self {
+        Self {
+            source: TocSource::None,
+            title: None,
+            entries: Vec::new(),
+            page_list: Vec::new(),
+            landmarks: Vec::new(),
+        }
+    }
+
+    // returns all entries of the tree in reading (depth first) order
+    pub fn flatten(&self) -> Vec<&TocEntry> {
+        fn walk<'a>(entries: &'a [TocEntry], list: &mut Vec<&'a TocEntry>) {
+            for entry in entries {
+                list.push(entry);
+                walk(&entry.children, list);
+            }
+        }
+
+        let mut list = Vec::new();
+        walk(&self.entries, &mut list);
+        list
+    }
+}
+
+// e_pub_toc_get command: returns the table of contents of an epub when invoked
+#[tauri::command]
+pub fn e_pub_toc_get(path: &str) -> Result<TableOfContents, ReaderError> {
+    let (mut archive, package) = open_book(path)?;
+
+    read_toc(&mut archive, &package)
+}
+
+// function to read the toc of an epub, epub 3 nav document is preferred over ncx
+pub fn read_toc<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    package: &Package,
+) -> Result<TableOfContents, ReaderError> {
+    let mut toc = TableOfContents::empty();
+
+    if let Some(nav) = package.nav_item() {
+        let nav_data = read_entry_string(archive, &nav.href)?;
+        toc = parse_nav(&nav_data, parent_dir(&nav.href));
+    }
+
+    // fall back to ncx if there was no nav document (or it had no toc)
+    if toc.entries.is_empty() {
+        if let Some(ncx) = package.ncx_item() {
+            let ncx_data = read_entry_string(archive, &ncx.href)?;
+            let ncx_toc = parse_ncx(&ncx_data, parent_dir(&ncx.href));
+
+            if !ncx_toc.entries.is_empty() {
+                if toc.page_list.is_empty() {
+                    toc.page_list = ncx_toc.page_list;
+                }
+                toc.source = ncx_toc.source;
+                toc.title = toc.title.or(ncx_toc.title);
+                toc.entries = ncx_toc.entries;
+            }
+        }
+    }
+
+    // use epub 2 guide as landmarks if nav document did not provide them
+    if toc.landmarks.is_empty() {
+        toc.landmarks = package
+            .guide
+            .iter()
+            .map(|reference| Landmark {
+                kind: reference.kind.clone(),
+                label: reference.title.clone(),
+                href: reference.href.clone(),
+            })
+            .collect();
+    }
+
+    Ok(toc)
+}
+
+// function to get the first toc label of every document (document href -> label)
+fn document_labels(toc: &TableOfContents) -> HashMap<&str, &str> {
+    let mut labels: HashMap<&str, &str> = HashMap::new();
+    for entry in toc.flatten() {
+        if let Some(href) = &entry.href {
+            let document = href.split('#').next().unwrap_or(href);
+            labels.entry(document).or_insert(&entry.label);
+        }
+    }
+    labels
+}
+
+// function to find the chapter title of every spine item (spine index -> title)
+// spine items without a toc entry belong to the chapter before them
+pub fn spine_chapters(package: &Package, toc: &TableOfContents) -> Vec<Option<String>> {
+    let labels = document_labels(toc);
+    let mut current: Option<String> = None;
+    package
+        .spine
+        .iter()
+        .map(|spine_item| {
+            let label = package
+                .item_by_id(&spine_item.idref)
+                .and_then(|item| labels.get(item.href.as_str()));
+            if let Some(label) = label {
+                current = Some(label.to_string());
+            }
+            current.clone()
+        })
+        .collect()
+}
+
+// function to find the spine items of the chapter containing a spine item
+// a chapter starts at a document with a toc entry and lasts until the next one, like in spine_chapters
+// without toc entries for the spine (an empty toc), every spine document is a chapter of its own
+pub fn chapter_span(package: &Package, toc: &TableOfContents, index: usize) -> Range<usize> {
+    let labels = document_labels(toc);
+    let starts_chapter = |spine_index: usize| {
+        package
+            .spine
+            .get(spine_index)
+            .and_then(|spine_item| package.item_by_id(&spine_item.idref))
+            .is_some_and(|item| labels.contains_key(item.href.as_str()))
+    };
+    if !(0..package.spine.len()).any(starts_chapter) {
+        return index..index + 1;
+    }
+
+    let start = (0..=index).rev().find(|&i| starts_chapter(i)).unwrap_or(0);
+    let end = (index + 1..package.spine.len())
+        .find(|&i| starts_chapter(i))
+        .unwrap_or(package.spine.len());
+    start..end.max(index + 1)
+}
+
+// function to attach a finished entry to its parent (or to the root list)
+fn attach(stack: &mut [TocEntry], root: &mut Vec<TocEntry>, entry: TocEntry) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(entry),
+        None => root.push(entry),
+    }
+}
+
+// function to parse epub 2 toc.ncx data
+pub fn parse_ncx(data: &str, base_dir: &str) -> TableOfContents {
+    let mut toc = TableOfContents::empty();
+    toc.source = TocSource::Ncx;
+
+    let mut stack: Vec<TocEntry> = Vec::new(); // open navPoints
+    let mut page: Option<PageTarget> = None; // open pageTarget
+    let mut in_doc_title = false;
+    let mut in_text = false;
+    let mut text = String::new();
+
+    for token in tokenize(data) {
+        match token {
+            Token::Text(raw) => {
+                if in_text {
+                    text.push_str(&decode_entities(raw));
+                }
+            }
+            Token::Tag(tag) => match (tag.name.as_str(), tag.is_close()) {
+                ("doctitle", closing) => in_doc_title = !closing,
+                ("navpoint", false) => stack.push(TocEntry {
+                    id: tag.attr("id"),
+                    label: String::new(),
+                    href: None,
+                    play_order: tag
+                        .attr("playorder")
+                        .and_then(|o| o.parse().ok())
+                        .unwrap_or(0),
+                    children: Vec::new(),
+                }),
+                ("navpoint", true) => {
+                    if let Some(entry) = stack.pop() {
+                        attach(&mut stack, &mut toc.entries, entry);
+                    }
+                }
+                ("pagetarget", false) => {
+                    page = Some(PageTarget {
+                        label: tag.attr("value").unwrap_or_default(),
+                        href: String::new(),
+                        play_order: tag
+                            .attr("playorder")
+                            .and_then(|o| o.parse().ok())
+                            .unwrap_or(0),
+                    })
+                }
+                ("pagetarget", true) => {
+                    if let Some(target) = page.take() {
+                        toc.page_list.push(target);
+                    }
+                }
+                ("text", false) => {
+                    in_text = true;
+                    text.clear();
+                }
+                ("text", true) => {
+                    in_text = false;
+                    let label = collapse_whitespace(&text);
+
+                    // text belongs to the innermost open element having a label
+                    if let Some(target) = page.as_mut() {
+                        target.label = label;
+                    } else if let Some(entry) = stack.last_mut() {
+                        if entry.label.is_empty() {
+                            entry.label = label;
+                        }
+                    } else if in_doc_title {
+                        toc.title = Some(label);
+                    }
+                }
+                ("content", false) => {
+                    if let Some(src) = tag.attr("src") {
+                        let href = resolve_href(base_dir, &src);
+                        if let Some(target) = page.as_mut() {
+                            target.href = href;
+                        } else if let Some(entry) = stack.last_mut() {
+                            entry.href.get_or_insert(href);
+                        }
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+
+    // close navPoints left open by malformed files
+    while let Some(entry) = stack.pop() {
+        attach(&mut stack, &mut toc.entries, entry);
+    }
+
+    // some ncx files omit playOrder, number them in reading order instead
+    if toc.flatten().iter().all(|entry| entry.play_order == 0) {
+        number_entries(&mut toc.entries, &mut 1);
+    }
+
+    toc
+}
+
+// kind of nav element in an epub 3 navigation document
+#[derive(PartialEq)]
+enum NavKind {
+    Toc,
+    PageList,
+    Landmarks,
+    Other,
+}
+
+// function to parse epub 3 nav.xhtml data
+pub fn parse_nav(data: &str, base_dir: &str) -> TableOfContents {
+    let mut toc = TableOfContents::empty();
+    toc.source = TocSource::Nav;
+
+    let mut nav: Option<NavKind> = None;
+    let mut stack: Vec<(TocEntry, Option<String>)> = Vec::new(); // open list items with their epub:type
+    let mut finished: Vec<(TocEntry, Option<String>)> = Vec::new(); // top level list items of current nav
+    let mut label: Option<(String, usize)> = None; // tag name and nesting depth of the label being read
+    let mut heading: Option<String> = None; // heading text of the toc nav
+    let mut in_heading = false;
+    let mut text = String::new();
+
+    for token in tokenize(data) {
+        let tag: Tag = match token {
+            Token::Text(raw) => {
+                if label.is_some() || in_heading {
+                    text.push_str(&decode_entities(raw));
+                }
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        // reading a label: track nesting of its tag until it is closed
+        if let Some((name, depth)) = label.as_mut() {
+            if tag.name == *name {
+                match tag.kind {
+                    TagKind::Open => *depth += 1,
+                    TagKind::Close => *depth -= 1,
+                    TagKind::SelfClosing => {}
+                }
+            }
+            if *depth == 0 {
+                if let Some((entry, _)) = stack.last_mut() {
+                    entry.label = collapse_whitespace(&text);
+                }
+                label = None;
+            }
+            continue;
+        }
+
+        match (tag.name.as_str(), tag.is_close()) {
+            ("nav", false) => {
+                nav = Some(if tag.attr_has("epub:type", "toc") {
+                    NavKind::Toc
+                } else if tag.attr_has("epub:type", "page-list") {
+                    NavKind::PageList
+                } else if tag.attr_has("epub:type", "landmarks") {
+                    NavKind::Landmarks
+                } else {
+                    NavKind::Other
+                });
+                finished.clear();
+            }
+            ("nav", true) => {
+                let items = std::mem::take(&mut finished);
+                match nav.take() {
+                    Some(NavKind::Toc) if toc.entries.is_empty() => {
+                        toc.entries = items.into_iter().map(|(entry, _)| entry).collect();
+                        toc.title = heading.take();
+                    }
+                    Some(NavKind::PageList) => {
+                        toc.page_list = items
+                            .into_iter()
+                            .filter_map(|(entry, _)| {
+                                Some(PageTarget {
+                                    label: entry.label,
+                                    href: entry.href?,
+                                    play_order: 0,
+                                })
+                            })
+                            .collect();
+                    }
+                    Some(NavKind::Landmarks) => {
+                        toc.landmarks = items
+                            .into_iter()
+                            .filter_map(|(entry, kind)| {
+                                Some(Landmark {
+                                    kind: kind.unwrap_or_default(),
+                                    label: entry.label,
+                                    href: entry.href?,
+                                })
+                            })
+                            .collect();
+                    }
+                    _ => {}
+                }
+            }
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", closing)
+                if nav == Some(NavKind::Toc) && stack.is_empty() =>
+            {
+                if closing {
+                    if in_heading {
+                        heading = Some(collapse_whitespace(&text));
+                    }
+                    in_heading = false;
+                } else {
+                    in_heading = true;
+                    text.clear();
+                }
+            }
+            ("li", false) if nav.is_some() => stack.push((
+                TocEntry {
+                    id: tag.attr("id"),
+                    label: String::new(),
+                    href: None,
+                    play_order: 0,
+                    children: Vec::new(),
+                },
+                None,
+            )),
+            ("li", true) if nav.is_some() => {
+                if let Some(item) = stack.pop() {
+                    match stack.last_mut() {
+                        Some((parent, _)) => parent.children.push(item.0),
+                        None => finished.push(item),
+                    }
+                }
+            }
+            ("a" | "span", false) if nav.is_some() && tag.is_open() => {
+                if let Some((entry, kind)) = stack.last_mut() {
+                    if entry.label.is_empty() {
+                        if let Some(href) = tag.attr("href") {
+                            entry.href = Some(resolve_href(base_dir, &href));
+                        }
+                        if let Some(epub_type) = tag.attr("epub:type") {
+                            *kind = Some(epub_type);
+                        }
+                        label = Some((tag.name.clone(), 1));
+                        text.clear();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    number_entries(&mut toc.entries, &mut 1);
+    for (i, target) in toc.page_list.iter_mut().enumerate() {
+        target.play_order = i as u32 + 1;
+    }
+
+    toc
+}
+
+// function to number entries in depth first order
+fn number_entries(entries: &mut [TocEntry], next: &mut u32) {
+    for entry in entries {
+        entry.play_order = *next;
+        *next += 1;
+        number_entries(&mut entry.children, next);
+    }
+}
@@ -0,0 +1,599 @@
+/*
+    reader module:
+    validate.rs checks an epub for common structural problems, so users can see why a book does not open
+*/
+
+// importing crates and modules
+use crate::reader::epub::{open_archive, parse_package, read_entry_string, Package};
+use crate::reader::library::BookFormat;
+use crate::reader::xml::{parent_dir, resolve_path, tokenize, TagKind, Token};
+use crate::reader::ReaderError;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::sync::OnceLock;
+use zip::{CompressionMethod, ZipArchive};
+
+// number of findings reported, a badly broken book would otherwise list every link
+const MAX_FINDINGS: usize = 200;
+
+// content of the mimetype file of every epub
+const EPUB_MIMETYPE: &str = "application/epub+zip";
+
+// elements and attributes linking to other files of the book
+const LINK_ATTRIBUTES: [(&str, &str); 13] = [
+    ("a", "href"),
+    ("area", "href"),
+    ("link", "href"),
+    ("img", "src"),
+    ("script", "src"),
+    ("audio", "src"),
+    ("video", "src"),
+    ("source", "src"),
+    ("track", "src"),
+    ("iframe", "src"),
+    ("embed", "src"),
+    ("image", "xlink:href"),
+    ("image", "href"),
+];
+
+// how serious a finding is
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,   // readers are likely to fail on the book
+    Warning, // parts of the book may not work
+    Info,
+}
+
+// part of the book a finding is about
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum FindingKind {
+    Archive,
+    Mimetype,
+    Container,
+    Package,
+    Manifest,
+    Spine,
+    Markup,
+    Link,
+}
+
+// Finding struct: a problem found in the book
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub kind: FindingKind,
+    pub location: Option<String>, // file inside the archive, with the line for markup problems
+    pub message: String,
+}
+
+// ValidationReport struct: findings of a book, most serious checks first
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+    pub errors: usize,
+    pub warnings: usize,
+    pub truncated: bool, // set when more than MAX_FINDINGS problems were found
+}
+
+// implementations for ValidationReport struct
+impl ValidationReport {
+    // adds a finding, counting it even if the list is full
+    fn add(
+        &mut self,
+        severity: Severity,
+        kind: FindingKind,
+        location: Option<&str>,
+        message: String,
+    ) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Info => {}
+        }
+        if self.findings.len() == MAX_FINDINGS {
+            self.truncated = true;
+            return;
+        }
+        self.findings.push(Finding {
+            severity,
+            kind,
+            location: location.map(String::from),
+            message,
+        });
+    }
+}
+
+// regex matching a character or entity reference at the start of the text after '&'
+fn reference_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^(?:#[0-9]+|#[xX][0-9a-fA-F]+|[A-Za-z][A-Za-z0-9]*);").unwrap())
+}
+
+// function to get the line of a byte offset
+fn line_of(data: &str, offset: usize) -> usize {
+    data[..offset.min(data.len())].matches('\n').count() + 1
+}
+
+// function to find the first well-formedness problem of an xml document with the given root element, with its line
+fn check_markup(data: &str, root: &str) -> Option<(usize, String)> {
+    let mut open: Vec<(String, usize)> = Vec::new(); // open elements and their offsets
+    let mut has_root = false;
+    let mut offset = 0;
+
+    for token in tokenize(data) {
+        match token {
+            Token::Text(text) => {
+                let start = text.as_ptr() as usize - data.as_ptr() as usize;
+                offset = start + text.len();
+                if data[..start].ends_with("<![CDATA[") {
+                    continue; // cdata sections may contain markup characters
+                }
+                if let Some(index) = text.find('<') {
+                    return Some((
+                        line_of(data, start + index),
+                        "unescaped '<' in text".to_string(),
+                    ));
+                }
+                for (index, _) in text.match_indices('&') {
+                    if !reference_regex().is_match(&text[index + 1..]) {
+                        return Some((
+                            line_of(data, start + index),
+                            "unescaped '&' in text".to_string(),
+                        ));
+                    }
+                }
+            }
+            Token::Tag(tag) => {
+                offset = tag.end;
+                if !has_root && tag.kind != TagKind::Close {
+                    has_root = true;
+                    if tag.name != root {
+                        return Some((
+                            line_of(data, tag.start),
+                            format!("root element is <{}> instead of <{}>", tag.name, root),
+                        ));
+                    }
+                }
+                match tag.kind {
+                    TagKind::Open => open.push((tag.name, tag.start)),
+                    TagKind::SelfClosing => {}
+                    TagKind::Close => match open.pop() {
+                        Some((name, _)) if name == tag.name => {}
+                        Some((name, start)) => {
+                            return Some((
+                                line_of(data, tag.start),
+                                format!(
+                                    "<{}> opened on line {} is closed by </{}>",
+                                    name,
+                                    line_of(data, start),
+                                    tag.name
+                                ),
+                            ))
+                        }
+                        None => {
+                            return Some((
+                                line_of(data, tag.start),
+                                format!("</{}> closes an element that was never opened", tag.name),
+                            ))
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    match open.last() {
+        Some((name, start)) => Some((line_of(data, *start), format!("<{}> is never closed", name))),
+        None if !has_root => Some((
+            line_of(data, offset),
+            "document has no elements".to_string(),
+        )),
+        None => None,
+    }
+}
+
+// function to collect the ids of an xhtml document, targets of links with a fragment
+fn collect_ids(data: &str) -> HashSet<String> {
+    tokenize(data)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Tag(tag) if !tag.is_close() => tag
+                .attr("id")
+                .or_else(|| (tag.name == "a").then(|| tag.attr("name")).flatten()),
+            _ => None,
+        })
+        .collect()
+}
+
+// function to collect the links of an xhtml document to files inside the book (url and offset)
+fn collect_links(data: &str) -> Vec<(String, usize)> {
+    let mut links = Vec::new();
+    for token in tokenize(data) {
+        let Token::Tag(tag) = token else {
+            continue;
+        };
+        if tag.is_close() {
+            continue;
+        }
+        for (element, attribute) in LINK_ATTRIBUTES {
+            if tag.name != element {
+                continue;
+            }
+            let Some(url) = tag.attr(attribute) else {
+                continue;
+            };
+            // urls with a scheme (http:, mailto:, data:) point outside the book
+            let scheme = url
+                .find(':')
+                .is_some_and(|colon| !url[..colon].contains(['/', '?', '#']));
+            if !url.trim().is_empty() && !scheme {
+                links.push((url, tag.start));
+            }
+        }
+    }
+    links
+}
+
+// function to check the mimetype file, which must be the first and uncompressed entry
+fn check_mimetype(archive: &mut ZipArchive<File>, report: &mut ValidationReport) {
+    let first = archive
+        .by_index_raw(0)
+        .map(|entry| entry.name().to_string())
+        .unwrap_or_default();
+
+    let mut entry = match archive.by_name("mimetype") {
+        Ok(entry) => entry,
+        Err(_) => {
+            return report.add(
+                Severity::Error,
+                FindingKind::Mimetype,
+                None,
+                "the mimetype file is missing".to_string(),
+            )
+        }
+    };
+    if entry.compression() != CompressionMethod::Stored {
+        report.add(
+            Severity::Warning,
+            FindingKind::Mimetype,
+            Some("mimetype"),
+            "the mimetype file is compressed, strict readers can not detect the book".to_string(),
+        );
+    }
+    if first != "mimetype" {
+        report.add(
+            Severity::Warning,
+            FindingKind::Mimetype,
+            Some("mimetype"),
+            format!(
+                "the mimetype file is not the first file of the archive ({} is)",
+                first
+            ),
+        );
+    }
+
+    let mut content = String::new();
+    if entry.read_to_string(&mut content).is_err() || content.trim_end() != EPUB_MIMETYPE {
+        report.add(
+            Severity::Error,
+            FindingKind::Mimetype,
+            Some("mimetype"),
+            format!("the mimetype file does not contain {}", EPUB_MIMETYPE),
+        );
+    }
+}
+
+// function to find the package document from META-INF/container.xml, reporting problems
+// a broken container falls back to the first opf file of the archive, like the reader does
+fn check_container(
+    archive: &mut ZipArchive<File>,
+    entries: &HashSet<String>,
+    report: &mut ValidationReport,
+) -> Option<String> {
+    const CONTAINER: &str = "META-INF/container.xml";
+    let problem = match read_entry_string(archive, CONTAINER) {
+        Ok(container) => {
+            let rootfile = tokenize(&container)
+                .into_iter()
+                .find_map(|token| match token {
+                    Token::Tag(tag) if tag.name == "rootfile" && !tag.is_close() => {
+                        tag.attr("full-path")
+                    }
+                    _ => None,
+                });
+            match rootfile.map(|path| resolve_path("", &path)) {
+                Some(path) if entries.contains(&path) => return Some(path),
+                Some(path) => format!("the package document {} does not exist", path),
+                None => "no rootfile with a full-path is declared".to_string(),
+            }
+        }
+        Err(_) => "META-INF/container.xml is missing or unreadable".to_string(),
+    };
+    report.add(
+        Severity::Error,
+        FindingKind::Container,
+        Some(CONTAINER),
+        problem,
+    );
+
+    let fallback = entries
+        .iter()
+        .filter(|name| name.to_ascii_lowercase().ends_with(".opf"))
+        .min()
+        .cloned();
+    match &fallback {
+        Some(path) => report.add(
+            Severity::Info,
+            FindingKind::Container,
+            Some(path),
+            format!("{} was used as package document instead", path),
+        ),
+        None => report.add(
+            Severity::Error,
+            FindingKind::Package,
+            None,
+            "the archive contains no package document (.opf)".to_string(),
+        ),
+    }
+    fallback
+}
+
+// function to check metadata, manifest and spine of the package
+fn check_package(package: &Package, entries: &HashSet<String>, report: &mut ValidationReport) {
+    let opf = Some(package.opf_path.as_str());
+    if package.version.is_empty() {
+        report.add(
+            Severity::Error,
+            FindingKind::Package,
+            opf,
+            "the package document has no <package> element".to_string(),
+        );
+    }
+    for (missing, name) in [
+        (package.metadata.title.is_none(), "title"),
+        (package.metadata.identifier.is_none(), "identifier"),
+        (package.metadata.language.is_none(), "language"),
+    ] {
+        if missing {
+            report.add(
+                Severity::Warning,
+                FindingKind::Package,
+                opf,
+                format!("the metadata has no dc:{}", name),
+            );
+        }
+    }
+
+    let mut ids = HashSet::new();
+    let mut hrefs = HashSet::new();
+    for item in &package.manifest {
+        if !ids.insert(item.id.as_str()) {
+            report.add(
+                Severity::Error,
+                FindingKind::Manifest,
+                opf,
+                format!("the id {} is used by more than one manifest item", item.id),
+            );
+        }
+        if !hrefs.insert(item.href.as_str()) {
+            report.add(
+                Severity::Warning,
+                FindingKind::Manifest,
+                opf,
+                format!("{} is listed more than once in the manifest", item.href),
+            );
+        }
+        if !entries.contains(&item.href) {
+            report.add(
+                Severity::Error,
+                FindingKind::Manifest,
+                opf,
+                format!(
+                    "manifest item {} points to {}, which is not in the archive",
+                    item.id, item.href
+                ),
+            );
+        }
+    }
+    if package.nav_item().is_none() && package.ncx_item().is_none() {
+        report.add(
+            Severity::Warning,
+            FindingKind::Manifest,
+            opf,
+            "the book has no table of contents (nav document or ncx)".to_string(),
+        );
+    }
+
+    if package.spine.is_empty() {
+        report.add(
+            Severity::Error,
+            FindingKind::Spine,
+            opf,
+            "the spine is empty, the book has nothing to read".to_string(),
+        );
+    }
+    for spine_item in &package.spine {
+        match package.item_by_id(&spine_item.idref) {
+            None => report.add(
+                Severity::Error,
+                FindingKind::Spine,
+                opf,
+                format!(
+                    "the spine references {}, which is not in the manifest",
+                    spine_item.idref
+                ),
+            ),
+            Some(item)
+                if !matches!(
+                    item.media_type.as_str(),
+                    "application/xhtml+xml" | "text/html" | "image/svg+xml"
+                ) =>
+            {
+                report.add(
+                    Severity::Warning,
+                    FindingKind::Spine,
+                    opf,
+                    format!(
+                        "the spine item {} has the media type {}, which is not a document",
+                        item.href, item.media_type
+                    ),
+                )
+            }
+            Some(_) => {}
+        }
+    }
+
+    // files missing from the manifest are still served, but other readers may ignore them
+    let mut undeclared: Vec<&String> = entries
+        .iter()
+        .filter(|name| {
+            !name.ends_with('/')
+                && *name != "mimetype"
+                && !name.starts_with("META-INF/")
+                && **name != package.opf_path
+                && !hrefs.contains(name.as_str())
+        })
+        .collect();
+    undeclared.sort();
+    for name in undeclared {
+        report.add(
+            Severity::Info,
+            FindingKind::Manifest,
+            Some(name),
+            format!("{} is not listed in the manifest", name),
+        );
+    }
+}
+
+// function to check markup and internal links of the xhtml documents of the manifest
+fn check_documents(
+    archive: &mut ZipArchive<File>,
+    package: &Package,
+    entries: &HashSet<String>,
+    report: &mut ValidationReport,
+) {
+    let mut documents = Vec::new();
+    for item in &package.manifest {
+        if item.media_type != "application/xhtml+xml" || !entries.contains(&item.href) {
+            continue;
+        }
+        match read_entry_string(archive, &item.href) {
+            Ok(data) => documents.push((item.href.as_str(), data)),
+            Err(e) => report.add(
+                Severity::Error,
+                FindingKind::Markup,
+                Some(&item.href),
+                format!("the document can not be read: {}", e),
+            ),
+        }
+    }
+    let ids: HashMap<&str, HashSet<String>> = documents
+        .iter()
+        .map(|(href, data)| (*href, collect_ids(data)))
+        .collect();
+
+    for (href, data) in &documents {
+        if let Some((line, problem)) = check_markup(data, "html") {
+            report.add(
+                Severity::Error,
+                FindingKind::Markup,
+                Some(&format!("{}:{}", href, line)),
+                format!("invalid xhtml: {}", problem),
+            );
+        }
+
+        for (url, offset) in collect_links(data) {
+            let url = url.split('?').next().unwrap_or_default();
+            let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+            let target = match path {
+                "" => href.to_string(),
+                path => resolve_path(parent_dir(href), path),
+            };
+            let location = format!("{}:{}", href, line_of(data, offset));
+
+            if !entries.contains(&target) {
+                report.add(
+                    Severity::Warning,
+                    FindingKind::Link,
+                    Some(&location),
+                    format!("link to {} which is not in the archive", url),
+                );
+            } else if let (false, Some(target_ids)) =
+                (fragment.is_empty(), ids.get(target.as_str()))
+            {
+                if !target_ids.contains(fragment) {
+                    report.add(
+                        Severity::Warning,
+                        FindingKind::Link,
+                        Some(&location),
+                        format!("link to {} whose target id does not exist", url),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// function to validate the epub at path
+pub fn validate_epub(path: &str) -> Result<ValidationReport, ReaderError> {
+    let mut report = ValidationReport::default();
+    let mut archive = match open_archive(path) {
+        Ok(archive) => archive,
+        Err(ReaderError::ZipError(e)) => {
+            report.add(
+                Severity::Error,
+                FindingKind::Archive,
+                None,
+                format!("the file is not a readable zip archive: {}", e),
+            );
+            return Ok(report);
+        }
+        Err(e) => return Err(e),
+    };
+    let entries: HashSet<String> = archive.file_names().map(String::from).collect();
+
+    check_mimetype(&mut archive, &mut report);
+    let Some(opf_path) = check_container(&mut archive, &entries, &mut report) else {
+        return Ok(report);
+    };
+    let opf_data = match read_entry_string(&mut archive, &opf_path) {
+        Ok(data) => data,
+        Err(e) => {
+            report.add(
+                Severity::Error,
+                FindingKind::Package,
+                Some(&opf_path),
+                format!("the package document can not be read: {}", e),
+            );
+            return Ok(report);
+        }
+    };
+    if let Some((line, problem)) = check_markup(&opf_data, "package") {
+        report.add(
+            Severity::Error,
+            FindingKind::Package,
+            Some(&format!("{}:{}", opf_path, line)),
+            format!("invalid xml: {}", problem),
+        );
+    }
+
+    let package = parse_package(&opf_path, &opf_data);
+    check_package(&package, &entries, &mut report);
+    check_documents(&mut archive, &package, &entries, &mut report);
+    Ok(report)
+}
+
+// e_pub_validate command: checks an epub for structural problems and returns the findings when invoked
+#[tauri::command]
+pub fn e_pub_validate(path: &str) -> Result<ValidationReport, ReaderError> {
+    if BookFormat::from_path(path) != Some(BookFormat::Epub) {
+        return Err(ReaderError::ValidationError(format!(
+            "{} is not an epub file",
+            path
+        )));
+    }
+    validate_epub(path)
+}
@@ -0,0 +1,318 @@
+/*
+    reader module:
+    xml.rs contains a lightweight tokenizer for the xml/xhtml files found inside books
+*/
+
+// importing crates and modules
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// kind of a tag token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagKind {
+    Open,
+    Close,
+    SelfClosing,
+}
+
+// Tag struct (names are lowercased, element names are stripped of their namespace prefix)
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub kind: TagKind,
+    pub attrs: HashMap<String, String>,
+    pub start: usize, // byte offset of '<' in the source
+    pub end: usize,   // byte offset just after '>' in the source
+}
+
+// implementations for Tag struct
+impl Tag {
+    // returns the (entity decoded) value of an attribute
+    pub fn attr(&self, name: &str) -> Option<String> {
+        self.attrs.get(name).map(|value| decode_entities(value))
+    }
+
+    // checks if a space separated attribute (like class or epub:type) contains the given token
+    pub fn attr_has(&self, name: &str, token: &str) -> bool {
+        self.attrs
+            .get(name)
+            .map(|value| value.split_whitespace().any(|t| t == token))
+            .unwrap_or(false)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.kind == TagKind::Open
+    }
+
+    pub fn is_close(&self) -> bool {
+        self.kind == TagKind::Close
+    }
+}
+
+// Token enum: either a tag or raw (still entity encoded) text
+#[derive(Debug, Clone)]
+pub enum Token<'a> {
+    Tag(Tag),
+    Text(&'a str),
+}
+
+// regex matching comments, cdata, declarations and tags
+// comments end the way html ends them: <!--> and <!---> are empty comments, --!> also closes a comment
+// and a comment without an end runs to the end of the data
+fn tag_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r#"(?s)<!--(?:-?>|.*?--!?>|.*)|<!\[CDATA\[(.*?)\]\]>|<[?!][^>]*>|<(/?)([A-Za-z_][\w:.-]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#,
+        )
+        .unwrap()
+    })
+}
+
+// regex matching attributes inside a tag
+fn attr_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r#"([A-Za-z_][\w:.-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>/]+))"#).unwrap()
+    })
+}
+
+// function to split xml/html data into tags and text
+pub fn tokenize(data: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut last = 0;
+
+    for caps in tag_regex().captures_iter(data) {
+        let whole = caps.get(0).unwrap();
+
+        // push the text between the previous tag and this one
+        if whole.start() > last {
+            tokens.push(Token::Text(&data[last..whole.start()]));
+        }
+        last = whole.end();
+
+        if let Some(cdata) = caps.get(1) {
+            tokens.push(Token::Text(cdata.as_str()));
+            continue;
+        }
+
+        // comments, doctypes and processing instructions have no name group
+        let Some(name) = caps.get(3) else { continue };
+
+        let raw_attrs = caps.get(4).map(|m| m.as_str()).unwrap_or_default();
+        let kind = if !caps[2].is_empty() {
+            TagKind::Close
+        } else if raw_attrs.trim_end().ends_with('/') {
+            TagKind::SelfClosing
+        } else {
+            TagKind::Open
+        };
+
+        tokens.push(Token::Tag(Tag {
+            name: local_name(name.as_str()),
+            kind,
+            attrs: parse_attrs(raw_attrs),
+            start: whole.start(),
+            end: whole.end(),
+        }));
+    }
+
+    if last < data.len() {
+        tokens.push(Token::Text(&data[last..]));
+    }
+
+    tokens
+}
+
+// function to strip namespace prefix and lowercase a tag name
+fn local_name(name: &str) -> String {
+    name.rsplit(':').next().unwrap_or(name).to_ascii_lowercase()
+}
+
+// function to parse attributes of a tag into a hashmap (keys are lowercased)
+fn parse_attrs(raw: &str) -> HashMap<String, String> {
+    attr_regex()
+        .captures_iter(raw)
+        .map(|caps| {
+            let value = caps
+                .get(2)
+                .or_else(|| caps.get(3))
+                .or_else(|| caps.get(4))
+                .map(|m| m.as_str())
+                .unwrap_or_default();
+            (caps[1].to_ascii_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+// function to decode xml/html character entities
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(pos) = rest.find('&') {
+        decoded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        // entities are short, so do not look too far for the closing ';'
+        let entity = rest
+            .char_indices()
+            .take(12)
+            .find(|(_, c)| *c == ';')
+            .and_then(|(end, _)| decode_entity(&rest[1..end]).map(|c| (end, c)));
+
+        match entity {
+            Some((end, c)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+// function to decode a single entity name (without '&' and ';')
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse::<u32>().ok()?,
+        };
+        return char::from_u32(code);
+    }
+
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "shy" => '\u{ad}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "deg" => '°',
+        "middot" => '·',
+        "bull" => '•',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "times" => '×',
+        "divide" => '÷',
+        "plusmn" => '±',
+        "sect" => '§',
+        "para" => '¶',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "agrave" => 'à',
+        "aacute" => 'á',
+        "uuml" => 'ü',
+        "ouml" => 'ö',
+        "auml" => 'ä',
+        "szlig" => 'ß',
+        "ccedil" => 'ç',
+        "ntilde" => 'ñ',
+        _ => return None,
+    };
+    Some(c)
+}
+
+// function to collapse runs of whitespace into single spaces
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// function to escape text for xml content and double quoted attributes
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// function to percent-decode an href (e.g. "chapter%201.xhtml")
+pub fn percent_decode(href: &str) -> String {
+    let bytes = href.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2]));
+            if let (Some(high), Some(low)) = hex {
+                decoded.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// function to get value of a hex digit
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
+
+// function to resolve an href relative to a directory inside an archive ("OEBPS/text" + "../images/a.png" -> "OEBPS/images/a.png")
+pub fn resolve_path(base_dir: &str, href: &str) -> String {
+    let href = percent_decode(href);
+    let mut parts: Vec<&str> = if href.starts_with('/') {
+        Vec::new()
+    } else {
+        base_dir.split('/').filter(|p| !p.is_empty()).collect()
+    };
+
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+// function to resolve an href which may carry a fragment ("chapter.xhtml#sec1")
+pub fn resolve_href(base_dir: &str, href: &str) -> String {
+    match href.split_once('#') {
+        Some((path, fragment)) if !path.is_empty() => {
+            format!("{}#{}", resolve_path(base_dir, path), fragment)
+        }
+        Some((_, fragment)) => format!("#{}", fragment),
+        None => resolve_path(base_dir, href),
+    }
+}
+
+// function to get the directory part of an archive path ("OEBPS/content.opf" -> "OEBPS")
+pub fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
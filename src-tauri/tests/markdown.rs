@@ -0,0 +1,32 @@
+/*
+    tests for the markdown renderer used for markdown documents
+*/
+
+// importing crates and modules
+use study_app_lib::reader::markdown::render_markdown;
+
+#[test]
+fn renders_empty_list_items() {
+    for (source, list, items) in [
+        ("-", "<ul>", 1),
+        ("1.", "<ol>", 1),
+        ("- a\n-", "<ul>", 2),
+        ("1. a\n2.\n\n", "<ol>", 2),
+        ("- a\n-\n\n\ntext", "<ul>", 2),
+    ] {
+        let (html, _) = render_markdown(source);
+        assert!(html.starts_with(list), "{:?} renders as {:?}", source, html);
+        assert_eq!(
+            html.matches("<li>").count(),
+            items,
+            "{:?} renders as {:?}",
+            source,
+            html
+        );
+    }
+
+    // blank lines after the last item are not part of it
+    let (html, _) = render_markdown("- a\n- b\n\n\ntext");
+    assert!(html.contains("<li>b</li>"), "{}", html);
+    assert!(html.ends_with("<p>text</p>\n"), "{}", html);
+}
@@ -0,0 +1,132 @@
+/*
+    tests for pdf text extraction, run over the sample pdfs in tests/fixtures/pdf
+*/
+
+// importing crates and modules
+use std::path::PathBuf;
+use study_app_lib::reader::pdf_text::{extract_pages, PageText, TextLine};
+
+// function to get the path of a sample pdf
+fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/pdf")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+// function to find the line with the given text on a page
+fn line<'a>(page: &'a PageText, text: &str) -> &'a TextLine {
+    page.lines
+        .iter()
+        .find(|line| line.text == text)
+        .unwrap_or_else(|| panic!("{:?} not found in {:#?}", text, page.lines))
+}
+
+// function to compare positions, which are computed in floating point
+fn assert_near(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 0.01,
+        "{} is not {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn extracts_lines_in_reading_order() {
+    let pages = extract_pages(&fixture("text.pdf"), None, None).unwrap();
+    assert_eq!(pages.len(), 1);
+
+    let page = &pages[0];
+    assert_eq!(page.page, 1);
+    assert_near(page.width, 612.0);
+    assert_near(page.height, 792.0);
+    assert_eq!(
+        page.text,
+        "Sample Title\nThe first line of text.\nThe second line of text.\nSplit words"
+    );
+
+    // positions are in points from the top left corner, y is the top of the line
+    let title = line(page, "Sample Title");
+    assert_near(title.x, 72.0);
+    assert_near(title.y, 792.0 - 700.0 - 24.0);
+    assert_near(title.font_size, 24.0);
+
+    let first = line(page, "The first line of text.");
+    let second = line(page, "The second line of text.");
+    assert_near(first.x, 72.0);
+    assert_near(first.y, 792.0 - 650.0 - 12.0);
+    assert_near(second.y - first.y, 14.0);
+    assert!(first.width > 0.0);
+
+    // spans drawn apart are joined with a space
+    let split = line(page, "Split words");
+    assert_near(split.x, 72.0);
+    assert_near(split.y, 792.0 - 600.0 - 12.0);
+}
+
+#[test]
+fn measures_positions_from_the_media_box_corner() {
+    // the media box is [100 200 712 992], text is drawn in page space
+    let pages = extract_pages(&fixture("offset.pdf"), None, None).unwrap();
+    let page = &pages[0];
+    assert_near(page.width, 612.0);
+    assert_near(page.height, 792.0);
+
+    let top = line(page, "Offset media box");
+    assert_near(top.x, 172.0 - 100.0);
+    assert_near(top.y, 992.0 - 892.0 - 12.0);
+
+    let bottom = line(page, "Bottom right");
+    assert_near(bottom.x, 400.0 - 100.0);
+    assert_near(bottom.y, 992.0 - 300.0 - 12.0);
+}
+
+#[test]
+fn returns_empty_pages_without_text() {
+    let pages = extract_pages(&fixture("no_text.pdf"), None, None).unwrap();
+    assert_eq!(pages.len(), 2);
+    assert!(pages[0].lines.is_empty());
+    assert_eq!(pages[0].text, "");
+    assert_eq!(pages[1].text, "Second page");
+
+    // page ranges are 1 based and inclusive
+    let pages = extract_pages(&fixture("no_text.pdf"), Some(2), Some(2)).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].page, 2);
+}
+
+#[test]
+fn reads_two_column_pages_column_by_column() {
+    let pages = extract_pages(&fixture("two_columns.pdf"), None, None).unwrap();
+    let column = |name: &str| {
+        (1..=6)
+            .map(|row| format!("{} column line {}", name, row))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    // columns are read one after the other, separated like paragraphs
+    assert_eq!(
+        pages[0].text,
+        format!(
+            "Two Column Title\n{}\n\n{}",
+            column("Left"),
+            column("Right")
+        )
+    );
+
+    // lines of both columns at the same height are not joined
+    let left = line(&pages[0], "Left column line 1");
+    let right = line(&pages[0], "Right column line 1");
+    assert_near(left.y, right.y);
+    assert_near(right.x, 330.0);
+}
+
+#[test]
+fn decrypts_pdfs_with_an_empty_user_password() {
+    // rc4 encrypted with an owner password only, like pdfs that restrict printing or copying
+    let pages = extract_pages(&fixture("encrypted.pdf"), None, None).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].text, "Encrypted text");
+}
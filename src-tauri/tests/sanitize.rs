@@ -0,0 +1,265 @@
+/*
+    tests for the sanitizer of epub and html content, run over the malicious samples in tests/fixtures/sanitize
+*/
+
+// importing crates and modules
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use study_app_lib::reader::epub::read_package;
+use study_app_lib::reader::sanitize::{sanitize_css, sanitize_markup, sanitize_resource};
+use study_app_lib::reader::xml::{tokenize, Token};
+use zip::ZipArchive;
+
+// function to get the path of a sample file
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/sanitize")
+        .join(name)
+}
+
+// function to sanitize a sample file as the book protocol would
+fn sanitized(name: &str) -> String {
+    let data = std::fs::read(fixture(name)).unwrap();
+    String::from_utf8(sanitize_resource(name, None, data)).unwrap()
+}
+
+// function to check that none of the given pieces are left in the sanitized output, ignoring case
+fn assert_removed(output: &str, pieces: &[&str]) {
+    let lower = output.to_ascii_lowercase();
+    for piece in pieces {
+        assert!(
+            !lower.contains(&piece.to_ascii_lowercase()),
+            "{:?} is left in:\n{}",
+            piece,
+            output
+        );
+    }
+}
+
+// function to check that no tag of the sanitized output runs script, escaped text may still mention handlers
+fn assert_inert(output: &str) {
+    for token in tokenize(output) {
+        let Token::Tag(tag) = token else { continue };
+        assert!(tag.name != "script", "script left in:\n{}", output);
+        for (name, value) in &tag.attrs {
+            assert!(
+                !name.starts_with("on"),
+                "{} left on {} in:\n{}",
+                name,
+                tag.name,
+                output
+            );
+            assert!(
+                !value.to_ascii_lowercase().contains("script:"),
+                "{} left on {} in:\n{}",
+                value,
+                tag.name,
+                output
+            );
+        }
+    }
+}
+
+#[test]
+fn removes_scripts_and_embedded_content() {
+    let output = sanitized("scripts.html");
+    assert_removed(
+        &output,
+        &[
+            "<script",
+            "alert(",
+            "document.write",
+            "<iframe",
+            "<object",
+            "<embed",
+            "<base",
+            "evil.example",
+        ],
+    );
+    assert!(output.contains(r#"<p id="keep">Kept paragraph</p>"#));
+    assert!(output.contains("<p>End</p>"));
+}
+
+#[test]
+fn removes_event_handlers() {
+    let output = sanitized("handlers.xhtml");
+    assert_removed(
+        &output,
+        &[
+            "onload",
+            "onclick",
+            "onerror",
+            "onmouseover",
+            "onfocus",
+            "alert(",
+        ],
+    );
+    assert!(output.contains(r#"<p  class="keep">Click</p>"#));
+    assert!(output.contains(r#"src="images/cover.png""#));
+    assert!(output.contains(r#"title="safe""#));
+    assert!(output.contains(r##"<a href="#note""##));
+}
+
+#[test]
+fn removes_script_and_encoded_urls() {
+    let output = sanitized("urls.html");
+    assert_removed(
+        &output,
+        &[
+            "javascript:",
+            "jav&#x61;script",
+            "&#106;avascript",
+            "java&#9;script",
+            "vbscript:",
+            "data:text/html",
+            "evil.example",
+        ],
+    );
+    assert!(output.contains(r##"href="chapter2.html#start""##));
+    assert!(output.contains(r#"href="https://example.org/""#));
+    assert!(output.contains(r#"src="images/local.png""#));
+}
+
+#[test]
+fn removes_remote_css() {
+    let output = sanitized("css.html");
+    assert_removed(&output, &["evil.example", "https://", "http://"]);
+    assert!(output.contains(r#"href="styles/local.css""#));
+    assert!(output.contains(r#"@import "styles/fonts.css";"#));
+    assert!(output.contains("url(images/paper.png)"));
+    assert!(output.contains(r#"style="color: red""#));
+
+    let css = sanitize_css("a { background: url(  \"//evil.example/a.png\" ) } b { color: blue }");
+    assert_removed(&css, &["evil.example"]);
+    assert!(css.contains("b { color: blue }"));
+}
+
+#[test]
+fn removes_meta_refresh() {
+    let output = sanitized("meta.html");
+    assert_removed(&output, &["refresh", "evil.example", "javascript:"]);
+    assert!(output.contains(r#"<meta charset="utf-8">"#));
+    assert!(output.contains(r#"<meta name="viewport""#));
+}
+
+#[test]
+fn removes_svg_animations_of_links_and_handlers() {
+    let output = sanitized("animation.svg");
+    assert_removed(
+        &output,
+        &["javascript:", "onbegin", "alert(", "evil.example"],
+    );
+    assert!(output.contains(r#"<animate attributeName="opacity""#));
+    assert!(output.contains(r#"<set attributeName="fill" to="blue" "#));
+    assert!(output.contains(r##"<a xlink:href="#start">"##));
+}
+
+#[test]
+fn does_not_let_comments_hide_markup() {
+    let output = sanitized("comments.html");
+    assert_inert(&output);
+    assert_removed(
+        &output,
+        &["<!--", "<![cdata[", "</style><img", "<img src=x onerror"],
+    );
+    assert!(output.contains("<style>\\3c /style>\\3c img"));
+    assert!(output.contains("p > em { color: green }"));
+    assert!(output.contains("<p>a</p>"));
+    assert!(output.contains("<p>b</p>"));
+    assert!(output.contains("<p>e</p>"));
+
+    // the comment ends html parsers use
+    for source in [
+        "<!--><img src=x onerror=alert(1)>-->",
+        "<!---><img src=x onerror=alert(1)>-->",
+        "<!-- a --!><img src=x onerror=alert(1)>-->",
+    ] {
+        let output = sanitize_markup(source);
+        assert_removed(&output, &["onerror"]);
+        assert!(output.contains("<img src=x >"), "{}", output);
+    }
+}
+
+#[test]
+fn keeps_markup_inside_raw_text_elements_as_text() {
+    for element in [
+        "style", "noscript", "textarea", "title", "xmp", "noembed", "noframes",
+    ] {
+        let source = format!(
+            r#"<{0}><p title="</{0}><img src=x onerror=alert(1)>"></p></{0}><p>after</p>"#,
+            element
+        );
+        let output = sanitize_markup(&source);
+        assert_inert(&output);
+        assert_removed(
+            &output,
+            &[&format!("</{}><img", element), "<img src=x onerror"],
+        );
+
+        // an html parser ends the element at its first closing tag, which must be the real one
+        let closing = format!("</{}>", element);
+        assert_eq!(output.matches(&closing).count(), 1, "{}", output);
+        assert!(
+            output.ends_with(&format!("{}<p>after</p>", closing)),
+            "{}",
+            output
+        );
+    }
+
+    let output = sanitize_markup(r#"<plaintext><p title="<img src=x onerror=alert(1)>">"#);
+    assert_removed(&output, &["<img src=x onerror"]);
+}
+
+#[test]
+fn sanitizes_every_file_of_an_epub() {
+    let mut archive = ZipArchive::new(File::open(fixture("malicious.epub")).unwrap()).unwrap();
+    let package = read_package(&mut archive).unwrap();
+    let mut checked = 0;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).unwrap();
+        let name = entry.name().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+
+        let media_type = package
+            .item_by_href(&name)
+            .map(|item| item.media_type.as_str());
+        let output = String::from_utf8(sanitize_resource(&name, media_type, data)).unwrap();
+        if name.starts_with("OEBPS/") && !name.ends_with(".opf") {
+            assert_inert(&output);
+            assert_removed(&output, &["evil.example", "refresh", "<img src=x onerror"]);
+            checked += 1;
+        }
+    }
+    assert_eq!(checked, 4);
+}
+
+#[test]
+fn sanitizes_files_of_unknown_or_mismatched_types() {
+    let script = b"<html><body><script>alert(1)</script><p>text</p></body></html>".to_vec();
+    for (path, media_type) in [
+        ("OEBPS/chapter.xml", None),
+        ("OEBPS/chapter", None),
+        ("OEBPS/chapter", Some("application/xhtml+xml")),
+        ("OEBPS/chapter.dat", Some("application/x-unknown")),
+        ("OEBPS/image.png", Some("application/xhtml+xml")),
+        ("OEBPS/chapter.html", Some("image/png")),
+    ] {
+        let output =
+            String::from_utf8(sanitize_resource(path, media_type, script.clone())).unwrap();
+        assert_removed(&output, &["<script", "alert("]);
+        assert!(output.contains("<p>text</p>"), "{}: {}", path, output);
+    }
+
+    // images and fonts are returned as they are
+    let image = vec![0x89, b'P', b'N', b'G', 0xff, 0xfe, b'<'];
+    assert_eq!(
+        sanitize_resource("OEBPS/image.png", None, image.clone()),
+        image
+    );
+    assert_eq!(
+        sanitize_resource("OEBPS/image", Some("image/png"), image.clone()),
+        image
+    );
+}
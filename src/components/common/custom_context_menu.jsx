@@ -8,6 +8,35 @@ import {
 } from "./active_state_context";
 
 import { writeText, readText } from '@tauri-apps/plugin-clipboard-manager';
+import { invoke } from "@tauri-apps/api/core";
+import { message } from "@tauri-apps/plugin-dialog";
+
+// function to look up the selected word in the installed dictionaries and show its definitions
+const defineSelection = async (selectedText) => {
+  try {
+    const entries = await invoke("dictionary_lookup", { word: selectedText }); // invoke dictionary_lookup
+    if (!entries.length) {
+      await message(`No definition found for "${selectedText.trim()}".`, {
+        title: "Dictionary",
+      });
+      return;
+    }
+
+    // exact matches are listed first, other matches are marked as suggestions
+    const definitions = entries
+      .slice(0, 5)
+      .map(
+        (entry) =>
+          `${entry.word} (${entry.dictionary})${
+            entry.match_kind === "Exact" ? "" : " - suggestion"
+          }\n${entry.definition}`
+      )
+      .join("\n\n");
+    await message(definitions, { title: entries[0].word });
+  } catch (error) {
+    await message(String(error), { title: "Dictionary", kind: "error" });
+  }
+};
 
 // context menu when a text is selected (but is non editable)
 export const TextSelection = ({ position, selection, setContextMenu }) => {
@@ -44,6 +73,17 @@ export const TextSelection = ({ position, selection, setContextMenu }) => {
         >
           Copy
         </li>
+        <li
+          className="cursor-pointer px-4 py-2 text-sm hover:bg-gray-700 rounded"
+          onClick={() => {
+            // define the selection with the offline dictionaries
+            defineSelection(selectedText);
+            setContextMenu(null);
+            selection.removeAllRanges?.();
+          }}
+        >
+          Define
+        </li>
         <li
           className="cursor-pointer px-4 py-2 text-sm hover:bg-gray-700 rounded"
           onClick={() => {
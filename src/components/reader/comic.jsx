@@ -0,0 +1,162 @@
+/*
+  comic reader component, shows the images of cbz archives one page at a time
+*/
+
+import React, { useEffect, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+const ComicViewer = ({ setFileName, setFilePath, filePath, bookId, url }) => {
+  const [pages, setPages] = useState([]);
+  const [pageNumber, setPageNumber] = useState(null);
+  const [thumbnailsOpen, setThumbnailsOpen] = useState(false);
+  const documentRef = useRef(null);
+
+  // load the page list and the last read page
+  useEffect(() => {
+    const load = async () => {
+      try {
+        const list = await invoke("comic_pages_list", { path: filePath });
+        const position = await invoke("reading_position_get", { bookId });
+        setPages(list);
+        setPageNumber(
+          position?.locator?.format === "Comic"
+            ? Math.min(position.locator.page, list.length)
+            : 1
+        );
+      } catch (error) {
+        console.error("Couldn't Load Comic: ", error);
+      }
+    };
+    load();
+  }, [filePath, bookId]);
+
+  // remember the page and scroll to top when turning pages
+  useEffect(() => {
+    if (!pageNumber || !pages.length) return;
+    documentRef.current?.scrollTo({ top: 0, behavior: "auto" });
+    invoke("reading_position_save", {
+      bookId,
+      locator: { format: "Comic", page: pageNumber, page_count: pages.length },
+    }).catch((error) => console.error("Couldn't Save Position: ", error));
+  }, [pageNumber]);
+
+  // change current page number
+  function changePage(offset) {
+    setPageNumber((prevPageNumber) => {
+      const newPageNumber = prevPageNumber + offset;
+      return newPageNumber > 0 && newPageNumber <= pages.length
+        ? newPageNumber
+        : prevPageNumber;
+    });
+  }
+
+  // adding keydown event listener to document
+  useEffect(() => {
+    function keydownListener(e) {
+      if (e.shiftKey || e.ctrlKey || e.altKey || e.metaKey) return;
+      if (e.key === "ArrowLeft") {
+        e.preventDefault();
+        changePage(-1);
+      } else if (e.key === "ArrowRight") {
+        e.preventDefault();
+        changePage(1);
+      }
+    }
+    document.addEventListener("keydown", keydownListener);
+    return () => document.removeEventListener("keydown", keydownListener);
+  }, [pages]);
+
+  return (
+    <div className="relative w-[calc(100%-2rem)] h-[calc(100%-2rem)] m-4 overflow-hidden inset-0 flex flex-col bg-gray-100 dark:bg-gray-900">
+      <div className="h-20 flex items-center justify-between p-4 bg-gray-300 dark:bg-gray-800 border-t border-gray-300 dark:border-gray-700 rounded shadow mx-4 mt-2">
+        <div className="flex gap-2 items-center">
+          <button
+            className={`p-3 ${
+              thumbnailsOpen ? "dark:bg-gray-600 bg-gray-50" : ""
+            } dark:hover:bg-gray-700 hover:bg-gray-400 dark:active:bg-gray-600 active:bg-gray-50 rounded-full transition-background duration-150`}
+            onClick={() => setThumbnailsOpen(!thumbnailsOpen)}
+          >
+            <img
+              src="images/thumbnail-svgrepo-com.svg"
+              alt="Expand"
+              className="w-4 h-4"
+            />
+          </button>
+          <button
+            className="px-3 py-1 dark:hover:bg-gray-700 hover:bg-gray-400 rounded-full text-2xl text-[red]"
+            onClick={() => {
+              // close the open file
+              setFileName(null);
+              setFilePath(null);
+            }}
+          >
+            ×
+          </button>
+        </div>
+        <div className="flex gap-2 items-center">
+          <button
+            type="button"
+            className="p-2 bg-blue-400 dark:bg-blue-1100 rounded-full shadow-md hover:bg-blue-200 dark:hover:bg-gray-700 outline outline-1 disabled:opacity-50 disabled:cursor-not-allowed disabled:outline-0"
+            disabled={pageNumber <= 1}
+            onClick={() => changePage(-1)}
+          >
+            <img
+              src="images/arrow-up-svgrepo-com.svg"
+              className="w-8 h-8 -rotate-90"
+            />
+          </button>
+          <p
+            className="text-lg font-semibold px-4 py-2 bg-gray-200 dark:bg-gray-700 rounded-md shadow-sm"
+            style={{ fontFamily: "monospace" }}
+          >
+            {pageNumber ?? "-"} / {pages.length}
+          </p>
+          <button
+            type="button"
+            className="p-2 bg-blue-400 dark:bg-blue-1100 rounded-full shadow-md hover:bg-blue-200 dark:hover:bg-gray-700 outline outline-1 disabled:opacity-50 disabled:cursor-not-allowed disabled:outline-0"
+            disabled={pageNumber >= pages.length}
+            onClick={() => changePage(1)}
+          >
+            <img
+              src="images/arrow-up-svgrepo-com.svg"
+              className="w-8 h-8 rotate-90"
+            />
+          </button>
+        </div>
+      </div>
+      <div className="flex flex-1 overflow-hidden">
+        {thumbnailsOpen && (
+          <div className="w-48 overflow-y-auto">
+            {pages.map(({ page }) => (
+              <div
+                key={page}
+                onClick={() => setPageNumber(page)}
+                className="relative m-4 rounded bg-black/20 p-[10px] cursor-pointer"
+              >
+                <img
+                  src={`${url}/previews/${page}`}
+                  loading="lazy"
+                  className={`${pageNumber === page ? "" : "opacity-50"} mx-auto`}
+                />
+                <div className="text-sm absolute bottom-2 bg-black/80 pointer-events-none text-white px-2 py-1 rounded left-[50%] transform -translate-x-[50%]">
+                  {page}
+                </div>
+              </div>
+            ))}
+          </div>
+        )}
+        <div ref={documentRef} className="flex-1 overflow-auto flex justify-center p-4">
+          {pageNumber && (
+            <img
+              src={`${url}/pages/${pageNumber}`}
+              className="max-w-full h-fit shadow-lg"
+              alt={`Page ${pageNumber}`}
+            />
+          )}
+        </div>
+      </div>
+    </div>
+  );
+};
+
+export default ComicViewer;
@@ -210,10 +210,12 @@ const EpubViewer = ({ url, setFileName, setFilePath, ePubData }) => {
   function setRenderSelection(cfiRange, contents) {
     if (rendition.current && modeRef.current.startsWith("h")) { // if highlight mode is on then only apply highlight
       try {
+        const text = contents.window.getSelection()?.toString() ?? ""; // selected text, saved along with the highlight
         setHighlights((list) => {
           const newSelection = {
             color: modeRef.current.split("_")[1],
             cfiRange,
+            text,
           };
 
           // do not add same highlight range twice
@@ -314,7 +316,7 @@ const EpubViewer = ({ url, setFileName, setFilePath, ePubData }) => {
       // set the invokeOnClose command so that if close button is pressed, the highlights will be saved before closing
       setInvokeOnClose({
         name: "e_pub_highlight_save",
-        args: { uid: ePubData.uid, data: JSON.stringify(highlights) },
+        args: { uid: ePubData.uid, highlights },
       });
     }
     return () => {
@@ -475,7 +477,7 @@ const EpubViewer = ({ url, setFileName, setFilePath, ePubData }) => {
               onClick={async () => {
                 await invoke("e_pub_highlight_save", {
                   uid: ePubData.uid,
-                  data: JSON.stringify(highlights),
+                  highlights,
                 });
                 setLocation(null);
                 setFileName(null);
@@ -684,11 +686,12 @@ const EpubViewer = ({ url, setFileName, setFilePath, ePubData }) => {
           });
           ePubData.highlights?.forEach((selection) => {
             if (!selection) return;
-            const { color, cfiRange } = selection;
+            const { color, cfi_range: cfiRange, text } = selection;
             setHighlights((list) =>
               list.concat({
                 color,
                 cfiRange,
+                text,
               })
             );
           });
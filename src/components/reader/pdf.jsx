@@ -9,6 +9,7 @@ pdfjs.GlobalWorkerOptions.workerSrc = new URL(
 ).toString();
 
 import React, { useEffect, useRef, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { Document, Page, Thumbnail, Outline } from "react-pdf";
 import "react-pdf/dist/Page/TextLayer.css";
 import "react-pdf/dist/Page/AnnotationLayer.css";
@@ -17,7 +18,73 @@ import "./pdf.css";
 
 import { TextSelection } from "../common/custom_context_menu";
 
-const PdfViewer = ({ setFileName, setFilePath, url }) => {
+// pdf.js only fetches the parts of the file it needs, so large pdfs are never loaded at once
+const DOCUMENT_OPTIONS = { disableAutoFetch: true, disableStream: true };
+
+// function to fetch a byte range of the pdf, the book: protocol may answer with less than asked
+const fetchRange = async (url, begin, end) => {
+  const response = await fetch(url, {
+    headers: { Range: `bytes=${begin}-${end - 1}` },
+  });
+  if (!response.ok) throw new Error(`Couldn't load pdf: ${response.status}`);
+  const length = Number(response.headers.get("Content-Range")?.split("/")[1]);
+  return { data: new Uint8Array(await response.arrayBuffer()), length };
+};
+
+// function to fetch a byte range in as many requests as needed, pdf.js wants it in one piece
+const fetchWholeRange = async (url, begin, end) => {
+  const data = new Uint8Array(end - begin);
+  let position = begin;
+  while (position < end) {
+    const part = await fetchRange(url, position, end);
+    if (!part.data.length) throw new Error("Couldn't load pdf: empty response");
+    data.set(part.data, position - begin);
+    position += part.data.length;
+  }
+  return data;
+};
+
+// width the first page is rendered at when it is used as cover, the largest thumbnail is 480px wide
+const COVER_WIDTH = 480;
+
+// function to save the first page as cover of pdfs without a cover image, e.g. pdfs with only text
+const saveCover = async (pdf, bookId) => {
+  try {
+    if (await invoke("book_cover_generate", { bookId })) return;
+
+    const page = await pdf.getPage(1);
+    const viewport = page.getViewport({
+      scale: COVER_WIDTH / page.getViewport({ scale: 1 }).width,
+    });
+    const canvas = document.createElement("canvas");
+    canvas.width = Math.floor(viewport.width);
+    canvas.height = Math.floor(viewport.height);
+    await page.render({ canvasContext: canvas.getContext("2d"), viewport }).promise;
+
+    const blob = await new Promise((resolve) => canvas.toBlob(resolve, "image/jpeg", 0.85));
+    const data = Array.from(new Uint8Array(await blob.arrayBuffer()));
+    await invoke("book_cover_save", { bookId, data });
+  } catch (error) {
+    console.error("Couldn't Save Cover: ", error);
+  }
+};
+
+// function to open the pdf through a range transport, starting with its first chunk
+const openRangeTransport = async (url) => {
+  const first = await fetchRange(url, 0, 1024 * 1024);
+  const transport = new pdfjs.PDFDataRangeTransport(
+    first.length || first.data.length,
+    first.data
+  );
+  transport.requestDataRange = (begin, end) => {
+    fetchWholeRange(url, begin, end)
+      .then((data) => transport.onDataRange(begin, data))
+      .catch((error) => console.error("Couldn't Load Pdf: ", error));
+  };
+  return transport;
+};
+
+const PdfViewer = ({ setFileName, setFilePath, url, bookId }) => {
   const [numPages, setNumPages] = useState(null);
   const [pageNumber, setPageNumber] = useState(1);
   const currentPageNumberInputRef = useRef(null);
@@ -31,10 +98,24 @@ const PdfViewer = ({ setFileName, setFilePath, url }) => {
   const [rotate, setRotate] = useState(0);
   const thumbnailDivRef = useRef(null);
   const [ContextMenu, setContextMenu] = useState(null);
+  const [file, setFile] = useState(null);
+
+  // open the pdf by ranges whenever the url changes
+  useEffect(() => {
+    let cancelled = false;
+    setFile(null);
+    openRangeTransport(url)
+      .then((range) => !cancelled && setFile({ range }))
+      .catch((error) => console.error("Couldn't Load Pdf: ", error));
+    return () => {
+      cancelled = true;
+    };
+  }, [url]);
 
   // set pdfref on loading
   function onDocumentLoadSuccess(pdf) {
     pdfRef.current = pdf;
+    saveCover(pdf, bookId);
   }
 
   // set outlines if any
@@ -543,12 +624,14 @@ const PdfViewer = ({ setFileName, setFilePath, url }) => {
         >
           <Document
             inputRef={documentRef}
-            file={url}
+            file={file}
+            options={DOCUMENT_OPTIONS}
             onLoadSuccess={onDocumentLoadSuccess}
             className={`h-full w-full relative ${
               scale >= 1 ? "" : "flex justify-center"
             }`}
             loading={<img src="images/loading.svg" className="w-[50%] h-full"/>}
+            noData={<img src="images/loading.svg" className="w-[50%] h-full"/>}
             onItemClick={onItemClick}
           >
             <Page
@@ -1,19 +1,29 @@
 /*
- Reader component, currently supports pdf and epub
+ Reader component, currently supports pdf, epub, documents (txt, markdown, html, docx, odt) and comics (cbz)
 */
 
 import { useEffect, useState, lazy } from "react";
 import { useActiveState } from "../common/active_state_context";
 import { invoke, convertFileSrc } from "@tauri-apps/api/core";
-import { confirm } from "@tauri-apps/plugin-dialog";
+import { confirm, message } from "@tauri-apps/plugin-dialog";
 const EpubViewer = lazy(() => import("./epub"));
 const PdfViewer = lazy(() => import("./pdf"));
+const ComicViewer = lazy(() => import("./comic"));
+
+// documents are served by the backend as single chapter epubs, so they open in the epub viewer
+const EPUB_EXTENSIONS = [
+  ".epub", ".txt", ".md", ".markdown", ".html", ".htm", ".xhtml", ".docx", ".odt",
+];
+const isEpubLike = (name) =>
+  EPUB_EXTENSIONS.some((extension) => name?.toLowerCase().endsWith(extension));
+const isComic = (name) => /\.cb[zr]$/i.test(name ?? "");
 
 export default function Reader() {
   const { currentPage } = useActiveState();
 
   const [FilePath, setFilePath] = useState(null);
   const [FileName, setFileName] = useState(null);
+  const [BookId, setBookId] = useState(null); // library id, used to load the book through the book: protocol
   const [ePubData, set_ePubData] = useState(null);
 
   // function to get epub data (highlights and titile)
@@ -27,20 +37,48 @@ export default function Reader() {
       set_ePubData(data);
     } catch (error) {
       console.error("Couldn't Load Highlights: ", error);
+      if (path.toLowerCase().endsWith(".epub")) showProblems(path);
+    }
+  };
+
+  // function to tell the user why an epub could not be opened
+  const showProblems = async (path) => {
+    try {
+      const report = await invoke("e_pub_validate", { path }); // invoke e_pub_validate
+      const problems = report.findings
+        .filter((finding) => finding.severity === "Error")
+        .slice(0, 10)
+        .map((finding) => `• ${finding.message}`);
+      if (problems.length) {
+        await message(problems.join("\n"), {
+          title: "This book has problems",
+          kind: "error",
+        });
+      }
+    } catch (error) {
+      console.error("Couldn't Validate Book: ", error);
     }
   };
 
   // get epub data if filepath/name is changed and the new one is an epub
   useEffect(() => {
-    if (FilePath && FileName?.toLowerCase().endsWith(".epub")) {
+    if (FilePath && isEpubLike(FileName)) {
       get_ePubData(FilePath);
     }
   }, [FilePath, FileName]);
 
-  // function to load file path and name
+  // function to open a library book, only library books are served to the viewers
+  // epubs are opened as a directory, so epub.js requests the files inside the archive one by one
+  const openFile = (response) => {
+    setBookId(response.book.id);
+    setFilePath(response.path);
+  };
+
+  // function to load file path and name, the picked file is added to the library by the dialog command
   const loadFile = async () => {
     try {
       const response = await invoke("open_file_dialog"); // invokes the command to open a file select dialog
+      if (!response) return; // dialog was closed without picking a file
       setFileName(response.name);
 
       // loading very large files is causing application to crash so added this check
@@ -49,11 +87,12 @@ export default function Reader() {
           "Loading files larger than 100MB may cause the application to crash. Wanna continue?"
         );
         if (continueLoading) {
-          setFilePath(response.path);
+          openFile(response);
         }
-      } else setFilePath(response.path);
+      } else openFile(response);
     } catch (error) {
-      console.error("Failed to open PDF:", error);
+      // the picked file could not be added to the library
+      await message(String(error), { title: "Couldn't open this file", kind: "error" });
     }
   };
   
@@ -91,16 +130,29 @@ export default function Reader() {
             </div>
           </div>
         )}
-        {FilePath && FileName?.toLowerCase().endsWith(".pdf") && (
+        {FilePath && BookId && FileName?.toLowerCase().endsWith(".pdf") && (
           <PdfViewer
-            url={convertFileSrc(FilePath)}
+            url={convertFileSrc(BookId, "book")}
+            bookId={BookId}
+            setFileName={setFileName}
+            setFilePath={setFilePath}
+          />
+        )}
+        {FilePath && BookId && isComic(FileName) && (
+          <ComicViewer
+            url={convertFileSrc(BookId, "book")}
+            filePath={FilePath}
+            bookId={BookId}
             setFileName={setFileName}
             setFilePath={setFilePath}
           />
         )}
-        {FilePath && FileName?.toLowerCase().endsWith(".epub") && ePubData && (
+        {FilePath &&
+          BookId &&
+          isEpubLike(FileName) &&
+          ePubData && (
           <EpubViewer
-            url={convertFileSrc(FilePath)}
+            url={convertFileSrc(BookId, "book") + "/"}
             setFileName={setFileName}
             setFilePath={setFilePath}
             ePubData={ePubData}